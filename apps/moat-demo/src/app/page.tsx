@@ -23,8 +23,9 @@ import {
   commitBatch,
   fetchBatchCommit,
   fetchBatchCommitsByCreator,
+  getCommitmentDomain,
 } from "@/lib/solana/moatClient";
-import { DEVNET_RPC } from "@/lib/solana/constants";
+import { DEVNET_RPC, MOAT_ENTRY_ID } from "@/lib/solana/constants";
 
 type PhantomProvider = {
   isPhantom?: boolean;
@@ -616,13 +617,15 @@ export default function Page() {
       const adapterResult = await adapter.execute(plan);
       setAdapterStatus(adapterResult);
 
+      const domain = await getCommitmentDomain();
       const leaves = await buildLeafHashes(
+        domain,
         walletAddress,
         batchId,
         cleanedRecipients,
       );
       const merkleRoot = await computeMerkleRoot(leaves);
-      const memoHash = await hashMemo(memo);
+      const memoHash = await hashMemo(memo, domain);
 
       setMerkleRootHex(toHex(merkleRoot));
       setMemoHashHex(toHex(memoHash));
@@ -637,10 +640,13 @@ export default function Page() {
       const sendCommit = async (activeProvider: AnchorProvider) =>
         commitBatch(
           activeProvider,
+          MOAT_ENTRY_ID,
           new BN(batchId.toString()),
           merkleRoot,
           memoHash,
           kindNumber,
+          domain,
+          leaves.length,
         );
 
       const finalize = async (activeProvider: AnchorProvider) => {
@@ -728,13 +734,15 @@ export default function Page() {
     }
     try {
       setVerificationMessage(null);
+      const domain = await getCommitmentDomain();
       const leaves = await buildLeafHashes(
+        domain,
         walletAddress,
         commitSnapshot.batchId,
         commitSnapshot.recipients,
       );
       const merkleRoot = await computeMerkleRoot(leaves);
-      const memoHash = await hashMemo(commitSnapshot.memo);
+      const memoHash = await hashMemo(commitSnapshot.memo, domain);
       const merkleHex = toHex(merkleRoot);
       const memoHex = toHex(memoHash);
 
@@ -756,13 +764,15 @@ export default function Page() {
     }
     try {
       setOnchainVerificationMessage(null);
+      const domain = await getCommitmentDomain();
       const leaves = await buildLeafHashes(
+        domain,
         walletAddress,
         commitSnapshot.batchId,
         commitSnapshot.recipients,
       );
       const merkleRoot = await computeMerkleRoot(leaves);
-      const memoHash = await hashMemo(commitSnapshot.memo);
+      const memoHash = await hashMemo(commitSnapshot.memo, domain);
       const batch = await fetchBatchCommit(
         provider,
         provider.wallet.publicKey,
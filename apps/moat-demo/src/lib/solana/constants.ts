@@ -1,2 +1,4 @@
 export const MOAT_PROGRAM_ID = "FTVm8gDndxnocAqi4sr53BnaymMXxESNGHgTzagJX2qY";
 export const DEVNET_RPC = "https://api.devnet.solana.com";
+// Registry entry the demo commits its batches to.
+export const MOAT_ENTRY_ID = 0;
@@ -4,6 +4,13 @@ import {
   PublicKey,
   SystemProgram,
 } from "@solana/web3.js";
+import bs58 from "bs58";
+import { Buffer } from "buffer";
+import {
+  MOAT_LEAF_SCHEMA_CUSTOM,
+  MOAT_ROOT_VERSION,
+  computeCommitmentDomain,
+} from "@moat/router";
 import idlJson from "./moat_registry.idl.json";
 import { MOAT_PROGRAM_ID } from "./constants";
 
@@ -12,8 +19,15 @@ type IdlWithAddress = Idl & {
   metadata?: { address?: string };
 };
 
-const BATCH_SEED = new TextEncoder().encode("batch");
+const COMMIT_SEED = new TextEncoder().encode("commit");
+const ENTRY_SEED = new TextEncoder().encode("entry");
+const ROLE_SEED = new TextEncoder().encode("role");
+const STATE_SEED = new TextEncoder().encode("state");
 const HASH_BYTES = 32;
+const HASH_ALG_SHA256 = 0;
+// `BatchCommit` layout: discriminator, creator, then batch_id.
+const BATCH_CREATOR_OFFSET = 8;
+const BATCH_ID_OFFSET = BATCH_CREATOR_OFFSET + 32;
 
 type BatchCommitSnake = {
   creator: PublicKey;
@@ -88,18 +102,60 @@ const assertHashLength = (value: Uint8Array, label: string) => {
   }
 };
 
-const deriveBatchPda = (
+const toU32 = (value: number): number => {
+  if (!Number.isInteger(value) || value < 0 || value > 0xffffffff) {
+    throw new Error("Entry id must be an integer between 0 and 2^32 - 1");
+  }
+  return value;
+};
+
+// Depth of the tree `computeMerkleRoot` builds over `leafCount` leaves.
+const treeDepth = (leafCount: number) => {
+  if (!Number.isInteger(leafCount) || leafCount < 1) {
+    throw new Error("A commit needs at least one leaf");
+  }
+  let depth = 0;
+  while (2 ** depth < leafCount) depth += 1;
+  return depth;
+};
+
+const deriveEntryPda = (
   programId: PublicKey,
-  creator: PublicKey,
-  batchId: BN,
+  state: PublicKey,
+  entryId: number,
 ) => {
-  const batchSeed = batchId.toArrayLike(Uint8Array, "le", 8);
+  const idSeed = new Uint8Array(4);
+  new DataView(idSeed.buffer).setUint32(0, toU32(entryId), true);
   return PublicKey.findProgramAddressSync(
-    [BATCH_SEED, creator.toBytes(), batchSeed],
+    [ENTRY_SEED, state.toBytes(), idSeed],
     programId,
   )[0];
 };
 
+// Commits live at the entry's next sequence number, not at `batch_id`.
+const deriveCommitPda = (programId: PublicKey, entry: PublicKey, seq: BN) =>
+  PublicKey.findProgramAddressSync(
+    [COMMIT_SEED, entry.toBytes(), seq.toArrayLike(Uint8Array, "le", 8)],
+    programId,
+  )[0];
+
+const deriveRolePda = (
+  programId: PublicKey,
+  state: PublicKey,
+  holder: PublicKey,
+) =>
+  PublicKey.findProgramAddressSync(
+    [ROLE_SEED, state.toBytes(), holder.toBytes()],
+    programId,
+  )[0];
+
+export const deriveStatePda = (
+  programId: PublicKey = new PublicKey(MOAT_PROGRAM_ID),
+) => PublicKey.findProgramAddressSync([STATE_SEED], programId)[0];
+
+export const getCommitmentDomain = () =>
+  computeCommitmentDomain(MOAT_PROGRAM_ID, deriveStatePda().toBase58());
+
 type BatchCommitAccountNamespace = {
   fetch: (address: PublicKey) => Promise<unknown>;
   all: (
@@ -107,6 +163,11 @@ type BatchCommitAccountNamespace = {
   ) => Promise<{ publicKey: PublicKey; account: unknown }[]>;
 };
 
+type RegistryEntryAccount = {
+  commitCount: BN;
+  lastCommitRoot: number[];
+};
+
 const getBatchCommitNamespace = (program: Program<Idl>) => {
   const accountNamespace = program.account as unknown as {
     batchCommit: BatchCommitAccountNamespace;
@@ -114,12 +175,26 @@ const getBatchCommitNamespace = (program: Program<Idl>) => {
   return accountNamespace.batchCommit;
 };
 
+const fetchRegistryEntry = async (program: Program<Idl>, entry: PublicKey) => {
+  const accountNamespace = program.account as unknown as {
+    registryEntry: {
+      fetch: (address: PublicKey) => Promise<RegistryEntryAccount>;
+    };
+  };
+  return accountNamespace.registryEntry.fetch(entry);
+};
+
+// Commits an unsharded SHA-256 root built by `computeMerkleRoot` to registry
+// entry `entryId`, chained onto the entry's previous root.
 export async function commitBatch(
   provider: AnchorProvider,
+  entryId: number,
   batchId: BN,
   merkleRoot: Uint8Array,
   memoHash: Uint8Array,
   kindNumber: number,
+  domain: Uint8Array,
+  leafCount: number,
 ): Promise<string> {
   const program = getProgram(provider);
   const creator = provider.wallet.publicKey;
@@ -127,19 +202,82 @@ export async function commitBatch(
 
   assertHashLength(merkleRoot, "Merkle root");
   assertHashLength(memoHash, "Memo hash");
+  assertHashLength(domain, "Commitment domain");
 
-  const batchPda = deriveBatchPda(program.programId, creator, batchId);
+  const state = deriveStatePda(program.programId);
+  const entry = deriveEntryPda(program.programId, state, entryId);
+  const { commitCount, lastCommitRoot } = await fetchRegistryEntry(
+    program,
+    entry,
+  );
+  const batch = deriveCommitPda(program.programId, entry, commitCount);
+  const rolePda = deriveRolePda(program.programId, state, creator);
+  const role = (await provider.connection.getAccountInfo(rolePda))
+    ? rolePda
+    : null;
 
   return await program.methods
-    .commitBatch(batchId, Array.from(merkleRoot), Array.from(memoHash), kind)
-    .accounts({
+    .commitBatch(batchId, {
+      merkleRoot: Array.from(merkleRoot),
+      memoHash: Array.from(memoHash),
+      kind,
+      commitKind: 0,
+      domain: Array.from(domain),
+      prevRoot: lastCommitRoot,
+      validUntil: new BN(0),
+      leafCount: new BN(leafCount),
+      depth: treeDepth(leafCount),
+      shardRoots: [],
+      shardLeafCounts: [],
+      hashAlg: HASH_ALG_SHA256,
+      rootVersion: MOAT_ROOT_VERSION,
+      sortedPairs: false,
+      leafSchema: MOAT_LEAF_SCHEMA_CUSTOM,
+      claimMint: PublicKey.default,
+      payloadUri: "",
+      validityProof: Buffer.alloc(0),
+    })
+    .accountsPartial({
       creator,
-      batch: batchPda,
+      state,
+      role,
+      session: null,
+      nonce: null,
+      entry,
+      batch,
+      recentCommits: null,
+      commitLog: null,
+      epochSummary: null,
+      commitKind: null,
+      cosigners: null,
+      instructions: null,
+      commitVerifier: null,
       systemProgram: SystemProgram.programId,
     })
     .rpc();
 }
 
+const findBatchCommit = async (
+  program: Program<Idl>,
+  creator: PublicKey,
+  batchId: BN,
+) => {
+  const filters: GetProgramAccountsFilter[] = [
+    { memcmp: { offset: BATCH_CREATOR_OFFSET, bytes: creator.toBase58() } },
+    {
+      memcmp: {
+        offset: BATCH_ID_OFFSET,
+        bytes: bs58.encode(batchId.toArrayLike(Uint8Array, "le", 8)),
+      },
+    },
+  ];
+  const [found] = await getBatchCommitNamespace(program).all(filters);
+  if (!found) {
+    throw new Error(`No commit for batch ${batchId.toString()}`);
+  }
+  return found;
+};
+
 export async function fetchBatchCommit(
   provider: AnchorProvider,
   creator: PublicKey,
@@ -154,9 +292,11 @@ export async function fetchBatchCommit(
   createdAt: BN;
 }> {
   const program = getProgram(provider);
-  const batchPda = deriveBatchPda(program.programId, creator, batchId);
-  const accountNamespace = getBatchCommitNamespace(program);
-  const account = await accountNamespace.fetch(batchPda);
+  const { publicKey: batchPda, account } = await findBatchCommit(
+    program,
+    creator,
+    batchId,
+  );
   if (!isBatchCommit(account)) {
     throw new Error("Unexpected batch commit shape");
   }
@@ -197,7 +337,7 @@ export async function fetchBatchCommitsByCreator(
   const program = getProgram(provider);
   const accountNamespace = getBatchCommitNamespace(program);
   const filters: GetProgramAccountsFilter[] = [
-    { memcmp: { offset: 8, bytes: creator.toBase58() } },
+    { memcmp: { offset: BATCH_CREATOR_OFFSET, bytes: creator.toBase58() } },
   ];
   const accounts = await accountNamespace.all(filters);
 
@@ -23,6 +23,14 @@ export type CommitmentMemo = {
   createdAt: string;
 };
 
+export type RegistryEntryLeaf = {
+  id: number;
+  targetProgram: string;
+  kind: number;
+  tags: bigint;
+  expiresAt: bigint;
+};
+
 export type CommitmentPlan = {
   id: string;
   creator: string;
@@ -39,10 +47,33 @@ export function validatePlan(plan: PayoutPlan) {
 
 export {
   MOAT_LEAF_PREFIX,
+  MOAT_DOMAIN_TAG,
+  MOAT_DOMAIN_VERSION,
+  MOAT_CLAIM_LEAF_TAG,
+  MOAT_KEY_LEAF_TAG,
+  MOAT_ENTRY_LEAF_TAG,
+  MOAT_ALLOWLIST_LEAF_TAG,
+  MOAT_ROOT_VERSION,
+  MOAT_ROOT_VERSION_V2,
+  MOAT_LEAF_SCHEMA_CUSTOM,
+  MOAT_LEAF_SCHEMA_CLAIM_V1,
+  MOAT_LEAF_SCHEMA_SORTED_KEY_V1,
+  MOAT_LEAF_SCHEMA_ENTRY_V1,
+  MOAT_LEAF_SCHEMA_ALLOWLIST_V1,
+  computeCommitmentDomain,
+  buildCosignMessage,
+  buildRootAttestationMessage,
   buildLeafHashes,
   hashLeaf,
+  hashClaimLeaf,
+  hashKeyLeaf,
+  hashEntryLeaf,
+  hashAllowlistLeaf,
+  buildSortedKeyLeaves,
+  encodeMemo,
   hashMemo,
   computeMerkleRoot,
+  buildMerkleProof,
   toHex,
 } from "./merkle";
 
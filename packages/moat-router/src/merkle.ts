@@ -1,7 +1,28 @@
 import bs58 from "bs58";
-import type { CommitmentMemo, CommitmentRecipient } from "./index";
+import type {
+  CommitmentMemo,
+  CommitmentRecipient,
+  RegistryEntryLeaf,
+} from "./index";
 
 export const MOAT_LEAF_PREFIX = "moat:v1";
+export const MOAT_DOMAIN_TAG = "moat:domain";
+export const MOAT_DOMAIN_VERSION = 1;
+export const MOAT_CLAIM_LEAF_TAG = "moat:claim";
+export const MOAT_KEY_LEAF_TAG = "moat:key";
+export const MOAT_ENTRY_LEAF_TAG = "moat:entry";
+export const MOAT_ALLOWLIST_LEAF_TAG = "moat:allow";
+// Leaf encoding produced by this module; pass as `rootVersion` when committing.
+export const MOAT_ROOT_VERSION = 1;
+// Leaves hashed under 0x00 and internal nodes under 0x01, so an internal node
+// can't be passed off as a leaf. Build with `{ rootVersion }` set to this.
+export const MOAT_ROOT_VERSION_V2 = 2;
+// `leafSchema` values a commit records; mirror `moat_merkle::LEAF_SCHEMA_*`.
+export const MOAT_LEAF_SCHEMA_CUSTOM = 0;
+export const MOAT_LEAF_SCHEMA_CLAIM_V1 = 1;
+export const MOAT_LEAF_SCHEMA_SORTED_KEY_V1 = 2;
+export const MOAT_LEAF_SCHEMA_ENTRY_V1 = 3;
+export const MOAT_LEAF_SCHEMA_ALLOWLIST_V1 = 4;
 
 const textEncoder = new TextEncoder();
 const MAX_U32 = 0xffffffff;
@@ -63,16 +84,75 @@ export const sha256 = async (data: Uint8Array) => {
   return new Uint8Array(digest);
 };
 
-export const hashMemo = async (memo: CommitmentMemo) => {
-  const payload = JSON.stringify({
-    title: memo.title ?? "",
-    note: memo.note ?? "",
-    createdAt: memo.createdAt,
-  });
-  return sha256(textEncoder.encode(payload));
+const decodePubkey = (value: string, label: string) => {
+  const bytes = bs58.decode(value);
+  if (bytes.length !== 32) {
+    throw new Error(`${label} pubkey must be 32 bytes`);
+  }
+  return bytes;
+};
+
+const assertDomain = (domain: Uint8Array) => {
+  if (domain.length !== 32) {
+    throw new Error("Commitment domain must be 32 bytes");
+  }
+};
+
+// Mirrors `commitment_domain` in the registry program: roots and memo hashes
+// built for another program, registry or scheme version will not match ours.
+export const computeCommitmentDomain = async (
+  programId: string,
+  registry: string,
+) =>
+  sha256(
+    concatBytes(
+      textEncoder.encode(MOAT_DOMAIN_TAG),
+      Uint8Array.of(MOAT_DOMAIN_VERSION),
+      decodePubkey(programId, "Program"),
+      decodePubkey(registry, "Registry"),
+    ),
+  );
+
+// Message the registry's root attestor signs: registry || entry || root || seq u64le.
+export const buildRootAttestationMessage = (
+  registry: string,
+  entry: string,
+  merkleRoot: Uint8Array,
+  seq: bigint,
+) => {
+  if (merkleRoot.length !== 32) {
+    throw new Error("Merkle root must be 32 bytes");
+  }
+  return concatBytes(
+    decodePubkey(registry, "Registry"),
+    decodePubkey(entry, "Entry"),
+    merkleRoot,
+    toU64Le(seq),
+  );
+};
+
+// What commit co-signers sign with Ed25519 before the commit is submitted; the
+// same tuple as the root attestation, so a vouched root can't move to another
+// entry or sequence number.
+export const buildCosignMessage = buildRootAttestationMessage;
+
+// The bytes `commit_batch_with_memo` stores inline; `hashMemo` hashes the same bytes.
+export const encodeMemo = (memo: CommitmentMemo) =>
+  textEncoder.encode(
+    JSON.stringify({
+      title: memo.title ?? "",
+      note: memo.note ?? "",
+      createdAt: memo.createdAt,
+    }),
+  );
+
+export const hashMemo = async (memo: CommitmentMemo, domain: Uint8Array) => {
+  assertDomain(domain);
+  return sha256(concatBytes(domain, encodeMemo(memo)));
 };
 
 type LeafInput = {
+  domain: Uint8Array;
   creator: string;
   batchId: bigint;
   index: number;
@@ -80,13 +160,12 @@ type LeafInput = {
 };
 
 export const hashLeaf = async (input: LeafInput) => {
-  const creatorBytes = bs58.decode(input.creator);
-  if (creatorBytes.length !== 32) {
-    throw new Error("Creator pubkey must be 32 bytes");
-  }
+  assertDomain(input.domain);
+  const creatorBytes = decodePubkey(input.creator, "Creator");
 
   const data = concatBytes(
     textEncoder.encode(MOAT_LEAF_PREFIX),
+    input.domain,
     creatorBytes,
     toU64Le(input.batchId),
     toU32Le(input.index),
@@ -98,32 +177,202 @@ export const hashLeaf = async (input: LeafInput) => {
   return sha256(data);
 };
 
+// ClaimLeafV1, redeemed on-chain by `claim`:
+// sha256(tag || domain || borsh({ wallet, amount })), i.e. claimant || amount u64le.
+export const hashClaimLeaf = async (
+  domain: Uint8Array,
+  claimant: string,
+  amount: bigint,
+) => {
+  assertDomain(domain);
+  return sha256(
+    concatBytes(
+      textEncoder.encode(MOAT_CLAIM_LEAF_TAG),
+      domain,
+      decodePubkey(claimant, "Claimant"),
+      toU64Le(amount),
+    ),
+  );
+};
+
 export const buildLeafHashes = async (
+  domain: Uint8Array,
   creator: string,
   batchId: bigint,
   recipients: CommitmentRecipient[],
 ) =>
   Promise.all(
     recipients.map((recipient, index) =>
-      hashLeaf({ creator, batchId, index, recipient }),
+      hashLeaf({ domain, creator, batchId, index, recipient }),
     ),
   );
 
-export const computeMerkleRoot = async (leaves: Uint8Array[]) => {
+// KeyLeafV1: sha256(tag || domain || key).
+export const hashKeyLeaf = async (domain: Uint8Array, key: Uint8Array) => {
+  assertDomain(domain);
+  if (key.length !== 32) {
+    throw new Error("Key must be 32 bytes");
+  }
+  return sha256(
+    concatBytes(textEncoder.encode(MOAT_KEY_LEAF_TAG), domain, key),
+  );
+};
+
+// EntryLeafV1, a leaf of the root `commit_registry_root` publishes; build that
+// tree from active entries in id order with `{ rootVersion: 2 }`.
+export const hashEntryLeaf = async (
+  domain: Uint8Array,
+  entry: RegistryEntryLeaf,
+) => {
+  assertDomain(domain);
+  if (!Number.isInteger(entry.kind) || entry.kind < 0 || entry.kind > 255) {
+    throw new Error("Kind must be an integer between 0 and 255");
+  }
+  return sha256(
+    concatBytes(
+      textEncoder.encode(MOAT_ENTRY_LEAF_TAG),
+      domain,
+      toU32Le(entry.id),
+      decodePubkey(entry.targetProgram, "Target program"),
+      Uint8Array.of(entry.kind),
+      toU64Le(entry.tags),
+      toU64Le(entry.expiresAt),
+    ),
+  );
+};
+
+// AllowlistLeafV1, checked by `register_entry_with_proof`; build the allowlist
+// with `{ sortedPairs: true, rootVersion: 2 }`.
+export const hashAllowlistLeaf = async (
+  domain: Uint8Array,
+  wallet: string,
+  targetProgram: string,
+) => {
+  assertDomain(domain);
+  return sha256(
+    concatBytes(
+      textEncoder.encode(MOAT_ALLOWLIST_LEAF_TAG),
+      domain,
+      decodePubkey(wallet, "Wallet"),
+      decodePubkey(targetProgram, "Target program"),
+    ),
+  );
+};
+
+// Leaves for a sorted-key commit: keys deduplicated and in ascending order, so
+// `verify_exclusion` can prove a key absent from its two neighbors.
+export const buildSortedKeyLeaves = async (
+  domain: Uint8Array,
+  keys: Uint8Array[],
+) => {
+  const sorted = keys
+    .slice()
+    .sort(compareBytes)
+    .filter((key, i, all) => i === 0 || compareBytes(all[i - 1], key) !== 0);
+  const leaves = await Promise.all(
+    sorted.map((key) => hashKeyLeaf(domain, key)),
+  );
+  return { keys: sorted, leaves };
+};
+
+type MerkleOptions = {
+  // Hash each pair in byte order (OpenZeppelin style); must match the commit's
+  // `sortedPairs` flag.
+  sortedPairs?: boolean;
+  // Must match the commit's `rootVersion`; defaults to `MOAT_ROOT_VERSION`.
+  rootVersion?: number;
+};
+
+const LEAF_NODE_PREFIX = Uint8Array.of(0x00);
+const INTERNAL_NODE_PREFIX = Uint8Array.of(0x01);
+
+const compareBytes = (a: Uint8Array, b: Uint8Array) => {
+  for (let i = 0; i < Math.min(a.length, b.length); i += 1) {
+    if (a[i] !== b[i]) return a[i] - b[i];
+  }
+  return a.length - b.length;
+};
+
+const treeLayout = (options: MerkleOptions) => {
+  const rootVersion = options.rootVersion ?? MOAT_ROOT_VERSION;
+  if (
+    rootVersion !== MOAT_ROOT_VERSION &&
+    rootVersion !== MOAT_ROOT_VERSION_V2
+  ) {
+    throw new Error(`Unsupported root version ${rootVersion}`);
+  }
+  return {
+    sortedPairs: options.sortedPairs ?? false,
+    nodePrefixes: rootVersion === MOAT_ROOT_VERSION_V2,
+  };
+};
+
+type TreeLayout = ReturnType<typeof treeLayout>;
+
+const leafLevel = (leaves: Uint8Array[], layout: TreeLayout) =>
+  layout.nodePrefixes
+    ? Promise.all(
+        leaves.map((leaf) => sha256(concatBytes(LEAF_NODE_PREFIX, leaf))),
+      )
+    : Promise.resolve(leaves.slice());
+
+const hashPair = (left: Uint8Array, right: Uint8Array, layout: TreeLayout) => {
+  const [a, b] =
+    layout.sortedPairs && compareBytes(right, left) < 0
+      ? [right, left]
+      : [left, right];
+  return layout.nodePrefixes
+    ? sha256(concatBytes(INTERNAL_NODE_PREFIX, a, b))
+    : sha256(concatBytes(a, b));
+};
+
+const nextLevel = async (level: Uint8Array[], layout: TreeLayout) => {
+  const next: Uint8Array[] = [];
+  for (let i = 0; i < level.length; i += 2) {
+    const left = level[i];
+    const right = level[i + 1] ?? left;
+    next.push(await hashPair(left, right, layout));
+  }
+  return next;
+};
+
+export const computeMerkleRoot = async (
+  leaves: Uint8Array[],
+  options: MerkleOptions = {},
+) => {
   if (leaves.length === 0) {
     throw new Error("At least one leaf is required to build the Merkle root");
   }
 
-  let level = leaves.slice();
+  const layout = treeLayout(options);
+  let level = await leafLevel(leaves, layout);
   while (level.length > 1) {
-    const next: Uint8Array[] = [];
-    for (let i = 0; i < level.length; i += 2) {
-      const left = level[i];
-      const right = level[i + 1] ?? left;
-      next.push(await sha256(concatBytes(left, right)));
-    }
-    level = next;
+    level = await nextLevel(level, layout);
   }
 
   return level[0];
 };
+
+// Sibling path for `leaves[index]` under `computeMerkleRoot`, in the order
+// `verify_inclusion` consumes it.
+export const buildMerkleProof = async (
+  leaves: Uint8Array[],
+  index: number,
+  options: MerkleOptions = {},
+) => {
+  if (!Number.isInteger(index) || index < 0 || index >= leaves.length) {
+    throw new Error("Leaf index is out of range");
+  }
+
+  const layout = treeLayout(options);
+  const proof: Uint8Array[] = [];
+  let level = await leafLevel(leaves, layout);
+  let position = index;
+  while (level.length > 1) {
+    proof.push(level[position ^ 1] ?? level[position]);
+    level = await nextLevel(level, layout);
+    position >>= 1;
+  }
+
+  return proof;
+};
@@ -0,0 +1,685 @@
+//! Leaf hashing, node hashing, root building and proof verification for moat
+//! commitments. The registry program verifies with exactly these functions, so
+//! a tree built with them is a tree the program accepts.
+//!
+//! Hashing is supplied through [`Hasher`]; the program backs it with syscalls,
+//! and the `sha2` feature provides a software SHA-256 for clients.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use borsh::{BorshDeserialize, BorshSerialize};
+
+pub type Hash = [u8; 32];
+
+pub const LEAF_PREFIX: &[u8] = b"moat:v1";
+pub const CLAIM_LEAF_TAG: &[u8] = b"moat:claim";
+pub const KEY_LEAF_TAG: &[u8] = b"moat:key";
+pub const ENTRY_LEAF_TAG: &[u8] = b"moat:entry";
+pub const ALLOWLIST_LEAF_TAG: &[u8] = b"moat:allow";
+pub const DOMAIN_TAG: &[u8] = b"moat:domain";
+pub const DOMAIN_VERSION: u8 = 1;
+
+/// Leaf encodings recorded on a commit as its `leaf_schema`. Custom leaves are
+/// whatever the integrator hashes; the others are [`LeafSchema`] types here.
+pub const LEAF_SCHEMA_CUSTOM: u16 = 0;
+pub const LEAF_SCHEMA_CLAIM_V1: u16 = 1;
+pub const LEAF_SCHEMA_SORTED_KEY_V1: u16 = 2;
+pub const LEAF_SCHEMA_ENTRY_V1: u16 = 3;
+pub const LEAF_SCHEMA_ALLOWLIST_V1: u16 = 4;
+
+/// Root versions a commit records. V2 hashes leaves and internal nodes under
+/// distinct one-byte prefixes, so an internal node can't pass as a leaf.
+pub const ROOT_VERSION_V1: u16 = 1;
+pub const ROOT_VERSION_V2: u16 = 2;
+pub const LEAF_NODE_PREFIX: &[u8] = &[0x00];
+pub const INTERNAL_NODE_PREFIX: &[u8] = &[0x01];
+
+pub trait Hasher {
+    fn hashv(&self, vals: &[&[u8]]) -> Hash;
+}
+
+#[cfg(feature = "sha2")]
+pub struct Sha256;
+
+#[cfg(feature = "sha2")]
+impl Hasher for Sha256 {
+    fn hashv(&self, vals: &[&[u8]]) -> Hash {
+        use sha2::Digest;
+
+        let mut hasher = sha2::Sha256::new();
+        for val in vals {
+            hasher.update(val);
+        }
+        hasher.finalize().into()
+    }
+}
+
+/// Prefix mixed into every leaf and memo preimage committed to `registry` of
+/// `program_id`. Always SHA-256, whatever the commit's hash algorithm.
+pub fn commitment_domain(
+    sha256: &impl Hasher,
+    program_id: &[u8; 32],
+    registry: &[u8; 32],
+) -> Hash {
+    sha256.hashv(&[DOMAIN_TAG, &[DOMAIN_VERSION], program_id, registry])
+}
+
+/// Payout leaf: `prefix || domain || creator || batch_id u64le || index u32le ||
+/// recipient || amount || asset`, the strings as CAIP-10/CAIP-19 UTF-8.
+#[allow(clippy::too_many_arguments)]
+pub fn hash_leaf(
+    hasher: &impl Hasher,
+    domain: &Hash,
+    creator: &[u8; 32],
+    batch_id: u64,
+    index: u32,
+    recipient: &str,
+    amount: &str,
+    asset: &str,
+) -> Hash {
+    hasher.hashv(&[
+        LEAF_PREFIX,
+        domain,
+        creator,
+        &batch_id.to_le_bytes(),
+        &index.to_le_bytes(),
+        recipient.as_bytes(),
+        amount.as_bytes(),
+        asset.as_bytes(),
+    ])
+}
+
+/// A borsh-encoded leaf layout, hashed as `TAG || domain || borsh(leaf)`.
+pub trait LeafSchema: BorshSerialize {
+    const SCHEMA_ID: u16;
+    const TAG: &'static [u8];
+}
+
+/// Leaf redeemed by the program's `claim`.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClaimLeafV1 {
+    pub wallet: [u8; 32],
+    pub amount: u64,
+}
+
+impl LeafSchema for ClaimLeafV1 {
+    const SCHEMA_ID: u16 = LEAF_SCHEMA_CLAIM_V1;
+    const TAG: &'static [u8] = CLAIM_LEAF_TAG;
+}
+
+/// Canonical bytes of `leaf`, as published alongside a commit's payload.
+pub fn encode_leaf<L: LeafSchema>(leaf: &L) -> Vec<u8> {
+    borsh::to_vec(leaf).expect("writing to a Vec cannot fail")
+}
+
+pub fn hash_schema_leaf<L: LeafSchema>(hasher: &impl Hasher, domain: &Hash, leaf: &L) -> Hash {
+    hasher.hashv(&[L::TAG, domain, &encode_leaf(leaf)])
+}
+
+/// Decodes `payload` as the borsh leaf of schema `schema_id` and hashes it.
+/// None for custom or unknown schemas, and for bytes that aren't exactly one
+/// such leaf.
+pub fn hash_raw_leaf(
+    hasher: &impl Hasher,
+    schema_id: u16,
+    domain: &Hash,
+    payload: &[u8],
+) -> Option<Hash> {
+    fn decode<L: LeafSchema + BorshDeserialize>(
+        hasher: &impl Hasher,
+        domain: &Hash,
+        payload: &[u8],
+    ) -> Option<Hash> {
+        let leaf = L::try_from_slice(payload).ok()?;
+        Some(hash_schema_leaf(hasher, domain, &leaf))
+    }
+
+    match schema_id {
+        LEAF_SCHEMA_CLAIM_V1 => decode::<ClaimLeafV1>(hasher, domain, payload),
+        LEAF_SCHEMA_SORTED_KEY_V1 => decode::<KeyLeafV1>(hasher, domain, payload),
+        LEAF_SCHEMA_ENTRY_V1 => decode::<EntryLeafV1>(hasher, domain, payload),
+        LEAF_SCHEMA_ALLOWLIST_V1 => decode::<AllowlistLeafV1>(hasher, domain, payload),
+        _ => None,
+    }
+}
+
+/// Leaf of a sorted set: a tree of these in strictly ascending `key` order can
+/// prove a key absent by showing the two adjacent leaves around it.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyLeafV1 {
+    pub key: [u8; 32],
+}
+
+impl LeafSchema for KeyLeafV1 {
+    const SCHEMA_ID: u16 = LEAF_SCHEMA_SORTED_KEY_V1;
+    const TAG: &'static [u8] = KEY_LEAF_TAG;
+}
+
+/// A registry entry as the registry root commits to it.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EntryLeafV1 {
+    pub id: u32,
+    pub target_program: [u8; 32],
+    pub kind: u8,
+    pub tags: u64,
+    pub expires_at: i64,
+}
+
+impl LeafSchema for EntryLeafV1 {
+    const SCHEMA_ID: u16 = LEAF_SCHEMA_ENTRY_V1;
+    const TAG: &'static [u8] = ENTRY_LEAF_TAG;
+}
+
+/// A wallet pre-approved to register `target_program`.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AllowlistLeafV1 {
+    pub wallet: [u8; 32],
+    pub target_program: [u8; 32],
+}
+
+impl LeafSchema for AllowlistLeafV1 {
+    const SCHEMA_ID: u16 = LEAF_SCHEMA_ALLOWLIST_V1;
+    const TAG: &'static [u8] = ALLOWLIST_LEAF_TAG;
+}
+
+/// [`ClaimLeafV1`] for `claimant`: `tag || domain || claimant || amount u64le`.
+pub fn claim_leaf(
+    hasher: &impl Hasher,
+    domain: &Hash,
+    claimant: &[u8; 32],
+    amount: u64,
+) -> Hash {
+    let leaf = ClaimLeafV1 {
+        wallet: *claimant,
+        amount,
+    };
+    hash_schema_leaf(hasher, domain, &leaf)
+}
+
+/// How a tree combines its nodes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Tree {
+    /// Hash each pair in byte order, as OpenZeppelin trees do.
+    pub sorted_pairs: bool,
+    /// Prefix leaves and internal nodes; see [`ROOT_VERSION_V2`].
+    pub node_prefixes: bool,
+}
+
+impl Tree {
+    /// Layout of a root built under `root_version`, or None for a version
+    /// this crate doesn't know.
+    pub fn for_root_version(root_version: u16, sorted_pairs: bool) -> Option<Self> {
+        let node_prefixes = match root_version {
+            ROOT_VERSION_V1 => false,
+            ROOT_VERSION_V2 => true,
+            _ => return None,
+        };
+        Some(Self {
+            sorted_pairs,
+            node_prefixes,
+        })
+    }
+
+    /// The bottom node standing for `leaf`.
+    #[inline]
+    pub fn leaf_node(&self, hasher: &impl Hasher, leaf: &Hash) -> Hash {
+        if self.node_prefixes {
+            hasher.hashv(&[LEAF_NODE_PREFIX, leaf])
+        } else {
+            *leaf
+        }
+    }
+
+    #[inline]
+    pub fn hash_pair(&self, hasher: &impl Hasher, left: &Hash, right: &Hash) -> Hash {
+        let (left, right) = if self.sorted_pairs && right < left {
+            (right, left)
+        } else {
+            (left, right)
+        };
+        if self.node_prefixes {
+            hasher.hashv(&[INTERNAL_NODE_PREFIX, left, right])
+        } else {
+            hasher.hashv(&[left, right])
+        }
+    }
+
+    fn next_level(&self, hasher: &impl Hasher, level: &[Hash]) -> Vec<Hash> {
+        level
+            .chunks(2)
+            .map(|pair| self.hash_pair(hasher, &pair[0], pair.get(1).unwrap_or(&pair[0])))
+            .collect()
+    }
+
+    fn leaf_level(&self, hasher: &impl Hasher, leaves: &[Hash]) -> Vec<Hash> {
+        leaves.iter().map(|leaf| self.leaf_node(hasher, leaf)).collect()
+    }
+}
+
+/// Pairs nodes left to right and carries an odd node up by pairing it with
+/// itself. Returns the root and the tree's depth, or None without leaves.
+pub fn compute_root(hasher: &impl Hasher, tree: Tree, leaves: &[Hash]) -> Option<(Hash, u8)> {
+    let mut level = tree.leaf_level(hasher, leaves);
+    let mut depth = 0u8;
+    while level.len() > 1 {
+        level = tree.next_level(hasher, &level);
+        depth += 1;
+    }
+    level.first().map(|root| (*root, depth))
+}
+
+/// Sibling path for `leaves[index]` under [`compute_root`], one node per level.
+pub fn build_proof(
+    hasher: &impl Hasher,
+    tree: Tree,
+    leaves: &[Hash],
+    index: usize,
+) -> Option<Vec<Hash>> {
+    if index >= leaves.len() {
+        return None;
+    }
+
+    let mut proof = Vec::new();
+    let mut level = tree.leaf_level(hasher, leaves);
+    let mut position = index;
+    while level.len() > 1 {
+        proof.push(*level.get(position ^ 1).unwrap_or(&level[position]));
+        level = tree.next_level(hasher, &level);
+        position >>= 1;
+    }
+    Some(proof)
+}
+
+/// Walks `proof` up from `leaf`. Unsorted trees take direction from `index`,
+/// which must then be consumed exactly; sorted trees ignore it. One hash per
+/// level and no allocation, so a depth-32 proof stays cheap on-chain.
+pub fn verify_proof(
+    hasher: &impl Hasher,
+    tree: Tree,
+    root: &Hash,
+    leaf: &Hash,
+    index: u64,
+    proof: &[Hash],
+) -> bool {
+    let mut node = tree.leaf_node(hasher, leaf);
+    let mut position = index;
+    for sibling in proof {
+        node = if tree.sorted_pairs || position & 1 == 0 {
+            tree.hash_pair(hasher, &node, sibling)
+        } else {
+            tree.hash_pair(hasher, sibling, &node)
+        };
+        position >>= 1;
+    }
+    (tree.sorted_pairs || position == 0) && node == *root
+}
+
+/// OpenZeppelin's processMultiProof: each flag hashes the next leaf or computed
+/// node with either another one (true) or the next proof node (false). Pairs
+/// are always sorted. Returns None for a proof whose shape doesn't add up.
+pub fn process_multiproof(
+    hasher: &impl Hasher,
+    tree: Tree,
+    leaves: &[Hash],
+    proof: &[Hash],
+    flags: &[bool],
+) -> Option<Hash> {
+    if leaves.is_empty() || leaves.len() + proof.len() != flags.len() + 1 {
+        return None;
+    }
+
+    let tree = Tree {
+        sorted_pairs: true,
+        ..tree
+    };
+    let leaves = tree.leaf_level(hasher, leaves);
+    let mut hashes: Vec<Hash> = Vec::with_capacity(flags.len());
+    let (mut leaf_pos, mut hash_pos, mut proof_pos) = (0, 0, 0);
+    for &flag in flags {
+        let mut next = || {
+            if leaf_pos < leaves.len() {
+                leaf_pos += 1;
+                Some(leaves[leaf_pos - 1])
+            } else {
+                hash_pos += 1;
+                hashes.get(hash_pos - 1).copied()
+            }
+        };
+        let a = next()?;
+        let b = if flag {
+            next()?
+        } else {
+            proof_pos += 1;
+            *proof.get(proof_pos - 1)?
+        };
+        hashes.push(tree.hash_pair(hasher, &a, &b));
+    }
+
+    match hashes.last() {
+        Some(root) => (proof_pos == proof.len()).then_some(*root),
+        None => leaves.first().copied(),
+    }
+}
+
+/// Adds the `leaf_count`th leaf to a tree kept as one pending left node per
+/// level. Returns false once `frontier` has no room for it.
+pub fn append_leaf(
+    hasher: &impl Hasher,
+    tree: Tree,
+    frontier: &mut [Hash],
+    leaf_count: u64,
+    leaf: &Hash,
+) -> bool {
+    let mut node = tree.leaf_node(hasher, leaf);
+    let mut index = leaf_count;
+    for slot in frontier.iter_mut() {
+        if index & 1 == 0 {
+            *slot = node;
+            return true;
+        }
+        node = tree.hash_pair(hasher, slot, &node);
+        index >>= 1;
+    }
+    false
+}
+
+/// The [`compute_root`] root and depth of the `leaf_count` leaves folded in by
+/// [`append_leaf`], or None without leaves.
+pub fn frontier_root(
+    hasher: &impl Hasher,
+    tree: Tree,
+    frontier: &[Hash],
+    leaf_count: u64,
+) -> Option<(Hash, u8)> {
+    if leaf_count == 0 {
+        return None;
+    }
+
+    // `carry` is the rightmost node of a level when it came from an unfinished
+    // pair below; an odd level's last node pairs with itself.
+    let mut carry: Option<Hash> = None;
+    let mut size = leaf_count;
+    let mut depth = 0u8;
+    while size > 1 {
+        let left = frontier.get(usize::from(depth))?;
+        carry = match (size & 1 == 1, carry) {
+            (true, Some(node)) => Some(tree.hash_pair(hasher, &node, &node)),
+            (true, None) => Some(tree.hash_pair(hasher, left, left)),
+            (false, Some(node)) => Some(tree.hash_pair(hasher, left, &node)),
+            (false, None) => None,
+        };
+        size = size.div_ceil(2);
+        depth += 1;
+    }
+    let root = carry.or_else(|| frontier.get(usize::from(depth)).copied())?;
+    Some((root, depth))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Expected values below were produced by packages/moat-router/src/merkle.ts,
+    // which clients build commits with, from the same inputs.
+    const PROGRAM: &str = "d6cd3cf192410117dc7c022565d23a530431d216140666339072189c4f692f03";
+    const REGISTRY: &str = "06ddf6e1d765a193d9cbe146ceeb79ac1cb485ed5f5b37913a8cf5857eff00a9";
+    const WALLET: &str = "0bbc0fc0bb47ca2f74c4112e94ab13cfa3c634e5dc17eacb03cd1a23cd7e787c";
+    const DOMAIN: &str = "616910dc60a1cc7f75e02d63fc66c03ea3af40c7167800e0891acee59f57ce78";
+
+    const LAYOUTS: [(Tree, &str); 4] = [
+        (
+            Tree {
+                sorted_pairs: false,
+                node_prefixes: false,
+            },
+            "f632ed650b4b2c467228719df716e74232b7293f997e481489bd172e73473a77",
+        ),
+        (
+            Tree {
+                sorted_pairs: true,
+                node_prefixes: false,
+            },
+            "3f8da9195401bea0e041048b7c772e105d19c40b943c9d21a9e3fc2b057886f7",
+        ),
+        (
+            Tree {
+                sorted_pairs: false,
+                node_prefixes: true,
+            },
+            "f6ab5bbc08c6028b7dd999a9fe1d057cce4d7d5fc7a47b18a17118eeda7fb8dc",
+        ),
+        (
+            Tree {
+                sorted_pairs: true,
+                node_prefixes: true,
+            },
+            "f7a82e7de18df0575cf4e4a93f8250d4de4e668df3b6cfe5b4e656b99bcdddd2",
+        ),
+    ];
+
+    fn hex(s: &str) -> Hash {
+        let mut out = [0u8; 32];
+        for (i, byte) in out.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[2 * i..2 * i + 2], 16).unwrap();
+        }
+        out
+    }
+
+    fn leaves(count: u8) -> Vec<Hash> {
+        (1..=count).map(|i| [i; 32]).collect()
+    }
+
+    #[test]
+    fn compute_root_matches_client() {
+        for (tree, root) in LAYOUTS {
+            assert_eq!(compute_root(&Sha256, tree, &leaves(5)), Some((hex(root), 3)));
+        }
+        assert_eq!(compute_root(&Sha256, Tree::default(), &[]), None);
+        assert_eq!(
+            compute_root(&Sha256, Tree::default(), &[[9; 32]]),
+            Some(([9; 32], 0))
+        );
+    }
+
+    #[test]
+    fn build_proof_matches_client() {
+        let v1 = Tree::for_root_version(ROOT_VERSION_V1, false).unwrap();
+        let v2 = Tree::for_root_version(ROOT_VERSION_V2, false).unwrap();
+        assert_eq!(
+            build_proof(&Sha256, v1, &leaves(5), 1).unwrap(),
+            [
+                hex("0101010101010101010101010101010101010101010101010101010101010101"),
+                hex("505a9c6ac70bdffa46248e2025483f9fe997a0e31ed25559e448b73b7e02b9bd"),
+                hex("53c78f30a0ee4e72fb8e56d75011cc1d2ec7c31bf54ebcd9357847426f3a374a"),
+            ]
+        );
+        // The odd last leaf is its own sibling.
+        assert_eq!(
+            build_proof(&Sha256, v2, &leaves(5), 4).unwrap(),
+            [
+                hex("f3ab555d06a67b08ab25039fdbe2a6fcb305c83bc165492ce81d3dea13ec1fbf"),
+                hex("89a6ad0ca94554047924c8af9b7d4ec785958f6085c5cf91e584b563d3c9e4f6"),
+                hex("3b3c0ce45d11517a54300a196b61497c4165150d72b7782a4548e3984da771b2"),
+            ]
+        );
+        assert_eq!(build_proof(&Sha256, v1, &leaves(5), 5), None);
+    }
+
+    #[test]
+    fn verify_proof_accepts_built_proofs() {
+        for count in 1..=9 {
+            let leaves = leaves(count);
+            for (tree, _) in LAYOUTS {
+                let (root, depth) = compute_root(&Sha256, tree, &leaves).unwrap();
+                for (index, leaf) in leaves.iter().enumerate() {
+                    let proof = build_proof(&Sha256, tree, &leaves, index).unwrap();
+                    assert_eq!(proof.len(), usize::from(depth));
+                    assert!(verify_proof(&Sha256, tree, &root, leaf, index as u64, &proof));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn verify_proof_rejects_tampering() {
+        let leaves = leaves(5);
+        let tree = Tree::for_root_version(ROOT_VERSION_V2, false).unwrap();
+        let (root, _) = compute_root(&Sha256, tree, &leaves).unwrap();
+        let proof = build_proof(&Sha256, tree, &leaves, 2).unwrap();
+
+        assert!(verify_proof(&Sha256, tree, &root, &leaves[2], 2, &proof));
+        assert!(!verify_proof(&Sha256, tree, &root, &leaves[3], 2, &proof));
+        assert!(!verify_proof(&Sha256, tree, &root, &leaves[2], 3, &proof));
+        // Index bits left over after the proof is spent mean a shorter path.
+        assert!(!verify_proof(&Sha256, tree, &root, &leaves[2], 2 + 8, &proof));
+        assert!(!verify_proof(&Sha256, tree, &root, &leaves[2], 2, &proof[..2]));
+
+        // An internal node can't stand in for a leaf once nodes are prefixed.
+        let node = tree.hash_pair(
+            &Sha256,
+            &tree.leaf_node(&Sha256, &leaves[0]),
+            &tree.leaf_node(&Sha256, &leaves[1]),
+        );
+        let proof = build_proof(&Sha256, tree, &leaves, 0).unwrap();
+        assert!(!verify_proof(&Sha256, tree, &root, &node, 0, &proof[1..]));
+    }
+
+    #[test]
+    fn sorted_proofs_ignore_index() {
+        let leaves = leaves(5);
+        let tree = Tree::for_root_version(ROOT_VERSION_V2, true).unwrap();
+        let (root, _) = compute_root(&Sha256, tree, &leaves).unwrap();
+        let proof = build_proof(&Sha256, tree, &leaves, 3).unwrap();
+        assert!(verify_proof(&Sha256, tree, &root, &leaves[3], 3, &proof));
+        assert!(verify_proof(&Sha256, tree, &root, &leaves[3], 0, &proof));
+    }
+
+    #[test]
+    fn process_multiproof_rebuilds_root() {
+        let leaves = leaves(4);
+        for version in [ROOT_VERSION_V1, ROOT_VERSION_V2] {
+            let tree = Tree::for_root_version(version, true).unwrap();
+            let (root, _) = compute_root(&Sha256, tree, &leaves).unwrap();
+            let nodes: Vec<Hash> = leaves
+                .iter()
+                .map(|leaf| tree.leaf_node(&Sha256, leaf))
+                .collect();
+            let right = tree.hash_pair(&Sha256, &nodes[2], &nodes[3]);
+
+            // Leaves 0 and 1 pair with each other, then with the proof node.
+            let proven = process_multiproof(&Sha256, tree, &leaves[..2], &[right], &[true, false]);
+            assert_eq!(proven, Some(root));
+
+            // Leaves 0 and 3: each takes its sibling from the proof.
+            let proven = process_multiproof(
+                &Sha256,
+                tree,
+                &[leaves[0], leaves[3]],
+                &[nodes[1], nodes[2]],
+                &[false, false, true],
+            );
+            assert_eq!(proven, Some(root));
+
+            // All four leaves need no proof at all.
+            let proven = process_multiproof(&Sha256, tree, &leaves, &[], &[true, true, true]);
+            assert_eq!(proven, Some(root));
+        }
+    }
+
+    #[test]
+    fn process_multiproof_rejects_bad_shapes() {
+        let leaves = leaves(4);
+        let tree = Tree::for_root_version(ROOT_VERSION_V2, true).unwrap();
+        let node = tree.leaf_node(&Sha256, &leaves[2]);
+
+        assert_eq!(process_multiproof(&Sha256, tree, &[], &[], &[]), None);
+        assert_eq!(process_multiproof(&Sha256, tree, &leaves[..2], &[node], &[true]), None);
+        // Counts add up, but the flags never reach the proof node.
+        assert_eq!(
+            process_multiproof(&Sha256, tree, &leaves[..2], &[node], &[true, true]),
+            None
+        );
+        // A single leaf is its own root.
+        assert_eq!(
+            process_multiproof(&Sha256, tree, &leaves[..1], &[], &[]),
+            Some(tree.leaf_node(&Sha256, &leaves[0]))
+        );
+    }
+
+    #[test]
+    fn frontier_root_matches_compute_root() {
+        for (tree, _) in LAYOUTS {
+            let mut frontier = [[0u8; 32]; 4];
+            assert_eq!(frontier_root(&Sha256, tree, &frontier, 0), None);
+            for count in 1..=15u8 {
+                let leaf = [count; 32];
+                assert!(append_leaf(&Sha256, tree, &mut frontier, u64::from(count - 1), &leaf));
+                assert_eq!(
+                    frontier_root(&Sha256, tree, &frontier, u64::from(count)),
+                    compute_root(&Sha256, tree, &leaves(count))
+                );
+            }
+            // A sixteenth leaf would complete the fourth level and need a
+            // fifth slot for the node it carries up.
+            assert!(!append_leaf(&Sha256, tree, &mut frontier, 15, &[16; 32]));
+        }
+    }
+
+    #[test]
+    fn leaves_match_client() {
+        let domain = commitment_domain(&Sha256, &hex(PROGRAM), &hex(REGISTRY));
+        assert_eq!(domain, hex(DOMAIN));
+
+        assert_eq!(
+            claim_leaf(&Sha256, &domain, &hex(WALLET), 1000),
+            hex("5711cf779429d77523a2f006105de5bf608d0dedc0e86df14bee17f21a1ba863")
+        );
+        assert_eq!(
+            hash_schema_leaf(&Sha256, &domain, &KeyLeafV1 { key: [7; 32] }),
+            hex("f7258590127ffbd2a0157f47c304bc47b6678e4b4abde2e45b6673e312e3f263")
+        );
+        let entry = EntryLeafV1 {
+            id: 3,
+            target_program: hex(PROGRAM),
+            kind: 2,
+            tags: 5,
+            expires_at: 1_700_000_000,
+        };
+        assert_eq!(
+            hash_schema_leaf(&Sha256, &domain, &entry),
+            hex("ba1cb86aff849afef812cf94dcaa2af4c0457744def79646350d57c96d2247f1")
+        );
+        let allow = AllowlistLeafV1 {
+            wallet: hex(WALLET),
+            target_program: hex(PROGRAM),
+        };
+        assert_eq!(
+            hash_schema_leaf(&Sha256, &domain, &allow),
+            hex("eee0302197844a80d14da5fca686b9fb631faec76dd408b0b2c3d505cf7ec221")
+        );
+        assert_eq!(
+            hash_raw_leaf(&Sha256, LEAF_SCHEMA_ALLOWLIST_V1, &domain, &encode_leaf(&allow)),
+            Some(hash_schema_leaf(&Sha256, &domain, &allow))
+        );
+
+        let chain = "solana:5eykt4UsFv8P8NJdTREpY1vzqKqZKvdp";
+        let recipient = alloc::format!("{chain}:noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV");
+        let asset = alloc::format!("{chain}/token:So11111111111111111111111111111111111111112");
+        assert_eq!(
+            hash_leaf(
+                &Sha256,
+                &domain,
+                &hex(WALLET),
+                9,
+                2,
+                &recipient,
+                "1000",
+                &asset,
+            ),
+            hex("d3765d8cb81f16f73d0e208e593ac9b7ebbf4fa07ed2a7da0bbe8e387201b883")
+        );
+    }
+}
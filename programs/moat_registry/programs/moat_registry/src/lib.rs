@@ -1,16 +1,247 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::{invoke, invoke_signed};
+use anchor_lang::system_program;
+use solana_instructions_sysvar::{load_current_index_checked, load_instruction_at_checked};
+use solana_sdk_ids::{ed25519_program, secp256k1_program, sysvar};
+use solana_sha256_hasher::hashv;
+
+pub use moat_merkle;
 
 declare_id!("FTVm8gDndxnocAqi4sr53BnaymMXxESNGHgTzagJX2qY");
 
+pub const ENTRY_STATUS_ACTIVE: u8 = 0;
+pub const ENTRY_STATUS_REVOKED: u8 = 1;
+pub const ENTRY_STATUS_SUSPENDED: u8 = 2;
+
+pub const COMMIT_STATUS_PENDING: u8 = 0;
+pub const COMMIT_STATUS_FINALIZED: u8 = 1;
+
+pub const TARGET_KIND_PROGRAM: u8 = 0;
+pub const TARGET_KIND_MINT: u8 = 1;
+pub const TARGET_KIND_WALLET: u8 = 2;
+pub const TARGET_KIND_PDA: u8 = 3;
+
+// Capability bits held by Role PDAs.
+pub const ROLE_REGISTER: u8 = 1 << 0;
+pub const ROLE_COMMIT: u8 = 1 << 1;
+pub const ROLE_PAUSE: u8 = 1 << 2;
+pub const ROLE_CONFIGURE: u8 = 1 << 3;
+pub const ROLE_ALL: u8 = ROLE_REGISTER | ROLE_COMMIT | ROLE_PAUSE | ROLE_CONFIGURE;
+
+pub const BPF_LOADER_ID: Pubkey = pubkey!("BPFLoader2111111111111111111111111111111111");
+pub const BPF_LOADER_DEPRECATED_ID: Pubkey = pubkey!("BPFLoader1111111111111111111111111111111111");
+pub const LOADER_V4_ID: Pubkey = pubkey!("LoaderV411111111111111111111111111111111111");
+pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+pub const SPL_ACCOUNT_COMPRESSION_ID: Pubkey =
+    pubkey!("cmtDvXumGCrqC1Age74AVPhSRVXJMd8PJS91L8KbNCK");
+pub const SPL_NOOP_ID: Pubkey = pubkey!("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV");
+
+// Anchor discriminators of the spl-account-compression instructions we call.
+const COMPRESSION_INIT_EMPTY_MERKLE_TREE: [u8; 8] = [191, 11, 119, 7, 180, 107, 220, 110];
+const COMPRESSION_APPEND: [u8; 8] = [149, 120, 18, 222, 236, 225, 88, 203];
+const COMPRESSION_VERIFY_LEAF: [u8; 8] = [124, 220, 22, 223, 104, 10, 250, 224];
+
+// Anchor discriminator of the `verify_batch` instruction commit verifiers expose.
+const VERIFIER_VERIFY_BATCH: [u8; 8] = [207, 55, 42, 119, 105, 251, 88, 199];
+
+// Shared by spl-token and token-2022.
+const TOKEN_TRANSFER_CHECKED: u8 = 12;
+const TOKEN_CLOSE_ACCOUNT: u8 = 9;
+
+pub const GC_TIP_LAMPORTS: u64 = 5_000;
+
+// Bits of `RegistryState.paused_operations`, checked alongside the global `paused`.
+pub const PAUSE_REGISTER: u8 = 1 << 0;
+pub const PAUSE_COMMIT: u8 = 1 << 1;
+pub const PAUSE_UPDATE: u8 = 1 << 2;
+pub const PAUSE_ATTEST: u8 = 1 << 3;
+pub const PAUSE_ALL: u8 = PAUSE_REGISTER | PAUSE_COMMIT | PAUSE_UPDATE | PAUSE_ATTEST;
+
+pub const RECOVERY_DELAY: i64 = 3 * 24 * 60 * 60;
+
+pub const ADMIN_LOG_CAPACITY: usize = 64;
+pub const RECENT_COMMITS_CAPACITY: usize = 32;
+pub const COMMIT_LOG_CAPACITY: usize = 2048;
+
+// Commitment preimages are bound to this program and registry; see `commitment_domain`.
+pub const COMMITMENT_DOMAIN_TAG: &[u8] = moat_merkle::DOMAIN_TAG;
+pub const COMMITMENT_DOMAIN_VERSION: u8 = moat_merkle::DOMAIN_VERSION;
+pub const CLAIM_LEAF_TAG: &[u8] = moat_merkle::CLAIM_LEAF_TAG;
+
+pub const HASH_ALG_SHA256: u8 = 0;
+pub const HASH_ALG_KECCAK256: u8 = 1;
+
+// Leaf encoding a root was built with. Newer versions can be committed before
+// this program learns them; verification rejects anything not listed here.
+pub const ROOT_VERSION_V1: u16 = moat_merkle::ROOT_VERSION_V1;
+// Leaves and internal nodes hashed under distinct prefixes.
+pub const ROOT_VERSION_V2: u16 = moat_merkle::ROOT_VERSION_V2;
+
+// How a commit's leaves are serialized; see `moat_merkle::LeafSchema`.
+pub const LEAF_SCHEMA_CUSTOM: u16 = moat_merkle::LEAF_SCHEMA_CUSTOM;
+pub const LEAF_SCHEMA_CLAIM_V1: u16 = moat_merkle::LEAF_SCHEMA_CLAIM_V1;
+pub const LEAF_SCHEMA_SORTED_KEY_V1: u16 = moat_merkle::LEAF_SCHEMA_SORTED_KEY_V1;
+pub const LEAF_SCHEMA_ENTRY_V1: u16 = moat_merkle::LEAF_SCHEMA_ENTRY_V1;
+pub const LEAF_SCHEMA_ALLOWLIST_V1: u16 = moat_merkle::LEAF_SCHEMA_ALLOWLIST_V1;
+
+pub const MAX_MERKLE_DEPTH: u8 = 32;
+// Roots a commit may carry beyond `merkle_root`, one per extra shard.
+pub const MAX_SHARD_ROOTS: usize = 8;
+pub const MAX_INLINE_MEMO_LEN: usize = 512;
+pub const MAX_RAW_LEAF_LEN: usize = 256;
+pub const MAX_EXCLUDED_LEAVES: usize = 128;
+pub const MAX_AGGREGATE_MEMBERS: usize = 64;
+pub const MAX_COMMIT_ACL_MEMBERS: usize = 16;
+pub const MAX_COMMIT_ANNOTATIONS: usize = 32;
+
+// Well-known `annotate_commit` codes; integrators may use any other.
+pub const ANNOTATION_RECONCILED: u16 = 1;
+pub const ANNOTATION_DISPUTED: u16 = 2;
+pub const ANNOTATION_PAYLOAD_REUPLOADED: u16 = 3;
+// Bitmaps are created through CPI, which caps new accounts at 10 KiB.
+pub const MAX_CLAIM_BITMAP_BYTES: usize = 10_240 - 8 - 32 - 8 - 1 - 4;
+
+pub const ADMIN_ACTION_DEFINE_KIND: u8 = 1;
+pub const ADMIN_ACTION_UPDATE_KIND: u8 = 2;
+pub const ADMIN_ACTION_DEFINE_TAG: u8 = 3;
+pub const ADMIN_ACTION_GRANT_ROLE: u8 = 4;
+pub const ADMIN_ACTION_REVOKE_ROLE: u8 = 5;
+pub const ADMIN_ACTION_CREATE_SESSION: u8 = 6;
+pub const ADMIN_ACTION_REVOKE_SESSION: u8 = 7;
+pub const ADMIN_ACTION_ADD_REGISTRAR: u8 = 8;
+pub const ADMIN_ACTION_REMOVE_REGISTRAR: u8 = 9;
+pub const ADMIN_ACTION_PROPOSE_ADMIN: u8 = 10;
+pub const ADMIN_ACTION_ACCEPT_ADMIN: u8 = 11;
+pub const ADMIN_ACTION_INITIATE_RECOVERY: u8 = 12;
+pub const ADMIN_ACTION_COMPLETE_RECOVERY: u8 = 13;
+pub const ADMIN_ACTION_SET_TIMELOCK_DELAY: u8 = 14;
+pub const ADMIN_ACTION_QUEUE_ACTION: u8 = 15;
+pub const ADMIN_ACTION_EXECUTE_ACTION: u8 = 16;
+pub const ADMIN_ACTION_CANCEL_ACTION: u8 = 17;
+pub const ADMIN_ACTION_CREATE_MULTISIG: u8 = 18;
+pub const ADMIN_ACTION_SET_REQUIRE_UPGRADE_AUTHORITY: u8 = 19;
+pub const ADMIN_ACTION_SET_AUDITOR: u8 = 20;
+pub const ADMIN_ACTION_SET_MIN_UPDATE_INTERVAL: u8 = 21;
+pub const ADMIN_ACTION_SET_RISK_AUTHORITY: u8 = 22;
+pub const ADMIN_ACTION_SET_OPEN_REGISTRATION: u8 = 23;
+pub const ADMIN_ACTION_SET_COMMIT_AUTHORITY: u8 = 24;
+pub const ADMIN_ACTION_SET_GUARDIAN: u8 = 25;
+pub const ADMIN_ACTION_PAUSE: u8 = 26;
+pub const ADMIN_ACTION_UNPAUSE: u8 = 27;
+pub const ADMIN_ACTION_SET_ATTESTATION_THRESHOLD: u8 = 28;
+pub const ADMIN_ACTION_ADD_ATTESTOR: u8 = 29;
+pub const ADMIN_ACTION_REMOVE_ATTESTOR: u8 = 30;
+pub const ADMIN_ACTION_BAN_TARGET: u8 = 31;
+pub const ADMIN_ACTION_UNBAN_TARGET: u8 = 32;
+pub const ADMIN_ACTION_SET_PAUSED_OPERATIONS: u8 = 33;
+pub const ADMIN_ACTION_SET_MIN_ACTION_SLOT_GAP: u8 = 34;
+pub const ADMIN_ACTION_SET_ROTATION_GRACE_SLOTS: u8 = 35;
+pub const ADMIN_ACTION_REVOKE_COMMIT: u8 = 36;
+pub const ADMIN_ACTION_SET_DISPUTE_WINDOW: u8 = 37;
+pub const ADMIN_ACTION_SET_DISPUTE_BONDS: u8 = 38;
+pub const ADMIN_ACTION_RESOLVE_CHALLENGE: u8 = 39;
+pub const ADMIN_ACTION_SET_EPOCH_LENGTH: u8 = 40;
+pub const ADMIN_ACTION_SET_COMMIT_COSIGNERS: u8 = 41;
+pub const ADMIN_ACTION_SET_MIN_COMMIT_INTERVAL: u8 = 42;
+pub const ADMIN_ACTION_SET_COMMIT_RETENTION: u8 = 43;
+pub const ADMIN_ACTION_DEFINE_COMMIT_KIND: u8 = 44;
+pub const ADMIN_ACTION_EXCLUDE_LEAVES: u8 = 45;
+pub const ADMIN_ACTION_SET_COMMIT_CONSUMER: u8 = 46;
+pub const ADMIN_ACTION_SET_ROOT_ATTESTOR: u8 = 47;
+pub const ADMIN_ACTION_SET_ROOT_ATTESTOR_ETH: u8 = 48;
+pub const ADMIN_ACTION_SET_COMMIT_VERIFIER: u8 = 49;
+pub const ADMIN_ACTION_COMMIT_REGISTRY_ROOT: u8 = 50;
+pub const ADMIN_ACTION_SET_REGISTRATION_ALLOWLIST: u8 = 51;
+pub const ADMIN_ACTION_SET_COMMIT_ATTESTATION_QUORUM: u8 = 52;
+
+pub const MAX_KIND_LABEL_LEN: usize = 32;
+pub const MAX_ENTRY_NAME_LEN: usize = 32;
+pub const MAX_TAG_LABEL_LEN: usize = 32;
+pub const MAX_METADATA_URI_LEN: usize = 200;
+pub const MAX_PAYLOAD_URI_LEN: usize = 128;
+
+// Approvals are tracked as a u16 bitmask over owner indices.
+pub const MAX_MULTISIG_OWNERS: usize = 16;
+pub const MAX_COMMIT_COSIGNERS: usize = 8;
+
 #[program]
 pub mod moat_registry {
     use super::*;
 
-    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
+    /// `recovery_authority` is fixed for the life of the registry; pass the
+    /// default pubkey to opt out of recovery.
+    pub fn initialize(ctx: Context<Initialize>, recovery_authority: Pubkey) -> Result<()> {
         let state = &mut ctx.accounts.state;
         state.admin = ctx.accounts.authority.key();
         state.next_id = 0;
         state.bump = ctx.bumps.state;
+        state.defined_tags = 0;
+        state.require_upgrade_authority = false;
+        state.auditor = Pubkey::default();
+        state.min_update_interval = 0;
+        state.risk_authority = Pubkey::default();
+        state.attestation_threshold = 0;
+        state.pending_admin = Pubkey::default();
+        state.guardian = Pubkey::default();
+        state.paused = false;
+        state.timelock_delay = 0;
+        state.next_action_id = 0;
+        state.open_registration = false;
+        state.commit_authority = Pubkey::default();
+        state.recovery_authority = recovery_authority;
+        state.recovery_admin = Pubkey::default();
+        state.recovery_eta = 0;
+        state.admin_log_enabled = false;
+        state.governance = Pubkey::default();
+        state.paused_operations = 0;
+        state.min_action_slot_gap = 0;
+        state.rotation_grace_slots = 0;
+        state.previous_admin = Pubkey::default();
+        state.previous_admin_valid_until = 0;
+        state.previous_commit_authority = Pubkey::default();
+        state.previous_commit_authority_valid_until = 0;
+        state.next_commit_id = 0;
+        state.dispute_window = 0;
+        state.challenge_bond = 0;
+        state.commit_stake = 0;
+        state.recent_commits_enabled = false;
+        state.commit_log = Pubkey::default();
+        state.epoch_length = 0;
+        state.epoch_origin = 0;
+        state.epoch_offset = 0;
+        state.commit_cosigner_threshold = 0;
+        state.min_commit_interval_slots = 0;
+        state.last_commit_slot = 0;
+        state.commit_retention = 0;
+        state.root_attestor = Pubkey::default();
+        state.root_attestor_eth = [0; 20];
+        state.commit_verifier = Pubkey::default();
+        state.registration_allowlist = [0; 32];
+        state.commit_attestation_quorum = 0;
+        Ok(())
+    }
+
+    /// Grows a registry created under an older layout to the current size.
+    /// Fields added since then start zeroed.
+    pub fn migrate_state(ctx: Context<MigrateState>) -> Result<()> {
+        let info = ctx.accounts.state.to_account_info();
+        let space = 8 + RegistryState::INIT_SPACE;
+
+        if info.data_len() < space {
+            grow_account(
+                &ctx.accounts.payer.to_account_info(),
+                &info,
+                &ctx.accounts.system_program.to_account_info(),
+                space,
+            )?;
+        }
+
+        // Deserializing after the resize also checks the discriminator.
+        let state = RegistryState::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+        require_keys_eq!(state.admin, ctx.accounts.authority.key(), MoatError::Unauthorized);
+
         Ok(())
     }
 
@@ -18,164 +249,10346 @@ pub mod moat_registry {
         ctx: Context<RegisterEntry>,
         target_program: Pubkey,
         kind: u8,
+        target_kind: u8,
+        expires_at: i64,
+        name: [u8; 32],
+    ) -> Result<()> {
+        process_register_entry(
+            ctx.accounts,
+            &ctx.bumps,
+            EntryRegistration {
+                target_program,
+                kind,
+                target_kind,
+                name,
+            },
+            expires_at,
+            false,
+        )
+    }
+
+    /// Registers `target_program` for a wallet the registry pre-approved:
+    /// `proof` must place `AllowlistLeafV1 { authority, target_program }` under
+    /// the registration allowlist, a sorted-pair `ROOT_VERSION_V2` tree. An
+    /// approved wallet registers as a registrar would.
+    pub fn register_entry_with_proof(
+        ctx: Context<RegisterEntry>,
+        target_program: Pubkey,
+        kind: u8,
+        target_kind: u8,
+        expires_at: i64,
+        name: [u8; 32],
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let state = &ctx.accounts.state;
+        require!(
+            state.registration_allowlist != [0; 32]
+                && proof.len() <= usize::from(MAX_MERKLE_DEPTH),
+            MoatError::NotAllowlisted
+        );
+        let hasher = CommitHasher::new(HASH_ALG_SHA256)?;
+        let leaf = moat_merkle::hash_schema_leaf(
+            &hasher,
+            &commitment_domain(&state.key()),
+            &moat_merkle::AllowlistLeafV1 {
+                wallet: ctx.accounts.authority.key().to_bytes(),
+                target_program: target_program.to_bytes(),
+            },
+        );
+        require!(
+            moat_merkle::verify_proof(
+                &hasher,
+                merkle_tree(ROOT_VERSION_V2, true)?,
+                &state.registration_allowlist,
+                &leaf,
+                0,
+                &proof,
+            ),
+            MoatError::NotAllowlisted
+        );
+
+        process_register_entry(
+            ctx.accounts,
+            &ctx.bumps,
+            EntryRegistration {
+                target_program,
+                kind,
+                target_kind,
+                name,
+            },
+            expires_at,
+            true,
+        )
+    }
+
+    pub fn register_named_entry(
+        ctx: Context<RegisterNamedEntry>,
+        target_program: Pubkey,
+        kind: u8,
+        target_kind: u8,
+        expires_at: i64,
+        name: String,
+    ) -> Result<()> {
+        validate_entry_name(&name)?;
+
+        // The canonical name doubles as the display name.
+        let mut display_name = [0u8; 32];
+        display_name[..name.len()].copy_from_slice(name.as_bytes());
+
+        process_register_entry(
+            &mut ctx.accounts.base,
+            &ctx.bumps.base,
+            EntryRegistration {
+                target_program,
+                kind,
+                target_kind,
+                name: display_name,
+            },
+            expires_at,
+            false,
+        )?;
+
+        let entry = &mut ctx.accounts.base.entry;
+        let name_lookup = &mut ctx.accounts.name_lookup;
+        if name_lookup.entry != Pubkey::default() {
+            return err!(MoatError::DuplicateName);
+        }
+        entry.named = true;
+        name_lookup.entry = entry.key();
+        name_lookup.id = entry.id;
+        name_lookup.name = name;
+        name_lookup.bump = ctx.bumps.name_lookup;
+
+        Ok(())
+    }
+
+    pub fn register_entries<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RegisterEntries<'info>>,
+        registrations: Vec<EntryRegistration>,
+        expires_at: i64,
     ) -> Result<()> {
         let state = &mut ctx.accounts.state;
+        let now = Clock::get()?.unix_timestamp;
 
-        require_keys_eq!(state.admin, ctx.accounts.authority.key(), MoatError::Unauthorized);
+        require_capability(
+            state,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.role.as_deref_mut(),
+            ctx.accounts.session.as_deref_mut(),
+            ctx.accounts.nonce.as_deref_mut(),
+            ROLE_REGISTER,
+        )?;
+        state.require_not_paused(PAUSE_REGISTER)?;
+        require!(!registrations.is_empty(), MoatError::EmptyBatch);
+        require!(
+            ctx.remaining_accounts.len() == registrations.len() * 4,
+            MoatError::InvalidRemainingAccounts
+        );
+        require!(expires_at == 0 || expires_at > now, MoatError::InvalidExpiry);
+
+        let state_key = state.key();
+        let authority = ctx.accounts.authority.key();
+        let payer = ctx.accounts.payer.to_account_info();
+        let system_program = ctx.accounts.system_program.to_account_info();
+
+        // remaining_accounts: [entry, lookup, kind_definition, ban] per registration
+        for (registration, accounts) in registrations
+            .iter()
+            .zip(ctx.remaining_accounts.chunks_exact(4))
+        {
+            let (entry_info, lookup_info, kind_info, ban_info) =
+                (&accounts[0], &accounts[1], &accounts[2], &accounts[3]);
+
+            let (kind_key, _) = Pubkey::find_program_address(
+                &[b"kind", state_key.as_ref(), &[registration.kind]],
+                &crate::ID,
+            );
+            require_keys_eq!(kind_info.key(), kind_key, MoatError::InvalidRemainingAccounts);
+            require_kind_enabled(kind_info)?;
+            increment_active_entries(kind_info)?;
+            require!(
+                registration.target_kind <= TARGET_KIND_PDA,
+                MoatError::InvalidTargetKind
+            );
+            validate_display_name(&registration.name)?;
+            require!(
+                !state.require_upgrade_authority
+                    || registration.target_kind != TARGET_KIND_PROGRAM,
+                MoatError::UpgradeAuthorityRequired
+            );
+
+            let entry_id =
+                u32::try_from(state.next_id).map_err(|_| MoatError::NextIdOverflow)?;
+            let id_bytes = entry_id.to_le_bytes();
+            let (entry_key, entry_bump) = Pubkey::find_program_address(
+                &[b"entry", state_key.as_ref(), &id_bytes],
+                &crate::ID,
+            );
+            require_keys_eq!(entry_info.key(), entry_key, MoatError::InvalidRemainingAccounts);
+
+            let (lookup_key, lookup_bump) = Pubkey::find_program_address(
+                &[b"entry", state_key.as_ref(), registration.target_program.as_ref()],
+                &crate::ID,
+            );
+            require_keys_eq!(lookup_info.key(), lookup_key, MoatError::InvalidRemainingAccounts);
+            require!(
+                lookup_info.owner != &crate::ID || lookup_info.data_is_empty(),
+                MoatError::DuplicateTarget
+            );
+
+            let (ban_key, _) = Pubkey::find_program_address(
+                &[b"ban", state_key.as_ref(), registration.target_program.as_ref()],
+                &crate::ID,
+            );
+            require_keys_eq!(ban_info.key(), ban_key, MoatError::InvalidRemainingAccounts);
+            require_not_banned(ban_info)?;
+
+            create_pda_account(
+                &payer,
+                entry_info,
+                &system_program,
+                8 + RegistryEntry::INIT_SPACE,
+                &[b"entry", state_key.as_ref(), &id_bytes, &[entry_bump]],
+            )?;
+            RegistryEntry::new(
+                state_key,
+                entry_id,
+                authority,
+                registration,
+                expires_at,
+                now,
+                entry_bump,
+            )
+            .try_serialize(&mut &mut entry_info.try_borrow_mut_data()?[..])?;
+
+            create_pda_account(
+                &payer,
+                lookup_info,
+                &system_program,
+                8 + EntryLookup::INIT_SPACE,
+                &[
+                    b"entry",
+                    state_key.as_ref(),
+                    registration.target_program.as_ref(),
+                    &[lookup_bump],
+                ],
+            )?;
+            EntryLookup {
+                entry: entry_key,
+                id: entry_id,
+                bump: lookup_bump,
+            }
+            .try_serialize(&mut &mut lookup_info.try_borrow_mut_data()?[..])?;
+
+            state.next_id = state
+                .next_id
+                .checked_add(1)
+                .ok_or(MoatError::Overflow)?;
+        }
+
+        Ok(())
+    }
 
-        let entry_id =
-            u32::try_from(state.next_id).map_err(|_| MoatError::NextIdOverflow)?;
+    pub fn update_entry(
+        ctx: Context<UpdateEntry>,
+        target_program: Pubkey,
+        kind: u8,
+    ) -> Result<()> {
         let entry = &mut ctx.accounts.entry;
-        entry.registry = state.key();
-        entry.id = entry_id;
-        entry.admin = ctx.accounts.authority.key();
+        let now = Clock::get()?.unix_timestamp;
+
+        ctx.accounts.state.require_not_paused(PAUSE_UPDATE)?;
+        require_keys_eq!(entry.admin, ctx.accounts.authority.key(), MoatError::Unauthorized);
+        require!(!entry.is_expired(now), MoatError::EntryExpired);
+        require!(!entry.frozen, MoatError::EntryFrozen);
+        entry.require_update_allowed(ctx.accounts.state.min_update_interval, now)?;
+        require_keys_neq!(target_program, Pubkey::default(), MoatError::InvalidTargetProgram);
+        require_not_banned(&ctx.accounts.ban)?;
+        require_kind_enabled(&ctx.accounts.kind_definition)?;
+
+        if kind != entry.kind && entry.status == ENTRY_STATUS_ACTIVE {
+            let current = &mut ctx.accounts.current_kind_definition;
+            current.active_entries = current.active_entries.saturating_sub(1);
+            increment_active_entries(&ctx.accounts.kind_definition)?;
+        }
+
+        if target_program != entry.target_program {
+            // Consent was given for the old target only.
+            require!(
+                !ctx.accounts.state.require_upgrade_authority
+                    || entry.target_kind != TARGET_KIND_PROGRAM,
+                MoatError::UpgradeAuthorityRequired
+            );
+            entry.upgrade_authority_verified = false;
+
+            let new_lookup = &mut ctx.accounts.new_lookup;
+            require_keys_eq!(new_lookup.entry, Pubkey::default(), MoatError::DuplicateTarget);
+            new_lookup.entry = entry.key();
+            new_lookup.id = entry.id;
+            new_lookup.bump = ctx.bumps.new_lookup;
+
+            ctx.accounts
+                .lookup
+                .close(ctx.accounts.authority.to_account_info())?;
+        }
+
         entry.target_program = target_program;
         entry.kind = kind;
-        entry.bump = ctx.bumps.entry;
+        entry.last_updated_at = now;
+        entry.version = entry.next_version()?;
 
-        state.next_id = state
-            .next_id
-            .checked_add(1)
-            .ok_or(MoatError::Overflow)?;
+        emit!(EntryUpdated {
+            entry: entry.key(),
+            id: entry.id,
+            version: entry.version,
+            target_program,
+            kind,
+        });
 
         Ok(())
     }
 
-    pub fn commit_batch(
-        ctx: Context<CommitBatch>,
-        batch_id: u64,
-        merkle_root: [u8; 32],
-        memo_hash: [u8; 32],
-        kind: u8,
+    pub fn set_entry_name(ctx: Context<SetEntryName>, name: [u8; 32]) -> Result<()> {
+        let entry = &mut ctx.accounts.entry;
+        let now = Clock::get()?.unix_timestamp;
+
+        ctx.accounts.state.require_not_paused(PAUSE_UPDATE)?;
+        require_keys_eq!(entry.admin, ctx.accounts.authority.key(), MoatError::Unauthorized);
+        require!(!entry.is_expired(now), MoatError::EntryExpired);
+        require!(!entry.frozen, MoatError::EntryFrozen);
+        entry.require_update_allowed(ctx.accounts.state.min_update_interval, now)?;
+        validate_display_name(&name)?;
+
+        entry.name = name;
+        entry.last_updated_at = now;
+        entry.version = entry.next_version()?;
+
+        emit!(EntryNameSet {
+            entry: entry.key(),
+            id: entry.id,
+            version: entry.version,
+            name,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_entry_metadata(
+        ctx: Context<SetEntryMetadata>,
+        metadata_uri: String,
     ) -> Result<()> {
-        let commit = &mut ctx.accounts.batch;
+        let entry = &mut ctx.accounts.entry;
+        let now = Clock::get()?.unix_timestamp;
+
+        ctx.accounts.state.require_not_paused(PAUSE_UPDATE)?;
+        require_admin_or_delegate(
+            entry,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.nonce.as_deref_mut(),
+        )?;
+        require!(!entry.is_expired(now), MoatError::EntryExpired);
+        require!(!entry.frozen, MoatError::EntryFrozen);
+        entry.require_update_allowed(ctx.accounts.state.min_update_interval, now)?;
+        require!(
+            metadata_uri.len() <= MAX_METADATA_URI_LEN,
+            MoatError::MetadataUriTooLong
+        );
+
+        entry.metadata_uri = metadata_uri;
+        entry.last_updated_at = now;
+        entry.version = entry.next_version()?;
+
+        emit!(EntryMetadataSet {
+            entry: entry.key(),
+            id: entry.id,
+            version: entry.version,
+            metadata_uri: entry.metadata_uri.clone(),
+        });
+
+        Ok(())
+    }
+
+    pub fn close_entry(ctx: Context<CloseEntry>) -> Result<()> {
+        let authority = ctx.accounts.authority.key();
+        let entry = &ctx.accounts.entry;
+        let is_registry_admin = ctx.accounts.state.is_admin(&authority)?;
+
+        ctx.accounts.state.require_not_paused(PAUSE_UPDATE)?;
+        require!(
+            authority == entry.admin || is_registry_admin,
+            MoatError::Unauthorized
+        );
+        require!(!entry.frozen || is_registry_admin, MoatError::EntryFrozen);
+        // Left open, the lookup would hold the name for a closed entry forever.
+        require!(
+            !entry.named || ctx.accounts.name_lookup.is_some(),
+            MoatError::NameLookupRequired
+        );
 
-        if commit.creator != Pubkey::default() {
-            return err!(MoatError::BatchAlreadyExists);
+        if entry.status == ENTRY_STATUS_ACTIVE {
+            let definition = &mut ctx.accounts.kind_definition;
+            definition.active_entries = definition.active_entries.saturating_sub(1);
         }
 
-        commit.creator = ctx.accounts.creator.key();
-        commit.batch_id = batch_id;
-        commit.kind = kind;
-        commit.merkle_root = merkle_root;
-        commit.memo_hash = memo_hash;
-        commit.created_at = Clock::get()?.unix_timestamp;
+        emit!(EntryClosed {
+            entry: entry.key(),
+            id: entry.id,
+            destination: ctx.accounts.destination.key(),
+        });
 
         Ok(())
     }
-}
 
-#[derive(Accounts)]
-pub struct Initialize<'info> {
-    #[account(mut)]
-    pub authority: Signer<'info>,
+    pub fn gc_entry(ctx: Context<GcEntry>) -> Result<()> {
+        let entry = &ctx.accounts.entry;
 
-    #[account(
-        init,
-        payer = authority,
-        space = 8 + RegistryState::INIT_SPACE,
-        seeds = [b"state"],
-        bump
-    )]
-    pub state: Account<'info, RegistryState>,
+        ctx.accounts.state.require_not_paused(PAUSE_UPDATE)?;
+        require!(
+            entry.is_expired(Clock::get()?.unix_timestamp),
+            MoatError::EntryNotExpired
+        );
+        require!(!entry.frozen, MoatError::EntryFrozen);
+        require!(
+            !entry.named || ctx.accounts.name_lookup.is_some(),
+            MoatError::NameLookupRequired
+        );
 
-    pub system_program: Program<'info, System>,
-}
+        if entry.status == ENTRY_STATUS_ACTIVE {
+            let definition = &mut ctx.accounts.kind_definition;
+            definition.active_entries = definition.active_entries.saturating_sub(1);
+        }
 
-#[derive(Accounts)]
-pub struct RegisterEntry<'info> {
-    #[account(mut)]
-    pub authority: Signer<'info>,
+        // The tip comes out of the entry's rent; the remainder goes back to the entry admin on close.
+        let entry_info = ctx.accounts.entry.to_account_info();
+        let tip = GC_TIP_LAMPORTS.min(entry_info.lamports());
+        **entry_info.try_borrow_mut_lamports()? -= tip;
+        **ctx.accounts.cranker.try_borrow_mut_lamports()? += tip;
 
-    #[account(
-        mut,
-        seeds = [b"state"],
-        bump = state.bump
-    )]
-    pub state: Account<'info, RegistryState>,
+        emit!(EntryClosed {
+            entry: entry_info.key(),
+            id: ctx.accounts.entry.id,
+            destination: ctx.accounts.admin.key(),
+        });
 
-    #[account(
-        init,
-        payer = authority,
-        space = 8 + RegistryEntry::INIT_SPACE,
-        seeds = [
-            b"entry",
-            state.key().as_ref(),
-            &state.next_id.to_le_bytes()[..4]
-        ],
-        bump
-    )]
-    pub entry: Account<'info, RegistryEntry>,
+        Ok(())
+    }
 
-    pub system_program: Program<'info, System>,
-}
+    pub fn propose_entry_transfer(
+        ctx: Context<ProposeEntryTransfer>,
+        new_admin: Pubkey,
+    ) -> Result<()> {
+        let entry = &mut ctx.accounts.entry;
 
-#[derive(Accounts)]
-#[instruction(batch_id: u64)]
-pub struct CommitBatch<'info> {
-    #[account(mut)]
-    pub creator: Signer<'info>,
+        ctx.accounts.state.require_not_paused(PAUSE_UPDATE)?;
+        require_keys_eq!(entry.admin, ctx.accounts.authority.key(), MoatError::Unauthorized);
+        require!(!entry.frozen, MoatError::EntryFrozen);
 
-    #[account(
-        init_if_needed,
-        payer = creator,
-        space = 8 + BatchCommit::INIT_SPACE,
-        seeds = [
-            b"batch",
-            creator.key().as_ref(),
-            &batch_id.to_le_bytes()
-        ],
-        bump
-    )]
-    pub batch: Account<'info, BatchCommit>,
+        entry.pending_admin = new_admin;
+        entry.version = entry.next_version()?;
 
-    pub system_program: Program<'info, System>,
-}
+        emit!(EntryTransferProposed {
+            entry: entry.key(),
+            id: entry.id,
+            version: entry.version,
+            admin: entry.admin,
+            pending_admin: new_admin,
+        });
 
-#[account]
-pub struct RegistryState {
-    pub admin: Pubkey,
-    pub next_id: u64,
-    pub bump: u8,
-}
-impl Space for RegistryState {
-    const INIT_SPACE: usize = 32 + 8 + 1;
-}
+        Ok(())
+    }
 
-#[account]
-pub struct RegistryEntry {
-    pub registry: Pubkey,
-    pub id: u32,
-    pub admin: Pubkey,
-    pub target_program: Pubkey,
-    pub kind: u8,
-    pub bump: u8,
-}
-impl Space for RegistryEntry {
-    const INIT_SPACE: usize = 32 + 4 + 32 + 32 + 1 + 1;
-}
+    pub fn accept_entry_transfer(ctx: Context<AcceptEntryTransfer>) -> Result<()> {
+        let entry = &mut ctx.accounts.entry;
+        let new_admin = ctx.accounts.new_admin.key();
 
-#[account]
-pub struct BatchCommit {
-    pub creator: Pubkey,
-    pub batch_id: u64,
-    pub kind: u8,
-    pub merkle_root: [u8; 32],
-    pub memo_hash: [u8; 32],
-    pub created_at: i64,
-}
-impl Space for BatchCommit {
-    const INIT_SPACE: usize = 32 + 8 + 1 + 32 + 32 + 8;
-}
+        ctx.accounts.state.require_not_paused(PAUSE_UPDATE)?;
+        require_keys_neq!(entry.pending_admin, Pubkey::default(), MoatError::NoPendingTransfer);
+        require!(!entry.frozen, MoatError::EntryFrozen);
+        require_keys_eq!(entry.pending_admin, new_admin, MoatError::Unauthorized);
 
-#[error_code]
-pub enum MoatError {
-    #[msg("Unauthorized")]
-    Unauthorized,
-    #[msg("Next id overflow")]
-    NextIdOverflow,
-    #[msg("Overflow")]
-    Overflow,
-    #[msg("Batch already exists for this creator")]
-    BatchAlreadyExists,
+        let previous_admin = entry.admin;
+        entry.admin = new_admin;
+        entry.pending_admin = Pubkey::default();
+        entry.delegate = None;
+        entry.version = entry.next_version()?;
+
+        emit!(EntryTransferAccepted {
+            entry: entry.key(),
+            id: entry.id,
+            version: entry.version,
+            previous_admin,
+            admin: new_admin,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_delegate(ctx: Context<SetDelegate>, delegate: Pubkey) -> Result<()> {
+        let entry = &mut ctx.accounts.entry;
+
+        ctx.accounts.state.require_not_paused(PAUSE_UPDATE)?;
+        require_keys_eq!(entry.admin, ctx.accounts.authority.key(), MoatError::Unauthorized);
+        require!(!entry.frozen, MoatError::EntryFrozen);
+        require_keys_neq!(delegate, Pubkey::default(), MoatError::InvalidDelegate);
+
+        entry.delegate = Some(delegate);
+        entry.version = entry.next_version()?;
+
+        emit!(EntryDelegateSet {
+            entry: entry.key(),
+            id: entry.id,
+            version: entry.version,
+            delegate: entry.delegate,
+        });
+
+        Ok(())
+    }
+
+    pub fn clear_delegate(ctx: Context<SetDelegate>) -> Result<()> {
+        let entry = &mut ctx.accounts.entry;
+
+        ctx.accounts.state.require_not_paused(PAUSE_UPDATE)?;
+        require_keys_eq!(entry.admin, ctx.accounts.authority.key(), MoatError::Unauthorized);
+        require!(!entry.frozen, MoatError::EntryFrozen);
+
+        entry.delegate = None;
+        entry.version = entry.next_version()?;
+
+        emit!(EntryDelegateSet {
+            entry: entry.key(),
+            id: entry.id,
+            version: entry.version,
+            delegate: None,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_tags(ctx: Context<SetTags>, tags: u64) -> Result<()> {
+        let state = &ctx.accounts.state;
+
+        state.require_not_paused(PAUSE_UPDATE)?;
+        state.require_admin(&ctx.accounts.authority.key())?;
+        require!(tags & !state.defined_tags == 0, MoatError::UndefinedTag);
+
+        let entry = &mut ctx.accounts.entry;
+        require!(!entry.frozen, MoatError::EntryFrozen);
+        entry.tags = tags;
+        entry.version = entry.next_version()?;
+
+        emit!(EntryTagsSet {
+            entry: entry.key(),
+            id: entry.id,
+            version: entry.version,
+            tags,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_weight(ctx: Context<SetWeight>, weight: u16) -> Result<()> {
+        ctx.accounts.state.require_not_paused(PAUSE_UPDATE)?;
+        ctx.accounts.state.require_admin(&ctx.accounts.authority.key())?;
+
+        let entry = &mut ctx.accounts.entry;
+        require!(!entry.frozen, MoatError::EntryFrozen);
+        entry.weight = weight;
+        entry.version = entry.next_version()?;
+
+        emit!(EntryWeightSet {
+            entry: entry.key(),
+            id: entry.id,
+            version: entry.version,
+            weight,
+        });
+
+        Ok(())
+    }
+
+    pub fn deprecate_entry(
+        ctx: Context<DeprecateEntry>,
+        successor: Option<Pubkey>,
+    ) -> Result<()> {
+        let authority = ctx.accounts.authority.key();
+        let is_registry_admin = ctx.accounts.state.is_admin(&authority)?;
+        let entry = &mut ctx.accounts.entry;
+
+        ctx.accounts.state.require_not_paused(PAUSE_UPDATE)?;
+        require!(authority == entry.admin || is_registry_admin, MoatError::Unauthorized);
+        require!(!entry.frozen || is_registry_admin, MoatError::EntryFrozen);
+
+        if let Some(successor) = successor {
+            let successor_entry = ctx
+                .accounts
+                .successor
+                .as_ref()
+                .ok_or(MoatError::InvalidSuccessor)?;
+            require_keys_eq!(successor_entry.key(), successor, MoatError::InvalidSuccessor);
+            require_keys_neq!(successor, entry.key(), MoatError::InvalidSuccessor);
+        }
+
+        entry.deprecated = true;
+        entry.successor = successor;
+        entry.version = entry.next_version()?;
+
+        emit!(EntryDeprecated {
+            entry: entry.key(),
+            id: entry.id,
+            version: entry.version,
+            successor,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_parent(ctx: Context<SetParent>, parent: Option<Pubkey>) -> Result<()> {
+        let entry = &mut ctx.accounts.entry;
+
+        ctx.accounts.state.require_not_paused(PAUSE_UPDATE)?;
+        require_keys_eq!(entry.admin, ctx.accounts.authority.key(), MoatError::Unauthorized);
+        require!(!entry.frozen, MoatError::EntryFrozen);
+
+        if let Some(parent) = parent {
+            let parent_entry = ctx
+                .accounts
+                .parent
+                .as_ref()
+                .ok_or(MoatError::InvalidParent)?;
+            require_keys_eq!(parent_entry.key(), parent, MoatError::InvalidParent);
+            require_keys_neq!(parent, entry.key(), MoatError::InvalidParent);
+            require!(parent_entry.parent != Some(entry.key()), MoatError::InvalidParent);
+        }
+
+        entry.parent = parent;
+        entry.version = entry.next_version()?;
+
+        emit!(EntryParentSet {
+            entry: entry.key(),
+            id: entry.id,
+            version: entry.version,
+            parent,
+        });
+
+        Ok(())
+    }
+
+    pub fn revoke_entry(ctx: Context<SetEntryStatus>, status: u8) -> Result<()> {
+        ctx.accounts.state.require_admin(&ctx.accounts.authority.key())?;
+        require!(
+            status == ENTRY_STATUS_REVOKED || status == ENTRY_STATUS_SUSPENDED,
+            MoatError::InvalidEntryStatus
+        );
+
+        let entry = &mut ctx.accounts.entry;
+        require!(entry.status == ENTRY_STATUS_ACTIVE, MoatError::EntryNotActive);
+        entry.status = status;
+
+        let definition = &mut ctx.accounts.kind_definition;
+        definition.active_entries = definition.active_entries.saturating_sub(1);
+        entry.version = entry.next_version()?;
+
+        emit!(EntryStatusChanged {
+            entry: entry.key(),
+            id: entry.id,
+            version: entry.version,
+            status,
+        });
+
+        Ok(())
+    }
+
+    pub fn reinstate_entry(ctx: Context<SetEntryStatus>) -> Result<()> {
+        ctx.accounts.state.require_not_paused(PAUSE_UPDATE)?;
+        ctx.accounts.state.require_admin(&ctx.accounts.authority.key())?;
+
+        let entry = &mut ctx.accounts.entry;
+        require!(entry.status != ENTRY_STATUS_ACTIVE, MoatError::EntryAlreadyActive);
+        entry.status = ENTRY_STATUS_ACTIVE;
+
+        let definition = &mut ctx.accounts.kind_definition;
+        definition.active_entries = definition
+            .active_entries
+            .checked_add(1)
+            .ok_or(MoatError::Overflow)?;
+        entry.version = entry.next_version()?;
+
+        emit!(EntryStatusChanged {
+            entry: entry.key(),
+            id: entry.id,
+            version: entry.version,
+            status: ENTRY_STATUS_ACTIVE,
+        });
+
+        Ok(())
+    }
+
+    pub fn renew_entry(ctx: Context<RenewEntry>, expires_at: i64) -> Result<()> {
+        ctx.accounts.state.require_not_paused(PAUSE_UPDATE)?;
+        ctx.accounts.state.require_admin(&ctx.accounts.authority.key())?;
+
+        let entry = &mut ctx.accounts.entry;
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            expires_at == 0 || (expires_at > now && expires_at > entry.expires_at),
+            MoatError::InvalidExpiry
+        );
+        entry.expires_at = expires_at;
+        entry.version = entry.next_version()?;
+
+        emit!(EntryRenewed {
+            entry: entry.key(),
+            id: entry.id,
+            version: entry.version,
+            expires_at,
+        });
+
+        Ok(())
+    }
+
+    pub fn freeze_entry(ctx: Context<SetEntryFrozen>) -> Result<()> {
+        ctx.accounts.state.require_admin(&ctx.accounts.authority.key())?;
+
+        let entry = &mut ctx.accounts.entry;
+        require!(!entry.frozen, MoatError::EntryFrozen);
+        entry.frozen = true;
+        entry.version = entry.next_version()?;
+
+        emit!(EntryFreezeChanged {
+            entry: entry.key(),
+            id: entry.id,
+            version: entry.version,
+            frozen: true,
+        });
+
+        Ok(())
+    }
+
+    pub fn thaw_entry(ctx: Context<SetEntryFrozen>) -> Result<()> {
+        ctx.accounts.state.require_not_paused(PAUSE_UPDATE)?;
+        ctx.accounts.state.require_admin(&ctx.accounts.authority.key())?;
+
+        let entry = &mut ctx.accounts.entry;
+        require!(entry.frozen, MoatError::EntryNotFrozen);
+        entry.frozen = false;
+        entry.version = entry.next_version()?;
+
+        emit!(EntryFreezeChanged {
+            entry: entry.key(),
+            id: entry.id,
+            version: entry.version,
+            frozen: false,
+        });
+
+        Ok(())
+    }
+
+    pub fn define_kind(
+        ctx: Context<DefineKind>,
+        kind: u8,
+        label: String,
+        enabled: bool,
+    ) -> Result<()> {
+        require_capability(
+            &ctx.accounts.state,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.role.as_deref_mut(),
+            ctx.accounts.session.as_deref_mut(),
+            ctx.accounts.nonce.as_deref_mut(),
+            ROLE_CONFIGURE,
+        )?;
+        require!(label.len() <= MAX_KIND_LABEL_LEN, MoatError::LabelTooLong);
+
+        let definition = &mut ctx.accounts.kind_definition;
+        definition.registry = ctx.accounts.state.key();
+        definition.kind = kind;
+        definition.label = label;
+        definition.enabled = enabled;
+        definition.bump = ctx.bumps.kind_definition;
+        definition.active_entries = 0;
+
+        emit!(KindDefined {
+            kind,
+            label: definition.label.clone(),
+            enabled,
+        });
+
+        record_admin_action(
+            &ctx.accounts.state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_DEFINE_KIND,
+            ctx.accounts.authority.key(),
+            &[&[kind], ctx.accounts.kind_definition.label.as_bytes(), &[u8::from(enabled)]],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn update_kind(ctx: Context<UpdateKind>, label: String, enabled: bool) -> Result<()> {
+        require_capability(
+            &ctx.accounts.state,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.role.as_deref_mut(),
+            ctx.accounts.session.as_deref_mut(),
+            ctx.accounts.nonce.as_deref_mut(),
+            ROLE_CONFIGURE,
+        )?;
+        require!(ctx.accounts.state.timelock_delay == 0, MoatError::TimelockRequired);
+        require!(label.len() <= MAX_KIND_LABEL_LEN, MoatError::LabelTooLong);
+
+        let definition = &mut ctx.accounts.kind_definition;
+        definition.label = label;
+        definition.enabled = enabled;
+
+        emit!(KindDefined {
+            kind: definition.kind,
+            label: definition.label.clone(),
+            enabled,
+        });
+
+        record_admin_action(
+            &ctx.accounts.state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_UPDATE_KIND,
+            ctx.accounts.authority.key(),
+            &[
+                &[ctx.accounts.kind_definition.kind],
+                ctx.accounts.kind_definition.label.as_bytes(),
+                &[u8::from(enabled)],
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Defines or updates a commit kind. Commits carrying a nonzero
+    /// `commit_kind` need its definition to be enabled.
+    pub fn define_commit_kind(
+        ctx: Context<DefineCommitKind>,
+        kind: u8,
+        label: String,
+        enabled: bool,
+    ) -> Result<()> {
+        require_capability(
+            &ctx.accounts.state,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.role.as_deref_mut(),
+            ctx.accounts.session.as_deref_mut(),
+            ctx.accounts.nonce.as_deref_mut(),
+            ROLE_CONFIGURE,
+        )?;
+        require!(kind != 0, MoatError::InvalidCommitKind);
+        require!(label.len() <= MAX_KIND_LABEL_LEN, MoatError::LabelTooLong);
+
+        let definition = &mut ctx.accounts.commit_kind;
+        definition.registry = ctx.accounts.state.key();
+        definition.kind = kind;
+        definition.label = label;
+        definition.enabled = enabled;
+        definition.bump = ctx.bumps.commit_kind;
+
+        emit!(CommitKindDefined {
+            kind,
+            label: definition.label.clone(),
+            enabled,
+        });
+
+        record_admin_action(
+            &ctx.accounts.state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_DEFINE_COMMIT_KIND,
+            ctx.accounts.authority.key(),
+            &[&[kind], ctx.accounts.commit_kind.label.as_bytes(), &[u8::from(enabled)]],
+        )?;
+
+        Ok(())
+    }
+
+    /// Bans `target` on new registrations and retargets. Existing entries for it
+    /// are left to `revoke_entry`.
+    pub fn ban_target(ctx: Context<BanTarget>, target: Pubkey) -> Result<()> {
+        let state = &ctx.accounts.state;
+
+        state.require_admin(&ctx.accounts.authority.key())?;
+
+        let ban = &mut ctx.accounts.ban;
+        ban.registry = state.key();
+        ban.target = target;
+        ban.banned_at = Clock::get()?.unix_timestamp;
+        ban.bump = ctx.bumps.ban;
+
+        emit!(TargetBanChanged {
+            target,
+            banned: true,
+        });
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_BAN_TARGET,
+            ctx.accounts.authority.key(),
+            &[target.as_ref()],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn unban_target(ctx: Context<UnbanTarget>) -> Result<()> {
+        let state = &ctx.accounts.state;
+        let target = ctx.accounts.ban.target;
+
+        state.require_admin(&ctx.accounts.authority.key())?;
+
+        emit!(TargetBanChanged {
+            target,
+            banned: false,
+        });
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_UNBAN_TARGET,
+            ctx.accounts.authority.key(),
+            &[target.as_ref()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Once created, every admin-gated instruction must append to the log.
+    pub fn init_admin_log(ctx: Context<InitAdminLog>) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        state.require_admin(&ctx.accounts.authority.key())?;
+
+        let mut log = ctx.accounts.admin_log.load_init()?;
+        log.registry = state.key();
+        log.total = 0;
+        log.bump = ctx.bumps.admin_log;
+
+        state.admin_log_enabled = true;
+
+        Ok(())
+    }
+
+    /// Once created, every commit must also be appended here.
+    pub fn init_recent_commits(ctx: Context<InitRecentCommits>) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        state.require_admin(&ctx.accounts.authority.key())?;
+
+        let mut recent = ctx.accounts.recent_commits.load_init()?;
+        recent.registry = state.key();
+        recent.total = 0;
+        recent.bump = ctx.bumps.recent_commits;
+
+        state.recent_commits_enabled = true;
+
+        Ok(())
+    }
+
+    /// Starts a new commit log segment. The account is too large to create
+    /// through CPI, so the client allocates it beforehand. A full segment is
+    /// retired by starting the next one, which links back to it.
+    pub fn init_commit_log(ctx: Context<InitCommitLog>) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        state.require_admin(&ctx.accounts.authority.key())?;
+
+        let segment = if state.commit_log == Pubkey::default() {
+            0
+        } else {
+            let previous = ctx
+                .accounts
+                .previous_commit_log
+                .as_ref()
+                .ok_or(MoatError::CommitLogRequired)?;
+            require_keys_eq!(previous.key(), state.commit_log, MoatError::CommitLogRequired);
+            previous
+                .load()?
+                .segment
+                .checked_add(1)
+                .ok_or(MoatError::Overflow)?
+        };
+
+        let mut log = ctx.accounts.commit_log.load_init()?;
+        log.registry = state.key();
+        log.previous = state.commit_log;
+        log.len = 0;
+        log.segment = segment;
+
+        state.commit_log = ctx.accounts.commit_log.key();
+
+        Ok(())
+    }
+
+    /// Grants `holder` a set of `ROLE_*` capability bits, replacing any it had.
+    pub fn grant_role(ctx: Context<GrantRole>, holder: Pubkey, capabilities: u8) -> Result<()> {
+        let state = &ctx.accounts.state;
+
+        state.require_admin(&ctx.accounts.authority.key())?;
+        require!(
+            capabilities != 0 && capabilities & !ROLE_ALL == 0,
+            MoatError::InvalidCapabilities
+        );
+
+        let role = &mut ctx.accounts.role;
+        role.registry = state.key();
+        role.holder = holder;
+        role.capabilities = capabilities;
+        role.bump = ctx.bumps.role;
+
+        emit!(RoleSet {
+            holder,
+            capabilities,
+        });
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_GRANT_ROLE,
+            ctx.accounts.authority.key(),
+            &[holder.as_ref(), &[capabilities]],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn revoke_role(ctx: Context<RevokeRole>) -> Result<()> {
+        ctx.accounts.state.require_admin(&ctx.accounts.authority.key())?;
+
+        emit!(RoleSet {
+            holder: ctx.accounts.role.holder,
+            capabilities: 0,
+        });
+
+        record_admin_action(
+            &ctx.accounts.state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_REVOKE_ROLE,
+            ctx.accounts.authority.key(),
+            &[ctx.accounts.role.holder.as_ref()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Issues a session granting `session_key` a subset of `ROLE_*` bits until
+    /// `expires_at_slot` (inclusive).
+    pub fn create_session(
+        ctx: Context<CreateSession>,
+        session_key: Pubkey,
+        capabilities: u8,
+        expires_at_slot: u64,
+    ) -> Result<()> {
+        let state = &ctx.accounts.state;
+
+        state.require_admin(&ctx.accounts.authority.key())?;
+        require!(
+            capabilities != 0 && capabilities & !ROLE_ALL == 0,
+            MoatError::InvalidCapabilities
+        );
+        require!(expires_at_slot > Clock::get()?.slot, MoatError::InvalidExpiry);
+
+        let session = &mut ctx.accounts.session;
+        session.registry = state.key();
+        session.session_key = session_key;
+        session.capabilities = capabilities;
+        session.expires_at_slot = expires_at_slot;
+        session.bump = ctx.bumps.session;
+
+        emit!(SessionCreated {
+            session_key,
+            capabilities,
+            expires_at_slot,
+        });
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_CREATE_SESSION,
+            ctx.accounts.authority.key(),
+            &[session_key.as_ref(), &[capabilities], &expires_at_slot.to_le_bytes()],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn revoke_session(ctx: Context<RevokeSession>) -> Result<()> {
+        ctx.accounts.state.require_admin(&ctx.accounts.authority.key())?;
+
+        emit!(SessionRevoked {
+            session_key: ctx.accounts.session.session_key,
+        });
+
+        record_admin_action(
+            &ctx.accounts.state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_REVOKE_SESSION,
+            ctx.accounts.authority.key(),
+            &[ctx.accounts.session.session_key.as_ref()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Fails unless `expected` is the authority's next nonce, then advances it
+    /// and arms it for one action. Session keys and delegates must prepend this
+    /// to each action they sign, so a payload re-signed and resubmitted with a
+    /// fresh blockhash cannot apply twice.
+    pub fn consume_nonce(ctx: Context<ConsumeNonce>, expected: u64) -> Result<()> {
+        let nonce = &mut ctx.accounts.nonce;
+
+        if nonce.authority == Pubkey::default() {
+            nonce.registry = ctx.accounts.state.key();
+            nonce.authority = ctx.accounts.authority.key();
+            nonce.bump = ctx.bumps.nonce;
+        }
+        require!(nonce.nonce == expected, MoatError::InvalidNonce);
+
+        nonce.nonce = nonce.nonce.checked_add(1).ok_or(MoatError::Overflow)?;
+        nonce.armed = true;
+
+        Ok(())
+    }
+
+    /// Allowlists `registrar` for `register_entry` by adding ROLE_REGISTER to its role.
+    pub fn add_registrar(ctx: Context<GrantRole>, registrar: Pubkey) -> Result<()> {
+        let state = &ctx.accounts.state;
+
+        state.require_admin(&ctx.accounts.authority.key())?;
+
+        let role = &mut ctx.accounts.role;
+        role.registry = state.key();
+        role.holder = registrar;
+        role.capabilities |= ROLE_REGISTER;
+        role.bump = ctx.bumps.role;
+
+        emit!(RoleSet {
+            holder: registrar,
+            capabilities: role.capabilities,
+        });
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_ADD_REGISTRAR,
+            ctx.accounts.authority.key(),
+            &[registrar.as_ref()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Clears ROLE_REGISTER, closing the role once it holds nothing else.
+    pub fn remove_registrar(ctx: Context<RemoveRegistrar>) -> Result<()> {
+        ctx.accounts.state.require_admin(&ctx.accounts.authority.key())?;
+
+        let role = &mut ctx.accounts.role;
+        role.capabilities &= !ROLE_REGISTER;
+
+        emit!(RoleSet {
+            holder: role.holder,
+            capabilities: role.capabilities,
+        });
+
+        record_admin_action(
+            &ctx.accounts.state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_REMOVE_REGISTRAR,
+            ctx.accounts.authority.key(),
+            &[role.holder.as_ref()],
+        )?;
+
+        if role.capabilities == 0 {
+            role.close(ctx.accounts.authority.to_account_info())?;
+        }
+
+        Ok(())
+    }
+
+    pub fn propose_admin(ctx: Context<ConfigureRegistry>, new_admin: Pubkey) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        require_keys_eq!(state.admin, ctx.accounts.authority.key(), MoatError::Unauthorized);
+        require!(state.timelock_delay == 0, MoatError::TimelockRequired);
+
+        state.pending_admin = new_admin;
+
+        emit!(AdminTransferProposed {
+            admin: state.admin,
+            pending_admin: new_admin,
+        });
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_PROPOSE_ADMIN,
+            ctx.accounts.authority.key(),
+            &[new_admin.as_ref()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Proposes a Realms governance's native treasury as admin. The treasury
+    /// PDA can only sign from an executed governance proposal, so once that
+    /// proposal calls `accept_admin` every admin-gated instruction requires a
+    /// passed vote.
+    pub fn propose_governance_admin(ctx: Context<ProposeGovernanceAdmin>) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        let governance = ctx.accounts.governance.key();
+        let governance_program = *ctx.accounts.governance.owner;
+
+        require_keys_eq!(state.admin, ctx.accounts.authority.key(), MoatError::Unauthorized);
+        require!(state.timelock_delay == 0, MoatError::TimelockRequired);
+        require!(
+            !ctx.accounts.governance.data_is_empty()
+                && governance_program != system_program::ID
+                && governance_program != crate::ID,
+            MoatError::InvalidGovernance
+        );
+
+        let (treasury, _) = Pubkey::find_program_address(
+            &[b"native-treasury", governance.as_ref()],
+            &governance_program,
+        );
+        state.pending_admin = treasury;
+        state.governance = governance;
+
+        emit!(AdminTransferProposed {
+            admin: state.admin,
+            pending_admin: treasury,
+        });
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_PROPOSE_ADMIN,
+            ctx.accounts.authority.key(),
+            &[treasury.as_ref(), governance.as_ref()],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        let new_admin = ctx.accounts.new_admin.key();
+
+        require_keys_neq!(state.pending_admin, Pubkey::default(), MoatError::NoPendingTransfer);
+        require_keys_eq!(state.pending_admin, new_admin, MoatError::Unauthorized);
+
+        let previous_admin = state.admin;
+        state.admin = new_admin;
+        state.pending_admin = Pubkey::default();
+        state.previous_admin = previous_admin;
+        state.previous_admin_valid_until = Clock::get()?
+            .slot
+            .saturating_add(state.rotation_grace_slots);
+
+        emit!(AdminTransferAccepted {
+            previous_admin,
+            admin: new_admin,
+        });
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_ACCEPT_ADMIN,
+            new_admin,
+            &[new_admin.as_ref()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Starts replacing the admin with `new_admin`. Calling again restarts the delay.
+    pub fn initiate_recovery(ctx: Context<Recover>, new_admin: Pubkey) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        require_keys_neq!(state.recovery_authority, Pubkey::default(), MoatError::Unauthorized);
+        require_keys_eq!(
+            state.recovery_authority,
+            ctx.accounts.recovery_authority.key(),
+            MoatError::Unauthorized
+        );
+        require_keys_neq!(new_admin, Pubkey::default(), MoatError::Unauthorized);
+
+        let eta = Clock::get()?
+            .unix_timestamp
+            .checked_add(RECOVERY_DELAY)
+            .ok_or(MoatError::Overflow)?;
+        state.recovery_admin = new_admin;
+        state.recovery_eta = eta;
+
+        emit!(RecoveryInitiated {
+            admin: state.admin,
+            new_admin,
+            eta,
+        });
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_INITIATE_RECOVERY,
+            ctx.accounts.recovery_authority.key(),
+            &[new_admin.as_ref()],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn complete_recovery(ctx: Context<Recover>) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        require_keys_eq!(
+            state.recovery_authority,
+            ctx.accounts.recovery_authority.key(),
+            MoatError::Unauthorized
+        );
+        require_keys_neq!(state.recovery_admin, Pubkey::default(), MoatError::NoPendingTransfer);
+        require!(
+            Clock::get()?.unix_timestamp >= state.recovery_eta,
+            MoatError::TimelockNotElapsed
+        );
+
+        let previous_admin = state.admin;
+        state.admin = state.recovery_admin;
+        state.pending_admin = Pubkey::default();
+        // No grace period: the key being recovered from may be compromised.
+        state.previous_admin = Pubkey::default();
+        state.previous_admin_valid_until = 0;
+        state.recovery_admin = Pubkey::default();
+        state.recovery_eta = 0;
+
+        emit!(AdminTransferAccepted {
+            previous_admin,
+            admin: state.admin,
+        });
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_COMPLETE_RECOVERY,
+            ctx.accounts.recovery_authority.key(),
+            &[state.admin.as_ref()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Enables the timelock. Once enabled, the delay itself can only be changed
+    /// through a queued `SetTimelockDelay` action.
+    pub fn set_timelock_delay(ctx: Context<ConfigureRegistry>, delay: i64) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        require_keys_eq!(state.admin, ctx.accounts.authority.key(), MoatError::Unauthorized);
+        require!(state.timelock_delay == 0, MoatError::TimelockRequired);
+        require!(delay >= 0, MoatError::InvalidInterval);
+
+        state.timelock_delay = delay;
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_SET_TIMELOCK_DELAY,
+            ctx.accounts.authority.key(),
+            &[&delay.to_le_bytes()],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn queue_action(ctx: Context<QueueAction>, action: TimelockAction) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        require_keys_eq!(state.admin, ctx.accounts.authority.key(), MoatError::Unauthorized);
+        match &action {
+            TimelockAction::UpdateKind { label, .. } => {
+                require!(label.len() <= MAX_KIND_LABEL_LEN, MoatError::LabelTooLong);
+            }
+            TimelockAction::SetTimelockDelay { delay } => {
+                require!(*delay >= 0, MoatError::InvalidInterval);
+            }
+            TimelockAction::ProposeAdmin { .. } | TimelockAction::SetDisputeBonds { .. } => {}
+        }
+
+        let now = Clock::get()?.unix_timestamp;
+        let eta = now
+            .checked_add(state.timelock_delay)
+            .ok_or(MoatError::Overflow)?;
+
+        let pending = &mut ctx.accounts.pending_action;
+        pending.registry = state.key();
+        pending.id = state.next_action_id;
+        pending.queued_by = ctx.accounts.authority.key();
+        pending.action = action.clone();
+        pending.queued_at = now;
+        pending.eta = eta;
+        pending.bump = ctx.bumps.pending_action;
+
+        state.next_action_id = state
+            .next_action_id
+            .checked_add(1)
+            .ok_or(MoatError::Overflow)?;
+
+        emit!(ActionQueued {
+            id: pending.id,
+            action,
+            eta,
+        });
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_QUEUE_ACTION,
+            ctx.accounts.authority.key(),
+            &[&pending.id.to_le_bytes(), &pending.action.try_to_vec()?],
+        )?;
+
+        Ok(())
+    }
+
+    /// Permissionless once the delay has elapsed.
+    pub fn execute_action(ctx: Context<ExecuteAction>) -> Result<()> {
+        let pending = &ctx.accounts.pending_action;
+        let state = &mut ctx.accounts.state;
+
+        require!(
+            Clock::get()?.unix_timestamp >= pending.eta,
+            MoatError::TimelockNotElapsed
+        );
+
+        match &pending.action {
+            TimelockAction::ProposeAdmin { new_admin } => {
+                state.pending_admin = *new_admin;
+
+                emit!(AdminTransferProposed {
+                    admin: state.admin,
+                    pending_admin: *new_admin,
+                });
+            }
+            TimelockAction::UpdateKind {
+                kind,
+                label,
+                enabled,
+            } => {
+                let definition = ctx
+                    .accounts
+                    .kind_definition
+                    .as_mut()
+                    .ok_or(MoatError::InvalidActionAccounts)?;
+                require!(definition.kind == *kind, MoatError::InvalidActionAccounts);
+
+                definition.label = label.clone();
+                definition.enabled = *enabled;
+
+                emit!(KindDefined {
+                    kind: *kind,
+                    label: label.clone(),
+                    enabled: *enabled,
+                });
+            }
+            TimelockAction::SetTimelockDelay { delay } => {
+                state.timelock_delay = *delay;
+            }
+            TimelockAction::SetDisputeBonds {
+                challenge_bond,
+                commit_stake,
+            } => {
+                state.challenge_bond = *challenge_bond;
+                state.commit_stake = *commit_stake;
+            }
+        }
+
+        emit!(ActionExecuted { id: pending.id });
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_EXECUTE_ACTION,
+            pending.queued_by,
+            &[&pending.id.to_le_bytes()],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn cancel_action(ctx: Context<CancelAction>) -> Result<()> {
+        ctx.accounts.state.require_admin(&ctx.accounts.authority.key())?;
+
+        emit!(ActionCancelled {
+            id: ctx.accounts.pending_action.id,
+        });
+
+        record_admin_action(
+            &ctx.accounts.state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_CANCEL_ACTION,
+            ctx.accounts.authority.key(),
+            &[&ctx.accounts.pending_action.id.to_le_bytes()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Creates the registry multisig. It only takes control once the admin
+    /// proposes its signer PDA and a multisig proposal executes `accept_admin`.
+    pub fn create_multisig(
+        ctx: Context<CreateMultisig>,
+        owners: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        ctx.accounts.state.require_admin(&ctx.accounts.authority.key())?;
+        validate_multisig_owners(&owners, threshold)?;
+
+        let multisig = &mut ctx.accounts.multisig;
+        multisig.registry = ctx.accounts.state.key();
+        multisig.owners = owners.clone();
+        multisig.threshold = threshold;
+        multisig.owner_set_seqno = 0;
+        multisig.next_proposal_id = 0;
+        multisig.bump = ctx.bumps.multisig;
+        multisig.signer_bump = ctx.bumps.multisig_signer;
+
+        emit!(MultisigOwnersSet {
+            multisig: multisig.key(),
+            signer: ctx.accounts.multisig_signer.key(),
+            owners,
+            threshold,
+            owner_set_seqno: 0,
+        });
+
+        record_admin_action(
+            &ctx.accounts.state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_CREATE_MULTISIG,
+            ctx.accounts.authority.key(),
+            &[ctx.accounts.multisig.key().as_ref(), &[threshold]],
+        )?;
+
+        Ok(())
+    }
+
+    /// Only callable by the multisig itself, through an executed proposal.
+    pub fn set_multisig_owners(
+        ctx: Context<SetMultisigOwners>,
+        owners: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        validate_multisig_owners(&owners, threshold)?;
+
+        let multisig = &mut ctx.accounts.multisig;
+        multisig.owners = owners.clone();
+        multisig.threshold = threshold;
+        // Pending proposals were approved by the old owner set.
+        multisig.owner_set_seqno = multisig
+            .owner_set_seqno
+            .checked_add(1)
+            .ok_or(MoatError::Overflow)?;
+
+        emit!(MultisigOwnersSet {
+            multisig: multisig.key(),
+            signer: ctx.accounts.multisig_signer.key(),
+            owners,
+            threshold,
+            owner_set_seqno: multisig.owner_set_seqno,
+        });
+
+        Ok(())
+    }
+
+    pub fn propose_multisig_action(
+        ctx: Context<ProposeMultisigAction>,
+        instruction_program: Pubkey,
+        instruction_accounts: Vec<MultisigAccountMeta>,
+        instruction_data: Vec<u8>,
+    ) -> Result<()> {
+        let multisig = &mut ctx.accounts.multisig;
+        let proposer = ctx.accounts.proposer.key();
+        let owner_index = multisig.owner_index(&proposer)?;
+
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.multisig = multisig.key();
+        proposal.id = multisig.next_proposal_id;
+        proposal.proposer = proposer;
+        proposal.program_id = instruction_program;
+        proposal.accounts = instruction_accounts;
+        proposal.data = instruction_data;
+        proposal.approvals = 1 << owner_index;
+        proposal.owner_set_seqno = multisig.owner_set_seqno;
+        proposal.executed = false;
+        proposal.created_at = Clock::get()?.unix_timestamp;
+        proposal.bump = ctx.bumps.proposal;
+
+        multisig.next_proposal_id = multisig
+            .next_proposal_id
+            .checked_add(1)
+            .ok_or(MoatError::Overflow)?;
+
+        emit!(MultisigActionProposed {
+            multisig: multisig.key(),
+            proposal: proposal.key(),
+            id: proposal.id,
+            proposer,
+        });
+
+        Ok(())
+    }
+
+    pub fn approve_multisig_action(ctx: Context<ApproveMultisigAction>) -> Result<()> {
+        let multisig = &ctx.accounts.multisig;
+        let owner = ctx.accounts.owner.key();
+        let owner_index = multisig.owner_index(&owner)?;
+
+        let proposal = &mut ctx.accounts.proposal;
+        require!(!proposal.executed, MoatError::ProposalAlreadyExecuted);
+        require!(
+            proposal.owner_set_seqno == multisig.owner_set_seqno,
+            MoatError::StaleProposal
+        );
+
+        proposal.approvals |= 1 << owner_index;
+
+        emit!(MultisigActionApproved {
+            multisig: multisig.key(),
+            proposal: proposal.key(),
+            id: proposal.id,
+            owner,
+            approvals: proposal.approvals,
+        });
+
+        Ok(())
+    }
+
+    /// Permissionless once the threshold is met. The stored instruction is
+    /// invoked with the multisig signer PDA signing; every account it
+    /// references must be passed as a remaining account.
+    pub fn execute_multisig_action(ctx: Context<ExecuteMultisigAction>) -> Result<()> {
+        let multisig = &ctx.accounts.multisig;
+        let proposal = &mut ctx.accounts.proposal;
+
+        require!(!proposal.executed, MoatError::ProposalAlreadyExecuted);
+        require!(
+            proposal.owner_set_seqno == multisig.owner_set_seqno,
+            MoatError::StaleProposal
+        );
+        require!(
+            proposal.approvals.count_ones() >= u32::from(multisig.threshold),
+            MoatError::NotEnoughApprovals
+        );
+
+        // Persist before the CPI so the action cannot be replayed re-entrantly.
+        proposal.executed = true;
+        proposal.exit(&crate::ID)?;
+
+        let signer = ctx.accounts.multisig_signer.key();
+        let instruction = Instruction {
+            program_id: proposal.program_id,
+            accounts: proposal
+                .accounts
+                .iter()
+                .map(|meta| AccountMeta {
+                    pubkey: meta.pubkey,
+                    is_signer: meta.is_signer || meta.pubkey == signer,
+                    is_writable: meta.is_writable,
+                })
+                .collect(),
+            data: proposal.data.clone(),
+        };
+        let multisig_key = multisig.key();
+        let signer_seeds: &[&[u8]] = &[
+            b"multisig_signer",
+            multisig_key.as_ref(),
+            &[multisig.signer_bump],
+        ];
+        invoke_signed(&instruction, ctx.remaining_accounts, &[signer_seeds])?;
+
+        emit!(MultisigActionExecuted {
+            multisig: multisig_key,
+            proposal: proposal.key(),
+            id: proposal.id,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_require_upgrade_authority(
+        ctx: Context<ConfigureRegistry>,
+        required: bool,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        require_capability(
+            state,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.role.as_deref_mut(),
+            ctx.accounts.session.as_deref_mut(),
+            ctx.accounts.nonce.as_deref_mut(),
+            ROLE_CONFIGURE,
+        )?;
+
+        state.require_upgrade_authority = required;
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_SET_REQUIRE_UPGRADE_AUTHORITY,
+            ctx.accounts.authority.key(),
+            &[&[u8::from(required)]],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn set_auditor(ctx: Context<ConfigureRegistry>, auditor: Pubkey) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        require_capability(
+            state,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.role.as_deref_mut(),
+            ctx.accounts.session.as_deref_mut(),
+            ctx.accounts.nonce.as_deref_mut(),
+            ROLE_CONFIGURE,
+        )?;
+
+        state.auditor = auditor;
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_SET_AUDITOR,
+            ctx.accounts.authority.key(),
+            &[auditor.as_ref()],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn set_min_update_interval(
+        ctx: Context<ConfigureRegistry>,
+        min_update_interval: i64,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        require_capability(
+            state,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.role.as_deref_mut(),
+            ctx.accounts.session.as_deref_mut(),
+            ctx.accounts.nonce.as_deref_mut(),
+            ROLE_CONFIGURE,
+        )?;
+        require!(min_update_interval >= 0, MoatError::InvalidInterval);
+
+        state.min_update_interval = min_update_interval;
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_SET_MIN_UPDATE_INTERVAL,
+            ctx.accounts.authority.key(),
+            &[&min_update_interval.to_le_bytes()],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn set_risk_authority(
+        ctx: Context<ConfigureRegistry>,
+        risk_authority: Pubkey,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        require_capability(
+            state,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.role.as_deref_mut(),
+            ctx.accounts.session.as_deref_mut(),
+            ctx.accounts.nonce.as_deref_mut(),
+            ROLE_CONFIGURE,
+        )?;
+
+        state.risk_authority = risk_authority;
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_SET_RISK_AUTHORITY,
+            ctx.accounts.authority.key(),
+            &[risk_authority.as_ref()],
+        )?;
+
+        Ok(())
+    }
+
+    /// When enabled, anyone may register a program whose upgrade authority co-signs.
+    pub fn set_open_registration(
+        ctx: Context<ConfigureRegistry>,
+        open_registration: bool,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        require_capability(
+            state,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.role.as_deref_mut(),
+            ctx.accounts.session.as_deref_mut(),
+            ctx.accounts.nonce.as_deref_mut(),
+            ROLE_CONFIGURE,
+        )?;
+
+        state.open_registration = open_registration;
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_SET_OPEN_REGISTRATION,
+            ctx.accounts.authority.key(),
+            &[&[u8::from(open_registration)]],
+        )?;
+
+        Ok(())
+    }
+
+    /// Restricts `commit_batch` to this key and ROLE_COMMIT holders. Unset
+    /// (the default) leaves commits open to any creator. Admin only, since the
+    /// key gets commit rights.
+    pub fn set_commit_authority(
+        ctx: Context<ConfigureRegistry>,
+        commit_authority: Pubkey,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        state.require_admin(&ctx.accounts.authority.key())?;
+
+        state.previous_commit_authority = state.commit_authority;
+        state.previous_commit_authority_valid_until = Clock::get()?
+            .slot
+            .saturating_add(state.rotation_grace_slots);
+        state.commit_authority = commit_authority;
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_SET_COMMIT_AUTHORITY,
+            ctx.accounts.authority.key(),
+            &[commit_authority.as_ref()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Operators whose Ed25519 signatures over `registry || entry || merkle_root ||
+    /// seq` (u64 LE) a commit must carry, `threshold` of them at least. A
+    /// threshold of 0 disables it.
+    pub fn set_commit_cosigners(
+        ctx: Context<SetCommitCosigners>,
+        signers: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        state.require_admin(&ctx.accounts.authority.key())?;
+        require!(
+            signers.len() <= MAX_COMMIT_COSIGNERS
+                && usize::from(threshold) <= signers.len()
+                && signers
+                    .iter()
+                    .enumerate()
+                    .all(|(i, signer)| !signers[..i].contains(signer)),
+            MoatError::InvalidCosigners
+        );
+
+        let cosigners = &mut ctx.accounts.cosigners;
+        cosigners.registry = state.key();
+        cosigners.threshold = threshold;
+        cosigners.signers = signers;
+        cosigners.bump = ctx.bumps.cosigners;
+
+        state.commit_cosigner_threshold = threshold;
+
+        let mut payload = vec![threshold];
+        for signer in &cosigners.signers {
+            payload.extend_from_slice(signer.as_ref());
+        }
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_SET_COMMIT_COSIGNERS,
+            ctx.accounts.authority.key(),
+            &[&payload],
+        )?;
+
+        Ok(())
+    }
+
+    /// Key whose Ed25519 signature over `registry || entry || merkle_root || seq`
+    /// every commit must carry, letting an off-chain auditor co-sign roots
+    /// without signing the transaction. The default key disables it. Admin only,
+    /// like the co-signer set.
+    pub fn set_root_attestor(ctx: Context<ConfigureRegistry>, root_attestor: Pubkey) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        state.require_admin(&ctx.accounts.authority.key())?;
+
+        state.root_attestor = root_attestor;
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_SET_ROOT_ATTESTOR,
+            ctx.accounts.authority.key(),
+            &[root_attestor.as_ref()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Ethereum address whose secp256k1 signature over the same message as
+    /// `set_root_attestor` every commit must carry, checked through the
+    /// Secp256k1 precompile. The precompile signs keccak256 of the raw message,
+    /// with no EIP-191 prefix. The zero address disables it. Admin only.
+    pub fn set_root_attestor_eth(
+        ctx: Context<ConfigureRegistry>,
+        root_attestor_eth: [u8; 20],
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        state.require_admin(&ctx.accounts.authority.key())?;
+
+        state.root_attestor_eth = root_attestor_eth;
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_SET_ROOT_ATTESTOR_ETH,
+            ctx.accounts.authority.key(),
+            &[&root_attestor_eth],
+        )?;
+
+        Ok(())
+    }
+
+    /// Program every commit must pass through before it is accepted. Commits
+    /// CPI into its `verify_batch(registry, entry, seq, merkle_root, leaf_count,
+    /// proof)` with the commit's `validity_proof` and abort if it fails. The
+    /// default key keeps commits optimistic. Admin only, since resetting it
+    /// turns validity-proven commits back into optimistic ones.
+    pub fn set_commit_verifier(
+        ctx: Context<ConfigureRegistry>,
+        commit_verifier: Pubkey,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        state.require_admin(&ctx.accounts.authority.key())?;
+
+        state.commit_verifier = commit_verifier;
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_SET_COMMIT_VERIFIER,
+            ctx.accounts.authority.key(),
+            &[commit_verifier.as_ref()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Root of the `AllowlistLeafV1` tree `register_entry_with_proof` checks
+    /// against. All zeroes turns proof-gated registration off.
+    pub fn set_registration_allowlist(
+        ctx: Context<ConfigureRegistry>,
+        root: [u8; 32],
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        require_capability(
+            state,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.role.as_deref_mut(),
+            ctx.accounts.session.as_deref_mut(),
+            ctx.accounts.nonce.as_deref_mut(),
+            ROLE_CONFIGURE,
+        )?;
+
+        state.registration_allowlist = root;
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_SET_REGISTRATION_ALLOWLIST,
+            ctx.accounts.authority.key(),
+            &[&root],
+        )?;
+
+        Ok(())
+    }
+
+    /// Minimum slots between actions by the same role or session key. The
+    /// admin is not rate limited.
+    pub fn set_min_action_slot_gap(
+        ctx: Context<ConfigureRegistry>,
+        min_action_slot_gap: u64,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        require_capability(
+            state,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.role.as_deref_mut(),
+            ctx.accounts.session.as_deref_mut(),
+            ctx.accounts.nonce.as_deref_mut(),
+            ROLE_CONFIGURE,
+        )?;
+
+        state.min_action_slot_gap = min_action_slot_gap;
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_SET_MIN_ACTION_SLOT_GAP,
+            ctx.accounts.authority.key(),
+            &[&min_action_slot_gap.to_le_bytes()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Seconds a new commit stays open to dispute before it can be finalized.
+    /// Applies to commits made after the change.
+    pub fn set_dispute_window(ctx: Context<ConfigureRegistry>, dispute_window: i64) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        require_capability(
+            state,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.role.as_deref_mut(),
+            ctx.accounts.session.as_deref_mut(),
+            ctx.accounts.nonce.as_deref_mut(),
+            ROLE_CONFIGURE,
+        )?;
+        require!(dispute_window >= 0, MoatError::InvalidInterval);
+
+        state.dispute_window = dispute_window;
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_SET_DISPUTE_WINDOW,
+            ctx.accounts.authority.key(),
+            &[&dispute_window.to_le_bytes()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Lamports a challenger must post, and lamports a committer locks in each
+    /// new commit as slashable stake. Once the timelock is enabled, this goes
+    /// through a queued `SetDisputeBonds` action.
+    pub fn set_dispute_bonds(
+        ctx: Context<ConfigureRegistry>,
+        challenge_bond: u64,
+        commit_stake: u64,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        require_capability(
+            state,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.role.as_deref_mut(),
+            ctx.accounts.session.as_deref_mut(),
+            ctx.accounts.nonce.as_deref_mut(),
+            ROLE_CONFIGURE,
+        )?;
+        require!(state.timelock_delay == 0, MoatError::TimelockRequired);
+
+        state.challenge_bond = challenge_bond;
+        state.commit_stake = commit_stake;
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_SET_DISPUTE_BONDS,
+            ctx.accounts.authority.key(),
+            &[&challenge_bond.to_le_bytes(), &commit_stake.to_le_bytes()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Seconds per settlement epoch; 0 stops bucketing. Numbering continues
+    /// from the next epoch, so earlier commits keep their epochs.
+    pub fn set_epoch_length(ctx: Context<ConfigureRegistry>, epoch_length: i64) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        require_capability(
+            state,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.role.as_deref_mut(),
+            ctx.accounts.session.as_deref_mut(),
+            ctx.accounts.nonce.as_deref_mut(),
+            ROLE_CONFIGURE,
+        )?;
+        require!(epoch_length >= 0, MoatError::InvalidInterval);
+
+        let now = Clock::get()?.unix_timestamp;
+        if state.epoch_length > 0 {
+            state.epoch_offset = state
+                .current_epoch(now)
+                .checked_add(1)
+                .ok_or(MoatError::Overflow)?;
+        }
+        state.epoch_length = epoch_length;
+        state.epoch_origin = now;
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_SET_EPOCH_LENGTH,
+            ctx.accounts.authority.key(),
+            &[&epoch_length.to_le_bytes()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Slots for which the outgoing admin or commit authority stays valid after
+    /// a rotation. Recovery revokes the old admin immediately.
+    pub fn set_rotation_grace_slots(
+        ctx: Context<ConfigureRegistry>,
+        rotation_grace_slots: u64,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        require_capability(
+            state,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.role.as_deref_mut(),
+            ctx.accounts.session.as_deref_mut(),
+            ctx.accounts.nonce.as_deref_mut(),
+            ROLE_CONFIGURE,
+        )?;
+
+        state.rotation_grace_slots = rotation_grace_slots;
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_SET_ROTATION_GRACE_SLOTS,
+            ctx.accounts.authority.key(),
+            &[&rotation_grace_slots.to_le_bytes()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Minimum slots between any two commits to the registry, capping how many
+    /// roots a leaked commit key can publish before it is rotated. Only applies
+    /// while a commit authority gates commits; an open registry has no commit
+    /// keys to leak, and a shared window would let any one caller take it.
+    pub fn set_min_commit_interval(
+        ctx: Context<ConfigureRegistry>,
+        min_commit_interval_slots: u64,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        require_capability(
+            state,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.role.as_deref_mut(),
+            ctx.accounts.session.as_deref_mut(),
+            ctx.accounts.nonce.as_deref_mut(),
+            ROLE_CONFIGURE,
+        )?;
+
+        state.min_commit_interval_slots = min_commit_interval_slots;
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_SET_MIN_COMMIT_INTERVAL,
+            ctx.accounts.authority.key(),
+            &[&min_commit_interval_slots.to_le_bytes()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Seconds after creation once a settled commit may be pruned to a
+    /// tombstone; 0 keeps commits forever.
+    pub fn set_commit_retention(
+        ctx: Context<ConfigureRegistry>,
+        commit_retention: i64,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        require_capability(
+            state,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.role.as_deref_mut(),
+            ctx.accounts.session.as_deref_mut(),
+            ctx.accounts.nonce.as_deref_mut(),
+            ROLE_CONFIGURE,
+        )?;
+        require!(commit_retention >= 0, MoatError::InvalidInterval);
+
+        state.commit_retention = commit_retention;
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_SET_COMMIT_RETENTION,
+            ctx.accounts.authority.key(),
+            &[&commit_retention.to_le_bytes()],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn set_guardian(ctx: Context<ConfigureRegistry>, guardian: Pubkey) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        require_capability(
+            state,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.role.as_deref_mut(),
+            ctx.accounts.session.as_deref_mut(),
+            ctx.accounts.nonce.as_deref_mut(),
+            ROLE_CONFIGURE,
+        )?;
+
+        state.guardian = guardian;
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_SET_GUARDIAN,
+            ctx.accounts.authority.key(),
+            &[guardian.as_ref()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Callable by the guardian, a ROLE_PAUSE holder or the admin. Blocks
+    /// registration, commits and entry mutations; admin configuration,
+    /// `revoke_entry` and `freeze_entry` stay available for incident response.
+    pub fn pause(ctx: Context<ConfigureRegistry>) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        let authority = ctx.accounts.authority.key();
+
+        if state.guardian != authority {
+            require_capability(
+                state,
+                &authority,
+                ctx.accounts.role.as_deref_mut(),
+                ctx.accounts.session.as_deref_mut(),
+                ctx.accounts.nonce.as_deref_mut(),
+                ROLE_PAUSE,
+            )?;
+        }
+
+        state.paused = true;
+
+        emit!(RegistryPauseChanged {
+            paused: true,
+            authority,
+        });
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_PAUSE,
+            authority,
+            &[],
+        )?;
+
+        Ok(())
+    }
+
+    // Deliberately admin-only so a leaked guardian key cannot flap the switch.
+    pub fn unpause(ctx: Context<ConfigureRegistry>) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        let authority = ctx.accounts.authority.key();
+
+        state.require_admin(&authority)?;
+
+        state.paused = false;
+
+        emit!(RegistryPauseChanged {
+            paused: false,
+            authority,
+        });
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_UNPAUSE,
+            authority,
+            &[],
+        )?;
+
+        Ok(())
+    }
+
+    /// Setting bits follows `pause` permissions; clearing any bit follows `unpause`.
+    pub fn set_paused_operations(
+        ctx: Context<ConfigureRegistry>,
+        paused_operations: u8,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        let authority = ctx.accounts.authority.key();
+
+        require!(paused_operations & !PAUSE_ALL == 0, MoatError::InvalidPauseFlags);
+        if state.paused_operations & !paused_operations != 0 {
+            state.require_admin(&authority)?;
+        } else if state.guardian != authority {
+            require_capability(
+                state,
+                &authority,
+                ctx.accounts.role.as_deref_mut(),
+                ctx.accounts.session.as_deref_mut(),
+                ctx.accounts.nonce.as_deref_mut(),
+                ROLE_PAUSE,
+            )?;
+        }
+
+        state.paused_operations = paused_operations;
+
+        emit!(PausedOperationsChanged {
+            paused_operations,
+            authority,
+        });
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_SET_PAUSED_OPERATIONS,
+            authority,
+            &[&[paused_operations]],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn set_risk_score(ctx: Context<SetRiskScore>, risk_score: u8) -> Result<()> {
+        let state = &ctx.accounts.state;
+
+        state.require_not_paused(PAUSE_ATTEST)?;
+        require_keys_neq!(state.risk_authority, Pubkey::default(), MoatError::Unauthorized);
+        require_keys_eq!(
+            state.risk_authority,
+            ctx.accounts.risk_authority.key(),
+            MoatError::Unauthorized
+        );
+
+        let entry = &mut ctx.accounts.entry;
+        entry.risk_score = risk_score;
+        entry.risk_updated_at = Clock::get()?.unix_timestamp;
+        entry.version = entry.next_version()?;
+
+        emit!(EntryRiskScoreSet {
+            entry: entry.key(),
+            id: entry.id,
+            version: entry.version,
+            risk_score,
+            risk_updated_at: entry.risk_updated_at,
+        });
+
+        Ok(())
+    }
+
+    pub fn record_audit(ctx: Context<RecordAudit>, report_hash: [u8; 32]) -> Result<()> {
+        let state = &ctx.accounts.state;
+        let auditor = ctx.accounts.auditor.key();
+
+        state.require_not_paused(PAUSE_ATTEST)?;
+        require_keys_neq!(state.auditor, Pubkey::default(), MoatError::Unauthorized);
+        require_keys_eq!(state.auditor, auditor, MoatError::Unauthorized);
+
+        let entry = &mut ctx.accounts.entry;
+        let record = &mut ctx.accounts.audit_record;
+        record.entry = entry.key();
+        record.auditor = auditor;
+        record.report_hash = report_hash;
+        record.created_at = Clock::get()?.unix_timestamp;
+        record.active = true;
+        record.bump = ctx.bumps.audit_record;
+
+        entry.active_audits = entry.active_audits.checked_add(1).ok_or(MoatError::Overflow)?;
+        entry.audited = true;
+        entry.version = entry.next_version()?;
+
+        emit!(AuditRecorded {
+            entry: entry.key(),
+            id: entry.id,
+            version: entry.version,
+            auditor,
+            report_hash,
+            active: true,
+        });
+
+        Ok(())
+    }
+
+    pub fn revoke_audit(ctx: Context<RevokeAudit>) -> Result<()> {
+        let record = &mut ctx.accounts.audit_record;
+
+        ctx.accounts.state.require_not_paused(PAUSE_ATTEST)?;
+        require_keys_eq!(record.auditor, ctx.accounts.auditor.key(), MoatError::Unauthorized);
+        require!(record.active, MoatError::AuditNotActive);
+
+        record.active = false;
+
+        let entry = &mut ctx.accounts.entry;
+        entry.active_audits = entry.active_audits.saturating_sub(1);
+        entry.audited = entry.active_audits > 0;
+        entry.version = entry.next_version()?;
+
+        emit!(AuditRecorded {
+            entry: entry.key(),
+            id: entry.id,
+            version: entry.version,
+            auditor: record.auditor,
+            report_hash: record.report_hash,
+            active: false,
+        });
+
+        Ok(())
+    }
+
+    pub fn add_attestor(ctx: Context<AddAttestor>, attestor: Pubkey) -> Result<()> {
+        let state = &ctx.accounts.state;
+
+        state.require_admin(&ctx.accounts.authority.key())?;
+
+        let config = &mut ctx.accounts.attestor_config;
+        config.registry = state.key();
+        config.attestor = attestor;
+        config.bump = ctx.bumps.attestor_config;
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_ADD_ATTESTOR,
+            ctx.accounts.authority.key(),
+            &[attestor.as_ref()],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn remove_attestor(ctx: Context<RemoveAttestor>) -> Result<()> {
+        ctx.accounts.state.require_admin(&ctx.accounts.authority.key())?;
+
+        record_admin_action(
+            &ctx.accounts.state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_REMOVE_ATTESTOR,
+            ctx.accounts.authority.key(),
+            &[ctx.accounts.attestor_config.attestor.as_ref()],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn attest_entry(
+        ctx: Context<AttestEntry>,
+        claim_hash: [u8; 32],
+        expires_at: i64,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(expires_at == 0 || expires_at > now, MoatError::InvalidExpiry);
+
+        ctx.accounts.state.require_not_paused(PAUSE_ATTEST)?;
+        let attestation = &mut ctx.accounts.attestation;
+        // Revoked attestations are kept as history rather than overwritten.
+        require!(!attestation.revoked, MoatError::AttestationRevoked);
+
+        let entry = &mut ctx.accounts.entry;
+        if attestation.attestor == Pubkey::default() {
+            entry.attestation_count = entry
+                .attestation_count
+                .checked_add(1)
+                .ok_or(MoatError::Overflow)?;
+            entry.refresh_verified(ctx.accounts.state.attestation_threshold);
+            entry.version = entry.next_version()?;
+        }
+
+        attestation.entry = entry.key();
+        attestation.attestor = ctx.accounts.attestor.key();
+        attestation.claim_hash = claim_hash;
+        attestation.created_at = now;
+        attestation.expires_at = expires_at;
+        attestation.bump = ctx.bumps.attestation;
+
+        emit!(EntryAttested {
+            entry: attestation.entry,
+            attestor: attestation.attestor,
+            claim_hash,
+            expires_at,
+        });
+
+        Ok(())
+    }
+
+    pub fn revoke_attestation(ctx: Context<RevokeAttestation>, reason: u8) -> Result<()> {
+        let authority = ctx.accounts.authority.key();
+        let attestation = &mut ctx.accounts.attestation;
+
+        ctx.accounts.state.require_not_paused(PAUSE_ATTEST)?;
+        require!(
+            authority == attestation.attestor || ctx.accounts.state.is_admin(&authority)?,
+            MoatError::Unauthorized
+        );
+        require!(!attestation.revoked, MoatError::AttestationRevoked);
+
+        attestation.revoked = true;
+        attestation.revoked_at = Clock::get()?.unix_timestamp;
+        attestation.revocation_reason = reason;
+
+        let entry = &mut ctx.accounts.entry;
+        entry.attestation_count = entry.attestation_count.saturating_sub(1);
+        entry.refresh_verified(ctx.accounts.state.attestation_threshold);
+        entry.version = entry.next_version()?;
+
+        emit!(AttestationRevoked {
+            entry: attestation.entry,
+            attestor: attestation.attestor,
+            reason,
+            revoked_at: attestation.revoked_at,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_attestation_threshold(
+        ctx: Context<ConfigureRegistry>,
+        attestation_threshold: u16,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        require_capability(
+            state,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.role.as_deref_mut(),
+            ctx.accounts.session.as_deref_mut(),
+            ctx.accounts.nonce.as_deref_mut(),
+            ROLE_CONFIGURE,
+        )?;
+
+        state.attestation_threshold = attestation_threshold;
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_SET_ATTESTATION_THRESHOLD,
+            ctx.accounts.authority.key(),
+            &[&attestation_threshold.to_le_bytes()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Attestations a new commit needs before proofs against it verify; 0
+    /// makes commits verifiable without any. Existing commits keep the
+    /// quorum they were made under.
+    pub fn set_commit_attestation_quorum(
+        ctx: Context<ConfigureRegistry>,
+        quorum: u16,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        require_capability(
+            state,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.role.as_deref_mut(),
+            ctx.accounts.session.as_deref_mut(),
+            ctx.accounts.nonce.as_deref_mut(),
+            ROLE_CONFIGURE,
+        )?;
+
+        state.commit_attestation_quorum = quorum;
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_SET_COMMIT_ATTESTATION_QUORUM,
+            ctx.accounts.authority.key(),
+            &[&quorum.to_le_bytes()],
+        )?;
+
+        Ok(())
+    }
+
+    // Permissionless: re-derives `verified` after the threshold changes.
+    pub fn refresh_verification(ctx: Context<RefreshVerification>) -> Result<()> {
+        ctx.accounts.state.require_not_paused(PAUSE_ATTEST)?;
+
+        let entry = &mut ctx.accounts.entry;
+        let was_verified = entry.verified;
+        entry.refresh_verified(ctx.accounts.state.attestation_threshold);
+        if entry.verified != was_verified {
+            entry.version = entry.next_version()?;
+        }
+
+        Ok(())
+    }
+
+    /// Permissionless: drops an attestation that has expired, or whose attestor
+    /// has since been removed, from the entry's count. Neither event touches
+    /// the entry by itself, so `verified` stays stale until this runs.
+    pub fn lapse_attestation(ctx: Context<LapseAttestation>) -> Result<()> {
+        ctx.accounts.state.require_not_paused(PAUSE_ATTEST)?;
+
+        let attestation = &ctx.accounts.attestation;
+        // Revoked attestations are already uncounted and kept as history.
+        require!(!attestation.revoked, MoatError::AttestationRevoked);
+        let expired = attestation.expires_at != 0
+            && Clock::get()?.unix_timestamp >= attestation.expires_at;
+        let attestor_removed = ctx.accounts.attestor_config.data_is_empty();
+        require!(expired || attestor_removed, MoatError::AttestationStillActive);
+
+        let entry = &mut ctx.accounts.entry;
+        entry.attestation_count = entry.attestation_count.saturating_sub(1);
+        entry.refresh_verified(ctx.accounts.state.attestation_threshold);
+        entry.version = entry.next_version()?;
+
+        emit!(AttestationLapsed {
+            entry: attestation.entry,
+            attestor: attestation.attestor,
+            expired,
+            attestor_removed,
+        });
+
+        Ok(())
+    }
+
+    /// Counts one registered attestor towards the commit's quorum. A commit
+    /// made while `commit_attestation_quorum` was K verifies only once K
+    /// distinct attestors have vouched for it.
+    pub fn attest_commit(ctx: Context<AttestCommit>) -> Result<()> {
+        ctx.accounts.state.require_not_paused(PAUSE_ATTEST)?;
+
+        let batch = &mut ctx.accounts.batch;
+        require!(!batch.revoked, MoatError::CommitRevoked);
+        batch.attestation_count = batch
+            .attestation_count
+            .checked_add(1)
+            .ok_or(MoatError::Overflow)?;
+
+        let attestation = &mut ctx.accounts.commit_attestation;
+        attestation.commit = batch.key();
+        attestation.attestor = ctx.accounts.attestor.key();
+        attestation.created_at = Clock::get()?.unix_timestamp;
+        attestation.bump = ctx.bumps.commit_attestation;
+
+        emit!(CommitAttested {
+            commit: attestation.commit,
+            attestor: attestation.attestor,
+            attestation_count: batch.attestation_count,
+            quorum: batch.attestation_quorum,
+        });
+
+        Ok(())
+    }
+
+    pub fn define_tag(ctx: Context<DefineTag>, bit: u8, label: String) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+
+        require_capability(
+            state,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.role.as_deref_mut(),
+            ctx.accounts.session.as_deref_mut(),
+            ctx.accounts.nonce.as_deref_mut(),
+            ROLE_CONFIGURE,
+        )?;
+        require!(bit < 64, MoatError::InvalidTagBit);
+        require!(label.len() <= MAX_TAG_LABEL_LEN, MoatError::LabelTooLong);
+
+        let definition = &mut ctx.accounts.tag_definition;
+        definition.registry = state.key();
+        definition.bit = bit;
+        definition.label = label;
+        definition.bump = ctx.bumps.tag_definition;
+
+        state.defined_tags |= 1u64 << bit;
+
+        emit!(TagDefined {
+            bit,
+            label: definition.label.clone(),
+        });
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_DEFINE_TAG,
+            ctx.accounts.authority.key(),
+            &[&[bit], ctx.accounts.tag_definition.label.as_bytes()],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn commit_batch(
+        ctx: Context<CommitBatch>,
+        batch_id: u64,
+        params: CommitParams,
+    ) -> Result<()> {
+        process_commit_batch(ctx.accounts, &ctx.bumps, batch_id, params)
+    }
+
+    /// Publishes one root per entry in a single transaction. Counts as one
+    /// commit against the cooldown.
+    pub fn commit_batches<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CommitBatches<'info>>,
+        commits: Vec<EntryCommit>,
+    ) -> Result<()> {
+        require!(!commits.is_empty(), MoatError::EmptyBatch);
+        require!(
+            ctx.remaining_accounts.len() == commits.len() * 2,
+            MoatError::InvalidRemainingAccounts
+        );
+
+        let accounts = ctx.accounts;
+        let creator = accounts.creator.key();
+        let clock = Clock::get()?;
+        authorize_commit(
+            &mut accounts.state,
+            &creator,
+            accounts.role.as_deref_mut(),
+            accounts.session.as_deref_mut(),
+            accounts.nonce.as_deref_mut(),
+            clock.slot,
+        )?;
+
+        let state_key = accounts.state.key();
+        let payer = accounts.creator.to_account_info();
+        let system_program = accounts.system_program.to_account_info();
+
+        // remaining_accounts: [entry, commit] per item, commit being the
+        // entry's next commit PDA
+        for (item, pair) in commits
+            .into_iter()
+            .zip(ctx.remaining_accounts.chunks_exact(2))
+        {
+            let (entry_info, commit_info) = (&pair[0], &pair[1]);
+
+            check_commit_params(
+                &accounts.state,
+                &item.params,
+                accounts.commit_kind.as_deref(),
+                clock.unix_timestamp,
+            )?;
+
+            let mut entry = Account::<RegistryEntry>::try_from(entry_info)?;
+            let (entry_key, _) = Pubkey::find_program_address(
+                &[b"entry", state_key.as_ref(), &entry.id.to_le_bytes()],
+                &crate::ID,
+            );
+            require_keys_eq!(entry_info.key(), entry_key, MoatError::InvalidRemainingAccounts);
+
+            let seq_bytes = entry.commit_count.to_le_bytes();
+            let (commit_key, commit_bump) = Pubkey::find_program_address(
+                &[b"commit", entry_key.as_ref(), &seq_bytes],
+                &crate::ID,
+            );
+            require_keys_eq!(commit_info.key(), commit_key, MoatError::InvalidRemainingAccounts);
+
+            create_pda_account(
+                &payer,
+                commit_info,
+                &system_program,
+                8 + BatchCommit::INIT_SPACE,
+                &[b"commit", entry_key.as_ref(), &seq_bytes, &[commit_bump]],
+            )?;
+            let stake = accounts.state.commit_stake;
+            if stake > 0 {
+                system_program::transfer(
+                    CpiContext::new(
+                        system_program.clone(),
+                        system_program::Transfer {
+                            from: payer.clone(),
+                            to: commit_info.clone(),
+                        },
+                    ),
+                    stake,
+                )?;
+            }
+
+            require_cosignatures(
+                &accounts.state,
+                accounts.cosigners.as_deref(),
+                accounts.instructions.as_ref(),
+                &entry_key,
+                entry.commit_count,
+                &item.params.merkle_root,
+            )?;
+            require_root_attestation(
+                &accounts.state,
+                accounts.instructions.as_ref(),
+                &entry_key,
+                entry.commit_count,
+                &item.params.merkle_root,
+            )?;
+            verify_commit_proof(
+                &accounts.state,
+                accounts.commit_verifier.as_ref(),
+                &entry_key,
+                entry.commit_count,
+                &item.params,
+            )?;
+            let mut commit = Account::<BatchCommit>::try_from_unchecked(commit_info)?;
+            write_commit(
+                &mut accounts.state,
+                &mut entry,
+                &mut commit,
+                creator,
+                item.batch_id,
+                item.params,
+                &clock,
+            )?;
+            commit.bump = commit_bump;
+            record_commit(
+                &accounts.state,
+                &commit,
+                accounts.recent_commits.as_ref(),
+                accounts.epoch_summary.as_deref_mut(),
+                ctx.bumps.epoch_summary.unwrap_or_default(),
+                accounts.commit_log.as_ref(),
+            )?;
+
+            // An entry may appear twice, so flush it before the next item reloads it.
+            commit.exit(&crate::ID)?;
+            entry.exit(&crate::ID)?;
+        }
+
+        Ok(())
+    }
+
+    /// Stores the memo preimage on the commit itself; it must hash to
+    /// `memo_hash` under the commitment domain.
+    pub fn commit_batch_with_memo(
+        ctx: Context<CommitBatch>,
+        batch_id: u64,
+        params: CommitParams,
+        memo: Vec<u8>,
+    ) -> Result<()> {
+        require!(memo.len() <= MAX_INLINE_MEMO_LEN, MoatError::MemoTooLong);
+        require!(
+            hash_with(params.hash_alg, &[&params.domain, &memo])? == params.memo_hash,
+            MoatError::InvalidMemoHash
+        );
+
+        process_commit_batch(ctx.accounts, &ctx.bumps, batch_id, params)?;
+
+        let batch_info = ctx.accounts.batch.to_account_info();
+        grow_account(
+            &ctx.accounts.creator.to_account_info(),
+            &batch_info,
+            &ctx.accounts.system_program.to_account_info(),
+            batch_info.data_len() + memo.len(),
+        )?;
+        ctx.accounts.batch.memo = memo;
+
+        Ok(())
+    }
+
+    /// Publishes a corrected commit and marks `superseded` as replaced by it.
+    /// Only the original creator or the admin may supersede.
+    pub fn supersede_commit(
+        ctx: Context<SupersedeCommit>,
+        batch_id: u64,
+        params: CommitParams,
+    ) -> Result<()> {
+        let creator = ctx.accounts.commit.creator.key();
+        let superseded = &ctx.accounts.superseded;
+
+        require!(
+            superseded.creator == creator || ctx.accounts.commit.state.is_admin(&creator)?,
+            MoatError::Unauthorized
+        );
+        require!(
+            superseded.superseded_by.is_none(),
+            MoatError::CommitAlreadySuperseded
+        );
+
+        process_commit_batch(&mut ctx.accounts.commit, &ctx.bumps.commit, batch_id, params)?;
+
+        let replacement = &mut ctx.accounts.commit.batch;
+        let superseded = &mut ctx.accounts.superseded;
+        replacement.supersedes = Some(superseded.key());
+        superseded.superseded_by = Some(replacement.key());
+
+        emit!(CommitSuperseded {
+            commit: superseded.key(),
+            replacement: replacement.key(),
+            entry: superseded.entry,
+        });
+
+        Ok(())
+    }
+
+    /// Publishes a commit whose leaves are the roots of the finalized commits
+    /// passed in `remaining_accounts`, in order, and records their ids. The
+    /// client supplies the resulting root, which is recomputed here.
+    pub fn aggregate_commits<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AggregateCommits<'info>>,
+        batch_id: u64,
+        params: CommitParams,
+    ) -> Result<()> {
+        let members = ctx.remaining_accounts;
+        require!(
+            !members.is_empty() && members.len() <= MAX_AGGREGATE_MEMBERS,
+            MoatError::InvalidAggregateMembers
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        let mut leaves = Vec::with_capacity(members.len());
+        let mut member_ids = Vec::with_capacity(members.len());
+        for info in members {
+            let member = Account::<BatchCommit>::try_from(info)?;
+            member.require_verifiable(now)?;
+            require!(
+                member.is_final()
+                    && member.shard_roots.is_empty()
+                    && !member_ids.contains(&member.id),
+                MoatError::InvalidAggregateMembers
+            );
+            leaves.push(member.merkle_root);
+            member_ids.push(member.id);
+        }
+
+        let (root, depth) = moat_merkle::compute_root(
+            &CommitHasher::new(params.hash_alg)?,
+            merkle_tree(params.root_version, params.sorted_pairs)?,
+            &leaves,
+        )
+        .ok_or(MoatError::InvalidAggregateMembers)?;
+        require!(
+            params.merkle_root == root
+                && params.leaf_count == member_ids.len() as u64
+                && params.depth == depth
+                && params.shard_roots.is_empty(),
+            MoatError::InvalidAggregateRoot
+        );
+
+        process_commit_batch(&mut ctx.accounts.commit, &ctx.bumps.commit, batch_id, params)?;
+
+        let commit = ctx.accounts.commit.batch.key();
+        let aggregate = &mut ctx.accounts.aggregate;
+        aggregate.commit = commit;
+        aggregate.member_ids = member_ids;
+        aggregate.bump = ctx.bumps.aggregate;
+
+        emit!(CommitsAggregated {
+            commit,
+            member_ids: aggregate.member_ids.clone(),
+        });
+
+        Ok(())
+    }
+
+    /// Folds the entries in `remaining_accounts` into the registry root being
+    /// built, one entry PDA per id starting where the last call stopped;
+    /// active, unexpired entries become `EntryLeafV1` leaves of a
+    /// `ROOT_VERSION_V2` tree, and a closed id is passed as its empty PDA.
+    /// Once every id below `next_id` has been passed the root is published,
+    /// so light clients can check an entry against one 32-byte commitment.
+    /// `restart` discards a build in progress.
+    pub fn commit_registry_root<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CommitRegistryRoot<'info>>,
+        restart: bool,
+    ) -> Result<()> {
+        require_capability(
+            &ctx.accounts.state,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.role.as_deref_mut(),
+            ctx.accounts.session.as_deref_mut(),
+            ctx.accounts.nonce.as_deref_mut(),
+            ROLE_CONFIGURE,
+        )?;
+
+        let state_key = ctx.accounts.state.key();
+        let now = Clock::get()?.unix_timestamp;
+        let snapshot = &mut ctx.accounts.registry_root;
+        snapshot.registry = state_key;
+        snapshot.bump = ctx.bumps.registry_root;
+        if restart {
+            snapshot.cursor = 0;
+            snapshot.pending_count = 0;
+        }
+
+        let domain = commitment_domain(&state_key);
+        let hasher = CommitHasher::new(HASH_ALG_SHA256)?;
+        let tree = merkle_tree(ROOT_VERSION_V2, false)?;
+        for info in ctx.remaining_accounts {
+            let id = u32::try_from(snapshot.cursor).map_err(|_| MoatError::Overflow)?;
+            let (entry_key, _) = Pubkey::find_program_address(
+                &[b"entry", state_key.as_ref(), &id.to_le_bytes()],
+                &crate::ID,
+            );
+            require_keys_eq!(info.key(), entry_key, MoatError::InvalidRegistryRootEntry);
+            snapshot.cursor += 1;
+            // Closed entries leave their id behind with nothing to commit.
+            if info.data_is_empty() {
+                continue;
+            }
+
+            let entry = Account::<RegistryEntry>::try_from(info)?;
+            if entry.status != ENTRY_STATUS_ACTIVE || entry.is_expired(now) {
+                continue;
+            }
+
+            let leaf = entry_leaf(&hasher, &domain, &entry);
+            let leaf_count = snapshot.pending_count;
+            require!(
+                moat_merkle::append_leaf(&hasher, tree, &mut snapshot.frontier, leaf_count, &leaf),
+                MoatError::TreeFull
+            );
+            snapshot.pending_count += 1;
+        }
+
+        if snapshot.cursor < ctx.accounts.state.next_id {
+            return Ok(());
+        }
+
+        let (root, depth) = if snapshot.pending_count == 0 {
+            ([0; 32], 0)
+        } else {
+            moat_merkle::frontier_root(
+                &hasher,
+                tree,
+                &snapshot.frontier,
+                snapshot.pending_count,
+            )
+            .ok_or(MoatError::TreeFull)?
+        };
+        snapshot.root = root;
+        snapshot.depth = depth;
+        snapshot.entry_count = snapshot.pending_count;
+        snapshot.version = snapshot
+            .version
+            .checked_add(1)
+            .ok_or(MoatError::Overflow)?;
+        snapshot.published_at = now;
+        snapshot.cursor = 0;
+        snapshot.pending_count = 0;
+
+        let entry_count = snapshot.entry_count;
+        let version = snapshot.version;
+        emit!(RegistryRootCommitted {
+            registry: state_key,
+            root,
+            entry_count,
+            depth,
+            version,
+        });
+
+        record_admin_action(
+            &ctx.accounts.state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_COMMIT_REGISTRY_ROOT,
+            ctx.accounts.authority.key(),
+            &[&root, &entry_count.to_le_bytes(), &version.to_le_bytes()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Checks `proof` for the entry at `leaf_index` of the latest registry
+    /// root and hands it back, with the entry's committed fields, through
+    /// return data and an `EntryProofExported` log. The path can come from any
+    /// indexer: it only gets out once it verifies, and a light client can
+    /// check the result against the 32-byte root alone.
+    pub fn export_entry_proof(
+        ctx: Context<ExportEntryProof>,
+        leaf_index: u64,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<EntryStateProof> {
+        let registry = ctx.accounts.state.key();
+        let snapshot = &ctx.accounts.registry_root;
+        let entry = &ctx.accounts.entry;
+        require!(snapshot.version > 0, MoatError::RegistryRootNotPublished);
+
+        let hasher = CommitHasher::new(HASH_ALG_SHA256)?;
+        let leaf = entry_leaf(&hasher, &commitment_domain(&registry), entry);
+        require!(
+            leaf_index < snapshot.entry_count
+                && proof.len() == usize::from(snapshot.depth)
+                && moat_merkle::verify_proof(
+                    &hasher,
+                    merkle_tree(ROOT_VERSION_V2, false)?,
+                    &snapshot.root,
+                    &leaf,
+                    leaf_index,
+                    &proof,
+                ),
+            MoatError::InvalidProof
+        );
+
+        emit!(EntryProofExported {
+            registry,
+            entry: entry.key(),
+            root: snapshot.root,
+            version: snapshot.version,
+            leaf_index,
+            leaf,
+        });
+
+        Ok(EntryStateProof {
+            registry,
+            root: snapshot.root,
+            version: snapshot.version,
+            entry_count: snapshot.entry_count,
+            leaf_index,
+            id: entry.id,
+            target_program: entry.target_program,
+            kind: entry.kind,
+            tags: entry.tags,
+            expires_at: entry.expires_at,
+            proof,
+        })
+    }
+
+    /// Flags a published root as untrusted. The commit stays on-chain so its
+    /// history remains enumerable.
+    pub fn revoke_commit(ctx: Context<RevokeCommit>, reason: u8) -> Result<()> {
+        let state = &ctx.accounts.state;
+        let authority = ctx.accounts.authority.key();
+
+        require!(
+            state.is_admin(&authority)?
+                || (state.commit_authority != Pubkey::default()
+                    && state.is_commit_authority(&authority)?),
+            MoatError::Unauthorized
+        );
+
+        let commit = &mut ctx.accounts.batch;
+        require!(!commit.revoked, MoatError::CommitRevoked);
+
+        commit.revoked = true;
+        commit.revoke_reason = reason;
+        commit.revoked_at = Clock::get()?.unix_timestamp;
+
+        emit!(CommitRevoked {
+            commit: commit.key(),
+            entry: commit.entry,
+            seq: commit.seq,
+            reason,
+        });
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_REVOKE_COMMIT,
+            authority,
+            &[commit.key().as_ref(), &[reason]],
+        )?;
+
+        Ok(())
+    }
+
+    /// Voids individual leaves of an otherwise valid commit. Excluded leaves
+    /// fail `verify_inclusion`, `consume_leaf` and `claim`.
+    pub fn exclude_leaves(
+        ctx: Context<ExcludeLeaves>,
+        root_index: u8,
+        indices: Vec<u64>,
+    ) -> Result<()> {
+        let state = &ctx.accounts.state;
+        let authority = ctx.accounts.authority.key();
+
+        require!(
+            state.is_admin(&authority)?
+                || (state.commit_authority != Pubkey::default()
+                    && state.is_commit_authority(&authority)?),
+            MoatError::Unauthorized
+        );
+
+        let commit = &mut ctx.accounts.batch;
+        commit.root(root_index)?;
+        let exclusions = &mut ctx.accounts.exclusions;
+        exclusions.commit = commit.key();
+        exclusions.bump = ctx.bumps.exclusions;
+        for &index in &indices {
+            let position = commit.claim_position(root_index, index);
+            require!(position < commit.claim_slots(), MoatError::InvalidLeafIndex);
+            if !exclusions.positions.contains(&position) {
+                exclusions.positions.push(position);
+            }
+        }
+        require!(
+            exclusions.positions.len() <= MAX_EXCLUDED_LEAVES,
+            MoatError::TooManyExcludedLeaves
+        );
+        commit.excluded_leaves = exclusions.positions.len() as u32;
+
+        let mut payload = Vec::with_capacity(indices.len() * 8);
+        for index in &indices {
+            payload.extend_from_slice(&index.to_le_bytes());
+        }
+        emit!(LeavesExcluded {
+            commit: commit.key(),
+            root_index,
+            indices,
+        });
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_EXCLUDE_LEAVES,
+            authority,
+            &[commit.key().as_ref(), &[root_index], &payload],
+        )?;
+
+        Ok(())
+    }
+
+    /// Hands `consume_leaf` on this commit to `consumer` alone, e.g. a signer PDA
+    /// of a settlement program. The default key restores the creator and entry
+    /// admin or delegate.
+    pub fn set_commit_consumer(ctx: Context<SetCommitConsumer>, consumer: Pubkey) -> Result<()> {
+        let state = &ctx.accounts.state;
+        let authority = ctx.accounts.authority.key();
+
+        require!(
+            state.is_admin(&authority)?
+                || (state.commit_authority != Pubkey::default()
+                    && state.is_commit_authority(&authority)?),
+            MoatError::Unauthorized
+        );
+
+        let commit = &mut ctx.accounts.batch;
+        commit.consumer = consumer;
+
+        emit!(CommitConsumerChanged {
+            commit: commit.key(),
+            consumer,
+        });
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_SET_COMMIT_CONSUMER,
+            authority,
+            &[commit.key().as_ref(), consumer.as_ref()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Limits `consume_leaf` and `claim` on this commit to `members`, each a
+    /// signer key or the program whose top-level instruction calls in. Only
+    /// the commit's creator may set it, and only while the commit is pending,
+    /// so a final commit's claimants can't be locked out. An empty list opens
+    /// the commit again.
+    pub fn set_commit_acl(ctx: Context<SetCommitAcl>, members: Vec<Pubkey>) -> Result<()> {
+        require!(
+            members.len() <= MAX_COMMIT_ACL_MEMBERS,
+            MoatError::TooManyAclMembers
+        );
+        require!(
+            members
+                .iter()
+                .enumerate()
+                .all(|(i, member)| !members[..i].contains(member)),
+            MoatError::DuplicateAclMember
+        );
+
+        let commit = &mut ctx.accounts.batch;
+        require!(
+            commit.status == COMMIT_STATUS_PENDING,
+            MoatError::CommitAlreadyFinalized
+        );
+        commit.acl_members = members.len() as u8;
+
+        let acl = &mut ctx.accounts.acl;
+        acl.commit = commit.key();
+        acl.members = members;
+        acl.bump = ctx.bumps.acl;
+
+        emit!(CommitAclChanged {
+            commit: acl.commit,
+            members: acl.members.clone(),
+        });
+
+        Ok(())
+    }
+
+    /// Appends a note to the commit's sidecar: an `ANNOTATION_*` or own code
+    /// and the hash of whatever backs it, e.g. a reconciliation report. The
+    /// commit itself is untouched. Open to its creator and, when one is set,
+    /// the registry's commit authority.
+    pub fn annotate_commit(ctx: Context<AnnotateCommit>, code: u16, hash: [u8; 32]) -> Result<()> {
+        let authority = ctx.accounts.authority.key();
+        let state = &ctx.accounts.state;
+        require!(
+            authority == ctx.accounts.batch.creator
+                || (state.commit_authority != Pubkey::default()
+                    && state.is_commit_authority(&authority)?),
+            MoatError::Unauthorized
+        );
+
+        let annotations = &mut ctx.accounts.annotations;
+        require!(
+            annotations.notes.len() < MAX_COMMIT_ANNOTATIONS,
+            MoatError::CommitAnnotationsFull
+        );
+        annotations.commit = ctx.accounts.batch.key();
+        annotations.bump = ctx.bumps.annotations;
+        annotations.notes.push(CommitAnnotation {
+            code,
+            hash,
+            author: authority,
+            created_at: Clock::get()?.unix_timestamp,
+        });
+
+        emit!(CommitAnnotated {
+            commit: annotations.commit,
+            code,
+            hash,
+            author: authority,
+        });
+
+        Ok(())
+    }
+
+    /// Permissionless once the commit's dispute window has passed.
+    pub fn finalize_commit(ctx: Context<FinalizeCommit>) -> Result<()> {
+        ctx.accounts.state.require_not_paused(PAUSE_COMMIT)?;
+
+        let commit = &mut ctx.accounts.batch;
+        require!(!commit.revoked, MoatError::CommitRevoked);
+        require!(!commit.challenged, MoatError::CommitUnderChallenge);
+        require!(
+            commit.status == COMMIT_STATUS_PENDING,
+            MoatError::CommitAlreadyFinalized
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(now >= commit.dispute_ends_at, MoatError::DisputeWindowOpen);
+
+        commit.status = COMMIT_STATUS_FINALIZED;
+        commit.finalized_at = now;
+
+        emit!(CommitFinalized {
+            commit: commit.key(),
+            entry: commit.entry,
+            seq: commit.seq,
+        });
+
+        Ok(())
+    }
+
+    /// Shrinks a settled commit past its retention window to a 40-byte tombstone
+    /// of its id and the hash of its full record, refunding the rest to the
+    /// creator and closing its claim bitmap, ACL, exclusion list and annotations
+    /// with it. A revoked commit forfeits its stake to the admin. The address
+    /// stays occupied, so the entry's commit sequence is never reused, and
+    /// `sweep_vault` can still return the vault's tokens.
+    pub fn prune_commit(ctx: Context<PruneCommit>) -> Result<()> {
+        let info = ctx.accounts.batch.to_account_info();
+        let commit = BatchCommit::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+        let now = Clock::get()?.unix_timestamp;
+
+        require_keys_eq!(
+            ctx.accounts.creator.key(),
+            commit.creator,
+            MoatError::Unauthorized
+        );
+        require_prunable(&commit, ctx.accounts.state.commit_retention, now)?;
+
+        let creator = ctx.accounts.creator.to_account_info();
+        for sidecar in [
+            &ctx.accounts.claim_bitmap,
+            &ctx.accounts.acl,
+            &ctx.accounts.exclusions,
+            &ctx.accounts.annotations,
+        ] {
+            // Sidecars the commit never had arrive as empty PDAs.
+            if !sidecar.data_is_empty() {
+                close_account(sidecar, &creator)?;
+            }
+        }
+
+        let tombstone = CommitTombstone {
+            id: commit.id,
+            record_hash: hashv(&[&info.try_borrow_data()?]).to_bytes(),
+        };
+
+        let space = 8 + CommitTombstone::INIT_SPACE;
+        info.resize(space)?;
+        tombstone.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+
+        let surplus = info
+            .lamports()
+            .saturating_sub(Rent::get()?.minimum_balance(space));
+        // An upheld challenge already paid the stake out; any other revocation
+        // leaves it for the admin rather than the committer.
+        let forfeited = if commit.revoked {
+            commit.stake.min(surplus)
+        } else {
+            0
+        };
+        let refunded = surplus - forfeited;
+        **info.try_borrow_mut_lamports()? -= surplus;
+        **ctx.accounts.admin.try_borrow_mut_lamports()? += forfeited;
+        **creator.try_borrow_mut_lamports()? += refunded;
+
+        emit!(CommitPruned {
+            commit: info.key(),
+            id: tombstone.id,
+            record_hash: tombstone.record_hash,
+            refunded,
+            forfeited,
+        });
+
+        Ok(())
+    }
+
+    /// Returns what is left in one of a commit's vaults to its creator and
+    /// closes the vault, once the commit could be pruned. Works on the
+    /// tombstone too, so pruning never strands tokens. A tombstone keeps neither
+    /// the creator nor the entry, so its vaults go to the admin of the entry
+    /// the commit's address derives from with `seq`.
+    pub fn sweep_vault(ctx: Context<SweepVault>, seq: u64) -> Result<()> {
+        let info = ctx.accounts.batch.to_account_info();
+        let recipient = {
+            let data = info.try_borrow_data()?;
+            if data.starts_with(CommitTombstone::DISCRIMINATOR) {
+                let entry = ctx.accounts.entry.key();
+                let (commit, _) = Pubkey::find_program_address(
+                    &[b"commit", entry.as_ref(), &seq.to_le_bytes()],
+                    &crate::ID,
+                );
+                require_keys_eq!(info.key(), commit, MoatError::CommitEntryMismatch);
+                ctx.accounts.entry.admin
+            } else {
+                let commit = BatchCommit::try_deserialize(&mut &data[..])?;
+                let now = Clock::get()?.unix_timestamp;
+                require_prunable(&commit, ctx.accounts.state.commit_retention, now)?;
+                commit.creator
+            }
+        };
+        require_keys_eq!(
+            ctx.accounts.recipient.key(),
+            recipient,
+            MoatError::Unauthorized
+        );
+
+        let vault_authority = ctx.accounts.vault_authority.key();
+        let mint = ctx.accounts.mint.key();
+        require!(
+            read_token_account(&ctx.accounts.vault)? == (mint, vault_authority),
+            MoatError::InvalidTokenAccount
+        );
+        require!(
+            read_token_account(&ctx.accounts.destination)? == (mint, recipient),
+            MoatError::InvalidTokenAccount
+        );
+        // The balance follows the mint and owner in a token account.
+        let amount = ctx
+            .accounts
+            .vault
+            .try_borrow_data()?
+            .get(64..72)
+            .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+            .ok_or(MoatError::InvalidTokenAccount)?;
+
+        let batch_key = info.key();
+        let seeds: &[&[u8]] = &[b"vault", batch_key.as_ref(), &[ctx.bumps.vault_authority]];
+        if amount > 0 {
+            transfer_from_vault(
+                &ctx.accounts.token_program,
+                &ctx.accounts.vault,
+                &ctx.accounts.mint,
+                &ctx.accounts.destination,
+                &ctx.accounts.vault_authority,
+                seeds,
+                amount,
+            )?;
+        }
+        invoke_signed(
+            &Instruction {
+                program_id: ctx.accounts.token_program.key(),
+                accounts: vec![
+                    AccountMeta::new(ctx.accounts.vault.key(), false),
+                    AccountMeta::new(recipient, false),
+                    AccountMeta::new_readonly(vault_authority, true),
+                ],
+                data: vec![TOKEN_CLOSE_ACCOUNT],
+            },
+            &[
+                ctx.accounts.vault.to_account_info(),
+                ctx.accounts.recipient.to_account_info(),
+                ctx.accounts.vault_authority.to_account_info(),
+                ctx.accounts.token_program.to_account_info(),
+            ],
+            &[seeds],
+        )?;
+
+        emit!(VaultSwept {
+            commit: batch_key,
+            vault: ctx.accounts.vault.key(),
+            mint,
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Creates the bitmap `consume_leaf` marks leaves off in, one bit per leaf
+    /// position of `batch`.
+    pub fn init_claim_bitmap(ctx: Context<InitClaimBitmap>) -> Result<()> {
+        let bytes = ctx.accounts.batch.claim_slots().div_ceil(8) as usize;
+
+        let bitmap = &mut ctx.accounts.claim_bitmap;
+        bitmap.commit = ctx.accounts.batch.key();
+        bitmap.consumed = 0;
+        bitmap.bump = ctx.bumps.claim_bitmap;
+        bitmap.bits = vec![0; bytes];
+
+        Ok(())
+    }
+
+    /// Recomputes the path from `leaf` at `index` against root `root_index` (0 for
+    /// `merkle_root`) of the entry's commit number `seq`, so any past commit can
+    /// be checked, not only the latest. The outcome is returned through return
+    /// data rather than an error, so a program calling in can branch on it; a
+    /// revoked, superseded, expired or excluded leaf reads as invalid.
+    pub fn verify_inclusion(
+        ctx: Context<VerifyInclusion>,
+        _seq: u64,
+        leaf: [u8; 32],
+        proof: Vec<[u8; 32]>,
+        index: u64,
+        root_index: u8,
+    ) -> Result<InclusionResult> {
+        let batch = &ctx.accounts.batch;
+        let valid = verify_leaf(
+            batch,
+            ctx.accounts.exclusions.as_deref(),
+            root_index,
+            index,
+            leaf,
+            &proof,
+            Clock::get()?.unix_timestamp,
+        )
+        .is_ok();
+
+        Ok(InclusionResult {
+            valid,
+            commit_id: batch.id,
+            root_index,
+            index,
+        })
+    }
+
+    /// `verify_inclusion` for a leaf given as its borsh payload: the program
+    /// hashes it under the commit's `leaf_schema` and domain, so callers can't
+    /// pre-hash it the wrong way. Custom-schema commits have no such rules.
+    pub fn verify_raw_leaf(
+        ctx: Context<VerifyInclusion>,
+        _seq: u64,
+        payload: Vec<u8>,
+        proof: Vec<[u8; 32]>,
+        index: u64,
+        root_index: u8,
+    ) -> Result<InclusionResult> {
+        require!(payload.len() <= MAX_RAW_LEAF_LEN, MoatError::RawLeafTooLong);
+        let batch = &ctx.accounts.batch;
+        require!(
+            batch.leaf_schema != LEAF_SCHEMA_CUSTOM,
+            MoatError::LeafSchemaMismatch
+        );
+        let leaf = moat_merkle::hash_raw_leaf(
+            &CommitHasher::new(batch.hash_alg)?,
+            batch.leaf_schema,
+            &commitment_domain(&ctx.accounts.entry.registry),
+            &payload,
+        )
+        .ok_or(MoatError::InvalidLeafPayload)?;
+
+        let valid = verify_leaf(
+            batch,
+            ctx.accounts.exclusions.as_deref(),
+            root_index,
+            index,
+            leaf,
+            &proof,
+            Clock::get()?.unix_timestamp,
+        )
+        .is_ok();
+
+        Ok(InclusionResult {
+            valid,
+            commit_id: batch.id,
+            root_index,
+            index,
+        })
+    }
+
+    /// Verifies many leaves of the entry's sorted-pair commit `seq` in one
+    /// OpenZeppelin-style multiproof against root `root_index`. As with
+    /// `verify_inclusion`, the outcome comes back as return data. Multiproofs
+    /// don't carry leaf indices, so a commit with exclusions never verifies here,
+    /// and neither does one without sorted pairs or without a root `root_index`.
+    pub fn verify_multi(
+        ctx: Context<VerifyInclusion>,
+        _seq: u64,
+        leaves: Vec<[u8; 32]>,
+        proof: Vec<[u8; 32]>,
+        flags: Vec<bool>,
+        root_index: u8,
+    ) -> Result<MultiInclusionResult> {
+        let batch = &ctx.accounts.batch;
+        let valid = verify_leaves(
+            batch,
+            root_index,
+            &leaves,
+            &proof,
+            &flags,
+            Clock::get()?.unix_timestamp,
+        )
+        .is_ok();
+
+        Ok(MultiInclusionResult {
+            valid,
+            commit_id: batch.id,
+            root_index,
+            leaf_count: leaves.len() as u32,
+        })
+    }
+
+    /// Proves `key` absent from the entry's sorted-key commit `seq` with the two
+    /// adjacent leaves around it; `low` is omitted below the first leaf and
+    /// `high` above the last. The creator vouches that leaves are in strictly
+    /// ascending key order, which the program cannot see. Like
+    /// `verify_inclusion`, the outcome comes back as return data.
+    pub fn verify_exclusion(
+        ctx: Context<VerifyInclusion>,
+        _seq: u64,
+        key: [u8; 32],
+        low: Option<KeyNeighbor>,
+        high: Option<KeyNeighbor>,
+    ) -> Result<ExclusionResult> {
+        let batch = &ctx.accounts.batch;
+        let domain = commitment_domain(&ctx.accounts.entry.registry);
+        let valid = verify_absent(
+            batch,
+            ctx.accounts.exclusions.as_deref(),
+            &domain,
+            &key,
+            low.as_ref(),
+            high.as_ref(),
+        )
+        .is_ok();
+
+        Ok(ExclusionResult {
+            valid,
+            commit_id: batch.id,
+            key,
+        })
+    }
+
+    /// Verifies `leaf` at `index` under root `root_index` and marks it consumed.
+    /// Only the commit's designated consumer may consume; without one, the
+    /// commit's creator or the entry admin or delegate may.
+    pub fn consume_leaf(
+        ctx: Context<ConsumeLeaf>,
+        root_index: u8,
+        index: u64,
+        leaf: [u8; 32],
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let authority = ctx.accounts.authority.key();
+        let consumer = ctx.accounts.batch.consumer;
+        require!(
+            if consumer != Pubkey::default() {
+                authority == consumer
+            } else {
+                authority == ctx.accounts.batch.creator
+                    || ctx.accounts.entry.is_admin_or_delegate(&authority)
+            },
+            MoatError::Unauthorized
+        );
+        if consumer == Pubkey::default() && authority != ctx.accounts.batch.creator {
+            require_admin_or_delegate(
+                &ctx.accounts.entry,
+                &authority,
+                ctx.accounts.nonce.as_deref_mut(),
+            )?;
+        }
+        require_acl_member(
+            &ctx.accounts.batch,
+            ctx.accounts.acl.as_deref(),
+            &authority,
+            ctx.accounts.instructions.as_ref(),
+        )?;
+
+        consume_leaf_at(
+            &ctx.accounts.batch,
+            &mut ctx.accounts.claim_bitmap,
+            ctx.accounts.exclusions.as_deref(),
+            root_index,
+            index,
+            leaf,
+            &proof,
+        )?;
+
+        emit!(LeafConsumed {
+            commit: ctx.accounts.batch.key(),
+            root_index,
+            index,
+            leaf,
+            consumer: authority,
+        });
+
+        Ok(())
+    }
+
+    /// Pays `amount` of the commit's claim mint from its vault to `claimant`
+    /// against a `(claimant, amount)` leaf, consuming it so it pays out once.
+    pub fn claim(
+        ctx: Context<Claim>,
+        root_index: u8,
+        index: u64,
+        amount: u64,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let claimant = ctx.accounts.claimant.key();
+        let vault_authority = ctx.accounts.vault_authority.key();
+        let mint = ctx.accounts.mint.key();
+        // Leaves carry no mint, so the vault authority's other token accounts
+        // must stay out of reach.
+        require_keys_eq!(mint, ctx.accounts.batch.claim_mint, MoatError::InvalidClaimMint);
+        require!(
+            read_token_account(&ctx.accounts.vault)? == (mint, vault_authority),
+            MoatError::InvalidTokenAccount
+        );
+        require!(
+            read_token_account(&ctx.accounts.destination)? == (mint, claimant),
+            MoatError::InvalidTokenAccount
+        );
+
+        let batch = &ctx.accounts.batch;
+        require!(
+            batch.leaf_schema == LEAF_SCHEMA_CLAIM_V1,
+            MoatError::LeafSchemaMismatch
+        );
+        require_acl_member(
+            batch,
+            ctx.accounts.acl.as_deref(),
+            &claimant,
+            ctx.accounts.instructions.as_ref(),
+        )?;
+        let leaf = moat_merkle::claim_leaf(
+            &CommitHasher::new(batch.hash_alg)?,
+            &commitment_domain(&ctx.accounts.entry.registry),
+            &claimant.to_bytes(),
+            amount,
+        );
+        consume_leaf_at(
+            batch,
+            &mut ctx.accounts.claim_bitmap,
+            ctx.accounts.exclusions.as_deref(),
+            root_index,
+            index,
+            leaf,
+            &proof,
+        )?;
+
+        let batch_key = batch.key();
+        transfer_from_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.vault,
+            &ctx.accounts.mint,
+            &ctx.accounts.destination,
+            &ctx.accounts.vault_authority,
+            &[b"vault", batch_key.as_ref(), &[ctx.bumps.vault_authority]],
+            amount,
+        )?;
+
+        emit!(TokensClaimed {
+            commit: batch_key,
+            claimant,
+            mint,
+            index,
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Disputes a pending commit by posting `state.challenge_bond`. One
+    /// challenge may be open per commit, and it blocks finalization.
+    pub fn challenge_commit(ctx: Context<ChallengeCommit>, claim_hash: [u8; 32]) -> Result<()> {
+        let state = &ctx.accounts.state;
+        let commit = &mut ctx.accounts.batch;
+        let now = Clock::get()?.unix_timestamp;
+
+        state.require_not_paused(PAUSE_COMMIT)?;
+        require!(!commit.revoked, MoatError::CommitRevoked);
+        require!(
+            commit.status == COMMIT_STATUS_PENDING,
+            MoatError::CommitAlreadyFinalized
+        );
+        require!(now < commit.dispute_ends_at, MoatError::DisputeWindowClosed);
+
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.challenger.to_account_info(),
+                    to: ctx.accounts.challenge.to_account_info(),
+                },
+            ),
+            state.challenge_bond,
+        )?;
+
+        let challenge = &mut ctx.accounts.challenge;
+        challenge.commit = commit.key();
+        challenge.challenger = ctx.accounts.challenger.key();
+        challenge.bond = state.challenge_bond;
+        challenge.claim_hash = claim_hash;
+        challenge.created_at = now;
+        challenge.bump = ctx.bumps.challenge;
+
+        commit.challenged = true;
+
+        emit!(CommitChallenged {
+            commit: commit.key(),
+            challenger: challenge.challenger,
+            bond: challenge.bond,
+            claim_hash,
+        });
+
+        Ok(())
+    }
+
+    /// Upholding revokes the commit and pays the bond and the committer's stake
+    /// to the challenger. Rejecting forfeits the challenge account, bond
+    /// included, to the committer.
+    pub fn resolve_challenge(
+        ctx: Context<ResolveChallenge>,
+        upheld: bool,
+        reason: u8,
+    ) -> Result<()> {
+        let state = &ctx.accounts.state;
+        let authority = ctx.accounts.authority.key();
+
+        state.require_admin(&authority)?;
+
+        let commit = &mut ctx.accounts.batch;
+        commit.challenged = false;
+
+        if upheld {
+            commit.revoked = true;
+            commit.revoke_reason = reason;
+            commit.revoked_at = Clock::get()?.unix_timestamp;
+
+            let stake = commit.stake;
+            commit.stake = 0;
+            **commit.to_account_info().try_borrow_mut_lamports()? -= stake;
+            **ctx.accounts.challenger.try_borrow_mut_lamports()? += stake;
+
+            ctx.accounts
+                .challenge
+                .close(ctx.accounts.challenger.to_account_info())?;
+        } else {
+            ctx.accounts
+                .challenge
+                .close(ctx.accounts.creator.to_account_info())?;
+        }
+
+        emit!(ChallengeResolved {
+            commit: commit.key(),
+            challenger: ctx.accounts.challenger.key(),
+            upheld,
+        });
+
+        record_admin_action(
+            state,
+            ctx.accounts.admin_log.as_ref(),
+            ADMIN_ACTION_RESOLVE_CHALLENGE,
+            authority,
+            &[commit.key().as_ref(), &[upheld as u8, reason]],
+        )?;
+
+        Ok(())
+    }
+
+    /// Creates an on-chain tree for `entry` that its admin or delegate appends to
+    /// leaf by leaf, holding up to `2^depth` leaves. Its root is a
+    /// `ROOT_VERSION_V2` root over the leaves appended so far, zero while empty.
+    pub fn init_tree(ctx: Context<InitTree>, depth: u8, hash_alg: u8) -> Result<()> {
+        require_admin_or_delegate(
+            &ctx.accounts.entry,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.nonce.as_deref_mut(),
+        )?;
+        require!(
+            depth > 0 && depth <= MAX_MERKLE_DEPTH,
+            MoatError::InvalidTreeShape
+        );
+
+        CommitHasher::new(hash_alg)?;
+
+        let tree = &mut ctx.accounts.tree;
+        tree.entry = ctx.accounts.entry.key();
+        tree.depth = depth;
+        tree.hash_alg = hash_alg;
+        tree.root_version = ROOT_VERSION_V2;
+        tree.leaf_count = 0;
+        tree.frontier = [[0; 32]; MAX_MERKLE_DEPTH as usize];
+        tree.root = [0; 32];
+        tree.bump = ctx.bumps.tree;
+
+        Ok(())
+    }
+
+    pub fn append_leaf(ctx: Context<AppendLeaf>, leaf: [u8; 32]) -> Result<()> {
+        ctx.accounts.state.require_not_paused(PAUSE_COMMIT)?;
+        require_admin_or_delegate(
+            &ctx.accounts.entry,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.nonce.as_deref_mut(),
+        )?;
+        require!(!ctx.accounts.entry.frozen, MoatError::EntryFrozen);
+
+        let tree = &mut ctx.accounts.tree;
+        let index = tree.leaf_count;
+        tree.append(leaf)?;
+
+        emit!(LeafAppended {
+            tree: tree.key(),
+            entry: tree.entry,
+            index,
+            leaf,
+            root: tree.root,
+        });
+
+        Ok(())
+    }
+
+    /// Attaches an spl-account-compression concurrent tree to `entry`. The
+    /// client allocates `merkle_tree` for the chosen depth and buffer size;
+    /// this program's `compressed_tree` PDA becomes its authority.
+    pub fn init_compressed_tree(
+        ctx: Context<InitCompressedTree>,
+        max_depth: u32,
+        max_buffer_size: u32,
+    ) -> Result<()> {
+        require_admin_or_delegate(
+            &ctx.accounts.entry,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.nonce.as_deref_mut(),
+        )?;
+
+        let compressed = &mut ctx.accounts.compressed_tree;
+        compressed.entry = ctx.accounts.entry.key();
+        compressed.merkle_tree = ctx.accounts.merkle_tree.key();
+        compressed.leaf_count = 0;
+        compressed.bump = ctx.bumps.compressed_tree;
+
+        invoke_compression(
+            &ctx.accounts.compression_program,
+            &ctx.accounts.compressed_tree,
+            &ctx.accounts.merkle_tree,
+            &ctx.accounts.noop,
+            compression_init_data(max_depth, max_buffer_size),
+        )
+    }
+
+    pub fn append_compressed_leaf(
+        ctx: Context<AppendCompressedLeaf>,
+        leaf: [u8; 32],
+    ) -> Result<()> {
+        ctx.accounts.state.require_not_paused(PAUSE_COMMIT)?;
+        require_admin_or_delegate(
+            &ctx.accounts.entry,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.nonce.as_deref_mut(),
+        )?;
+        require!(!ctx.accounts.entry.frozen, MoatError::EntryFrozen);
+
+        invoke_compression(
+            &ctx.accounts.compression_program,
+            &ctx.accounts.compressed_tree,
+            &ctx.accounts.merkle_tree,
+            &ctx.accounts.noop,
+            compression_append_data(&leaf),
+        )?;
+        let (seq, root) = compressed_tree_root(&ctx.accounts.merkle_tree.try_borrow_data()?)?;
+
+        let compressed = &mut ctx.accounts.compressed_tree;
+        let index = compressed.leaf_count;
+        compressed.leaf_count = compressed
+            .leaf_count
+            .checked_add(1)
+            .ok_or(MoatError::Overflow)?;
+
+        // Proofs may target this root until `max_buffer_size` further appends
+        // push it out of the tree's change log.
+        emit!(CompressedLeafAppended {
+            merkle_tree: compressed.merkle_tree,
+            entry: compressed.entry,
+            index,
+            leaf,
+            seq,
+            root,
+        });
+
+        Ok(())
+    }
+
+    /// Fails unless `leaf` sits at `index` under `root`, which may be any root
+    /// still in the tree's change-log buffer. Proof nodes go in remaining accounts.
+    pub fn verify_compressed_leaf<'info>(
+        ctx: Context<'_, '_, 'info, 'info, VerifyCompressedLeaf<'info>>,
+        root: [u8; 32],
+        leaf: [u8; 32],
+        index: u32,
+    ) -> Result<()> {
+        let data = compression_verify_leaf_data(&root, &leaf, index);
+
+        let mut metas = vec![AccountMeta::new_readonly(ctx.accounts.merkle_tree.key(), false)];
+        let mut infos = vec![
+            ctx.accounts.merkle_tree.to_account_info(),
+            ctx.accounts.compression_program.to_account_info(),
+        ];
+        for node in ctx.remaining_accounts {
+            metas.push(AccountMeta::new_readonly(node.key(), false));
+            infos.push(node.clone());
+        }
+
+        invoke(
+            &Instruction {
+                program_id: SPL_ACCOUNT_COMPRESSION_ID,
+                accounts: metas,
+                data,
+            },
+            &infos,
+        )?;
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + RegistryState::INIT_SPACE,
+        seeds = [b"state"],
+        bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateState<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: may predate the current layout, so it is deserialized in the handler.
+    #[account(mut, owner = crate::ID, seeds = [b"state"], bump)]
+    pub state: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(target_program: Pubkey, kind: u8)]
+pub struct RegisterEntry<'info> {
+    pub authority: Signer<'info>,
+
+    /// Funds new accounts so `authority` can be a PDA signing via CPI.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [b"role", state.key().as_ref(), authority.key().as_ref()],
+        bump = role.bump
+    )]
+    pub role: Option<Account<'info, Role>>,
+
+    #[account(
+        mut,
+        seeds = [b"session", state.key().as_ref(), authority.key().as_ref()],
+        bump = session.bump
+    )]
+    pub session: Option<Account<'info, Session>>,
+
+    #[account(
+        mut,
+        seeds = [b"nonce", state.key().as_ref(), authority.key().as_ref()],
+        bump = nonce.bump
+    )]
+    pub nonce: Option<Account<'info, AuthorityNonce>>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + RegistryEntry::INIT_SPACE,
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &state.next_id.to_le_bytes()[..4]
+        ],
+        bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+
+    // init_if_needed so a second registration of the same target hits DuplicateTarget
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + EntryLookup::INIT_SPACE,
+        seeds = [b"entry", state.key().as_ref(), target_program.as_ref()],
+        bump
+    )]
+    pub lookup: Account<'info, EntryLookup>,
+
+    /// CHECK: validated by `require_kind_enabled` so an undefined kind maps to `KindNotEnabled`.
+    #[account(mut, seeds = [b"kind", state.key().as_ref(), &[kind]], bump)]
+    pub kind_definition: UncheckedAccount<'info>,
+
+    /// CHECK: optional; when supplied its executable flag and owner are checked by `validate_target`.
+    #[account(address = target_program @ MoatError::InvalidTargetProgram)]
+    pub target: Option<UncheckedAccount<'info>>,
+
+    #[account(
+        seeds = [target_program.as_ref()],
+        bump,
+        seeds::program = bpf_loader_upgradeable::ID
+    )]
+    pub program_data: Option<Account<'info, ProgramData>>,
+
+    pub upgrade_authority: Option<Signer<'info>>,
+
+    /// CHECK: must be uninitialized; checked by `require_not_banned`.
+    #[account(seeds = [b"ban", state.key().as_ref(), target_program.as_ref()], bump)]
+    pub ban: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(
+    target_program: Pubkey,
+    kind: u8,
+    target_kind: u8,
+    expires_at: i64,
+    name: String
+)]
+pub struct RegisterNamedEntry<'info> {
+    pub base: RegisterEntry<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = base.payer,
+        space = 8 + NameLookup::INIT_SPACE,
+        seeds = [b"name", base.state.key().as_ref(), name.as_bytes()],
+        bump
+    )]
+    pub name_lookup: Account<'info, NameLookup>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterEntries<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [b"role", state.key().as_ref(), authority.key().as_ref()],
+        bump = role.bump
+    )]
+    pub role: Option<Account<'info, Role>>,
+
+    #[account(
+        mut,
+        seeds = [b"session", state.key().as_ref(), authority.key().as_ref()],
+        bump = session.bump
+    )]
+    pub session: Option<Account<'info, Session>>,
+
+    #[account(
+        mut,
+        seeds = [b"nonce", state.key().as_ref(), authority.key().as_ref()],
+        bump = nonce.bump
+    )]
+    pub nonce: Option<Account<'info, AuthorityNonce>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(target_program: Pubkey, kind: u8)]
+pub struct UpdateEntry<'info> {
+    // Receives the rent of the old lookup when the target changes.
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+
+    #[account(
+        mut,
+        seeds = [b"entry", state.key().as_ref(), entry.target_program.as_ref()],
+        bump = lookup.bump
+    )]
+    pub lookup: Account<'info, EntryLookup>,
+
+    // Same address as `lookup` when the target is unchanged.
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + EntryLookup::INIT_SPACE,
+        seeds = [b"entry", state.key().as_ref(), target_program.as_ref()],
+        bump
+    )]
+    pub new_lookup: Account<'info, EntryLookup>,
+
+    #[account(
+        mut,
+        seeds = [b"kind", state.key().as_ref(), &[entry.kind]],
+        bump = current_kind_definition.bump
+    )]
+    pub current_kind_definition: Account<'info, KindDefinition>,
+
+    // Same address as `current_kind_definition` when the kind is unchanged.
+    /// CHECK: validated by `require_kind_enabled` so an undefined kind maps to `KindNotEnabled`.
+    #[account(mut, seeds = [b"kind", state.key().as_ref(), &[kind]], bump)]
+    pub kind_definition: UncheckedAccount<'info>,
+
+    /// CHECK: must be uninitialized; checked by `require_not_banned`.
+    #[account(seeds = [b"ban", state.key().as_ref(), target_program.as_ref()], bump)]
+    pub ban: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetEntryName<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+}
+
+#[derive(Accounts)]
+#[instruction(metadata_uri: String)]
+pub struct SetEntryMetadata<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        realloc = 8 + RegistryEntry::INIT_SPACE + metadata_uri.len(),
+        realloc::payer = payer,
+        realloc::zero = false,
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+
+    #[account(
+        mut,
+        seeds = [b"nonce", state.key().as_ref(), authority.key().as_ref()],
+        bump = nonce.bump
+    )]
+    pub nonce: Option<Account<'info, AuthorityNonce>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseEntry<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        close = destination,
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+
+    #[account(
+        mut,
+        close = destination,
+        seeds = [b"entry", state.key().as_ref(), entry.target_program.as_ref()],
+        bump = lookup.bump
+    )]
+    pub lookup: Account<'info, EntryLookup>,
+
+    #[account(
+        mut,
+        seeds = [b"kind", state.key().as_ref(), &[entry.kind]],
+        bump = kind_definition.bump
+    )]
+    pub kind_definition: Account<'info, KindDefinition>,
+
+    #[account(
+        mut,
+        close = destination,
+        constraint = name_lookup.entry == entry.key() @ MoatError::InvalidNameLookup,
+        seeds = [b"name", state.key().as_ref(), name_lookup.name.as_bytes()],
+        bump = name_lookup.bump
+    )]
+    pub name_lookup: Option<Account<'info, NameLookup>>,
+
+    /// CHECK: receives the reclaimed rent; any writable account is accepted.
+    #[account(mut)]
+    pub destination: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GcEntry<'info> {
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        close = admin,
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+
+    #[account(
+        mut,
+        close = admin,
+        seeds = [b"entry", state.key().as_ref(), entry.target_program.as_ref()],
+        bump = lookup.bump
+    )]
+    pub lookup: Account<'info, EntryLookup>,
+
+    #[account(
+        mut,
+        seeds = [b"kind", state.key().as_ref(), &[entry.kind]],
+        bump = kind_definition.bump
+    )]
+    pub kind_definition: Account<'info, KindDefinition>,
+
+    #[account(
+        mut,
+        close = admin,
+        constraint = name_lookup.entry == entry.key() @ MoatError::InvalidNameLookup,
+        seeds = [b"name", state.key().as_ref(), name_lookup.name.as_bytes()],
+        bump = name_lookup.bump
+    )]
+    pub name_lookup: Option<Account<'info, NameLookup>>,
+
+    /// CHECK: the entry admin, refunded the remaining rent.
+    #[account(mut, address = entry.admin)]
+    pub admin: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeEntryTransfer<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptEntryTransfer<'info> {
+    pub new_admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+}
+
+#[derive(Accounts)]
+pub struct SetDelegate<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+}
+
+#[derive(Accounts)]
+pub struct SetTags<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+}
+
+#[derive(Accounts)]
+pub struct SetWeight<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+}
+
+#[derive(Accounts)]
+pub struct DeprecateEntry<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+
+    #[account(
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &successor.id.to_le_bytes()
+        ],
+        bump = successor.bump
+    )]
+    pub successor: Option<Account<'info, RegistryEntry>>,
+}
+
+#[derive(Accounts)]
+pub struct SetParent<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+
+    #[account(
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &parent.id.to_le_bytes()
+        ],
+        bump = parent.bump
+    )]
+    pub parent: Option<Account<'info, RegistryEntry>>,
+}
+
+#[derive(Accounts)]
+pub struct SetEntryStatus<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+
+    #[account(
+        mut,
+        seeds = [b"kind", state.key().as_ref(), &[entry.kind]],
+        bump = kind_definition.bump
+    )]
+    pub kind_definition: Account<'info, KindDefinition>,
+}
+
+#[derive(Accounts)]
+pub struct RenewEntry<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+}
+
+#[derive(Accounts)]
+pub struct SetEntryFrozen<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+}
+
+#[derive(Accounts)]
+#[instruction(kind: u8)]
+pub struct DefineKind<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [b"role", state.key().as_ref(), authority.key().as_ref()],
+        bump = role.bump
+    )]
+    pub role: Option<Account<'info, Role>>,
+
+    #[account(
+        mut,
+        seeds = [b"session", state.key().as_ref(), authority.key().as_ref()],
+        bump = session.bump
+    )]
+    pub session: Option<Account<'info, Session>>,
+
+    #[account(
+        mut,
+        seeds = [b"nonce", state.key().as_ref(), authority.key().as_ref()],
+        bump = nonce.bump
+    )]
+    pub nonce: Option<Account<'info, AuthorityNonce>>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + KindDefinition::INIT_SPACE,
+        seeds = [b"kind", state.key().as_ref(), &[kind]],
+        bump
+    )]
+    pub kind_definition: Account<'info, KindDefinition>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump = admin_log.load()?.bump
+    )]
+    pub admin_log: Option<AccountLoader<'info, AdminLog>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateKind<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [b"role", state.key().as_ref(), authority.key().as_ref()],
+        bump = role.bump
+    )]
+    pub role: Option<Account<'info, Role>>,
+
+    #[account(
+        mut,
+        seeds = [b"session", state.key().as_ref(), authority.key().as_ref()],
+        bump = session.bump
+    )]
+    pub session: Option<Account<'info, Session>>,
+
+    #[account(
+        mut,
+        seeds = [b"nonce", state.key().as_ref(), authority.key().as_ref()],
+        bump = nonce.bump
+    )]
+    pub nonce: Option<Account<'info, AuthorityNonce>>,
+
+    #[account(
+        mut,
+        seeds = [b"kind", state.key().as_ref(), &[kind_definition.kind]],
+        bump = kind_definition.bump
+    )]
+    pub kind_definition: Account<'info, KindDefinition>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump = admin_log.load()?.bump
+    )]
+    pub admin_log: Option<AccountLoader<'info, AdminLog>>,
+}
+
+#[derive(Accounts)]
+#[instruction(kind: u8)]
+pub struct DefineCommitKind<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [b"role", state.key().as_ref(), authority.key().as_ref()],
+        bump = role.bump
+    )]
+    pub role: Option<Account<'info, Role>>,
+
+    #[account(
+        mut,
+        seeds = [b"session", state.key().as_ref(), authority.key().as_ref()],
+        bump = session.bump
+    )]
+    pub session: Option<Account<'info, Session>>,
+
+    #[account(
+        mut,
+        seeds = [b"nonce", state.key().as_ref(), authority.key().as_ref()],
+        bump = nonce.bump
+    )]
+    pub nonce: Option<Account<'info, AuthorityNonce>>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + CommitKindDefinition::INIT_SPACE,
+        seeds = [b"commit_kind", state.key().as_ref(), &[kind]],
+        bump
+    )]
+    pub commit_kind: Account<'info, CommitKindDefinition>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump = admin_log.load()?.bump
+    )]
+    pub admin_log: Option<AccountLoader<'info, AdminLog>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(target: Pubkey)]
+pub struct BanTarget<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + BannedTarget::INIT_SPACE,
+        seeds = [b"ban", state.key().as_ref(), target.as_ref()],
+        bump
+    )]
+    pub ban: Account<'info, BannedTarget>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump = admin_log.load()?.bump
+    )]
+    pub admin_log: Option<AccountLoader<'info, AdminLog>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UnbanTarget<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"ban", state.key().as_ref(), ban.target.as_ref()],
+        bump = ban.bump
+    )]
+    pub ban: Account<'info, BannedTarget>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump = admin_log.load()?.bump
+    )]
+    pub admin_log: Option<AccountLoader<'info, AdminLog>>,
+}
+
+#[derive(Accounts)]
+pub struct InitAdminLog<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + AdminLog::INIT_SPACE,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump
+    )]
+    pub admin_log: AccountLoader<'info, AdminLog>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitRecentCommits<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + RecentCommits::INIT_SPACE,
+        seeds = [b"recent_commits", state.key().as_ref()],
+        bump
+    )]
+    pub recent_commits: AccountLoader<'info, RecentCommits>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitCommitLog<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(zero)]
+    pub commit_log: AccountLoader<'info, CommitLog>,
+
+    /// The segment being retired, required once a log exists.
+    pub previous_commit_log: Option<AccountLoader<'info, CommitLog>>,
+}
+
+#[derive(Accounts)]
+pub struct SetCommitCosigners<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + CommitCosigners::INIT_SPACE,
+        seeds = [b"cosigners", state.key().as_ref()],
+        bump
+    )]
+    pub cosigners: Account<'info, CommitCosigners>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump = admin_log.load()?.bump
+    )]
+    pub admin_log: Option<AccountLoader<'info, AdminLog>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(holder: Pubkey)]
+pub struct GrantRole<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + Role::INIT_SPACE,
+        seeds = [b"role", state.key().as_ref(), holder.as_ref()],
+        bump
+    )]
+    pub role: Account<'info, Role>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump = admin_log.load()?.bump
+    )]
+    pub admin_log: Option<AccountLoader<'info, AdminLog>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeRole<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"role", state.key().as_ref(), role.holder.as_ref()],
+        bump = role.bump
+    )]
+    pub role: Account<'info, Role>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump = admin_log.load()?.bump
+    )]
+    pub admin_log: Option<AccountLoader<'info, AdminLog>>,
+}
+
+#[derive(Accounts)]
+#[instruction(session_key: Pubkey)]
+pub struct CreateSession<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + Session::INIT_SPACE,
+        seeds = [b"session", state.key().as_ref(), session_key.as_ref()],
+        bump
+    )]
+    pub session: Account<'info, Session>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump = admin_log.load()?.bump
+    )]
+    pub admin_log: Option<AccountLoader<'info, AdminLog>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeSession<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"session", state.key().as_ref(), session.session_key.as_ref()],
+        bump = session.bump
+    )]
+    pub session: Account<'info, Session>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump = admin_log.load()?.bump
+    )]
+    pub admin_log: Option<AccountLoader<'info, AdminLog>>,
+}
+
+#[derive(Accounts)]
+pub struct ConsumeNonce<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + AuthorityNonce::INIT_SPACE,
+        seeds = [b"nonce", state.key().as_ref(), authority.key().as_ref()],
+        bump
+    )]
+    pub nonce: Account<'info, AuthorityNonce>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveRegistrar<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [b"role", state.key().as_ref(), role.holder.as_ref()],
+        bump = role.bump
+    )]
+    pub role: Account<'info, Role>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump = admin_log.load()?.bump
+    )]
+    pub admin_log: Option<AccountLoader<'info, AdminLog>>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigureRegistry<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [b"role", state.key().as_ref(), authority.key().as_ref()],
+        bump = role.bump
+    )]
+    pub role: Option<Account<'info, Role>>,
+
+    #[account(
+        mut,
+        seeds = [b"session", state.key().as_ref(), authority.key().as_ref()],
+        bump = session.bump
+    )]
+    pub session: Option<Account<'info, Session>>,
+
+    #[account(
+        mut,
+        seeds = [b"nonce", state.key().as_ref(), authority.key().as_ref()],
+        bump = nonce.bump
+    )]
+    pub nonce: Option<Account<'info, AuthorityNonce>>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump = admin_log.load()?.bump
+    )]
+    pub admin_log: Option<AccountLoader<'info, AdminLog>>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeGovernanceAdmin<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    /// CHECK: a Realms governance account; its owner is taken as the governance program.
+    pub governance: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump = admin_log.load()?.bump
+    )]
+    pub admin_log: Option<AccountLoader<'info, AdminLog>>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    pub new_admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump = admin_log.load()?.bump
+    )]
+    pub admin_log: Option<AccountLoader<'info, AdminLog>>,
+}
+
+#[derive(Accounts)]
+pub struct Recover<'info> {
+    pub recovery_authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump = admin_log.load()?.bump
+    )]
+    pub admin_log: Option<AccountLoader<'info, AdminLog>>,
+}
+
+#[derive(Accounts)]
+pub struct QueueAction<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + PendingAction::INIT_SPACE,
+        seeds = [
+            b"action",
+            state.key().as_ref(),
+            &state.next_action_id.to_le_bytes()
+        ],
+        bump
+    )]
+    pub pending_action: Account<'info, PendingAction>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump = admin_log.load()?.bump
+    )]
+    pub admin_log: Option<AccountLoader<'info, AdminLog>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteAction<'info> {
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        close = queued_by,
+        seeds = [
+            b"action",
+            state.key().as_ref(),
+            &pending_action.id.to_le_bytes()
+        ],
+        bump = pending_action.bump
+    )]
+    pub pending_action: Account<'info, PendingAction>,
+
+    /// CHECK: receives the rent of the executed action.
+    #[account(mut, address = pending_action.queued_by)]
+    pub queued_by: UncheckedAccount<'info>,
+
+    /// Required by `UpdateKind` actions.
+    #[account(
+        mut,
+        seeds = [b"kind", state.key().as_ref(), &[kind_definition.kind]],
+        bump = kind_definition.bump
+    )]
+    pub kind_definition: Option<Account<'info, KindDefinition>>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump = admin_log.load()?.bump
+    )]
+    pub admin_log: Option<AccountLoader<'info, AdminLog>>,
+}
+
+#[derive(Accounts)]
+pub struct CancelAction<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [
+            b"action",
+            state.key().as_ref(),
+            &pending_action.id.to_le_bytes()
+        ],
+        bump = pending_action.bump
+    )]
+    pub pending_action: Account<'info, PendingAction>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump = admin_log.load()?.bump
+    )]
+    pub admin_log: Option<AccountLoader<'info, AdminLog>>,
+}
+
+#[derive(Accounts)]
+pub struct CreateMultisig<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + MultisigConfig::INIT_SPACE,
+        seeds = [b"multisig", state.key().as_ref()],
+        bump
+    )]
+    pub multisig: Account<'info, MultisigConfig>,
+
+    /// CHECK: data-less PDA that signs executed proposals; only its address is used.
+    #[account(seeds = [b"multisig_signer", multisig.key().as_ref()], bump)]
+    pub multisig_signer: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump = admin_log.load()?.bump
+    )]
+    pub admin_log: Option<AccountLoader<'info, AdminLog>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetMultisigOwners<'info> {
+    #[account(
+        seeds = [b"multisig_signer", multisig.key().as_ref()],
+        bump = multisig.signer_bump
+    )]
+    pub multisig_signer: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [b"multisig", state.key().as_ref()],
+        bump = multisig.bump
+    )]
+    pub multisig: Account<'info, MultisigConfig>,
+}
+
+#[derive(Accounts)]
+#[instruction(
+    instruction_program: Pubkey,
+    instruction_accounts: Vec<MultisigAccountMeta>,
+    instruction_data: Vec<u8>
+)]
+pub struct ProposeMultisigAction<'info> {
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [b"multisig", state.key().as_ref()],
+        bump = multisig.bump
+    )]
+    pub multisig: Account<'info, MultisigConfig>,
+
+    #[account(
+        init,
+        payer = proposer,
+        space = 8
+            + MultisigProposal::INIT_SPACE
+            + instruction_accounts.len() * MultisigAccountMeta::INIT_SPACE
+            + instruction_data.len(),
+        seeds = [
+            b"proposal",
+            multisig.key().as_ref(),
+            &multisig.next_proposal_id.to_le_bytes()
+        ],
+        bump
+    )]
+    pub proposal: Account<'info, MultisigProposal>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveMultisigAction<'info> {
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        seeds = [b"multisig", state.key().as_ref()],
+        bump = multisig.bump
+    )]
+    pub multisig: Account<'info, MultisigConfig>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"proposal",
+            multisig.key().as_ref(),
+            &proposal.id.to_le_bytes()
+        ],
+        bump = proposal.bump
+    )]
+    pub proposal: Account<'info, MultisigProposal>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteMultisigAction<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        seeds = [b"multisig", state.key().as_ref()],
+        bump = multisig.bump
+    )]
+    pub multisig: Account<'info, MultisigConfig>,
+
+    /// CHECK: data-less PDA; signs the stored instruction via `invoke_signed`.
+    #[account(
+        seeds = [b"multisig_signer", multisig.key().as_ref()],
+        bump = multisig.signer_bump
+    )]
+    pub multisig_signer: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"proposal",
+            multisig.key().as_ref(),
+            &proposal.id.to_le_bytes()
+        ],
+        bump = proposal.bump
+    )]
+    pub proposal: Account<'info, MultisigProposal>,
+}
+
+#[derive(Accounts)]
+pub struct SetRiskScore<'info> {
+    pub risk_authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+}
+
+#[derive(Accounts)]
+#[instruction(report_hash: [u8; 32])]
+pub struct RecordAudit<'info> {
+    #[account(mut)]
+    pub auditor: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+
+    #[account(
+        init,
+        payer = auditor,
+        space = 8 + AuditRecord::INIT_SPACE,
+        seeds = [b"audit", entry.key().as_ref(), report_hash.as_ref()],
+        bump
+    )]
+    pub audit_record: Account<'info, AuditRecord>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeAudit<'info> {
+    pub auditor: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+
+    #[account(
+        mut,
+        seeds = [b"audit", entry.key().as_ref(), audit_record.report_hash.as_ref()],
+        bump = audit_record.bump
+    )]
+    pub audit_record: Account<'info, AuditRecord>,
+}
+
+#[derive(Accounts)]
+#[instruction(attestor: Pubkey)]
+pub struct AddAttestor<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + AttestorConfig::INIT_SPACE,
+        seeds = [b"attestor", state.key().as_ref(), attestor.as_ref()],
+        bump
+    )]
+    pub attestor_config: Account<'info, AttestorConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump = admin_log.load()?.bump
+    )]
+    pub admin_log: Option<AccountLoader<'info, AdminLog>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveAttestor<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"attestor", state.key().as_ref(), attestor_config.attestor.as_ref()],
+        bump = attestor_config.bump
+    )]
+    pub attestor_config: Account<'info, AttestorConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump = admin_log.load()?.bump
+    )]
+    pub admin_log: Option<AccountLoader<'info, AdminLog>>,
+}
+
+#[derive(Accounts)]
+pub struct AttestEntry<'info> {
+    #[account(mut)]
+    pub attestor: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        seeds = [b"attestor", state.key().as_ref(), attestor.key().as_ref()],
+        bump = attestor_config.bump
+    )]
+    pub attestor_config: Account<'info, AttestorConfig>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+
+    // init_if_needed so an attestor can refresh its claim in place
+    #[account(
+        init_if_needed,
+        payer = attestor,
+        space = 8 + Attestation::INIT_SPACE,
+        seeds = [b"attest", entry.key().as_ref(), attestor.key().as_ref()],
+        bump
+    )]
+    pub attestation: Account<'info, Attestation>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeAttestation<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+
+    #[account(
+        mut,
+        seeds = [b"attest", entry.key().as_ref(), attestation.attestor.as_ref()],
+        bump = attestation.bump
+    )]
+    pub attestation: Account<'info, Attestation>,
+}
+
+#[derive(Accounts)]
+pub struct LapseAttestation<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+
+    // Closed, so the attestor can attest afresh and be counted again.
+    #[account(
+        mut,
+        close = attestor,
+        seeds = [b"attest", entry.key().as_ref(), attestation.attestor.as_ref()],
+        bump = attestation.bump
+    )]
+    pub attestation: Account<'info, Attestation>,
+
+    /// CHECK: empty once `remove_attestor` has closed it.
+    #[account(
+        seeds = [b"attestor", state.key().as_ref(), attestation.attestor.as_ref()],
+        bump
+    )]
+    pub attestor_config: UncheckedAccount<'info>,
+
+    /// CHECK: the attestor, refunded the attestation's rent.
+    #[account(mut, address = attestation.attestor)]
+    pub attestor: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AttestCommit<'info> {
+    #[account(mut)]
+    pub attestor: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        seeds = [b"attestor", state.key().as_ref(), attestor.key().as_ref()],
+        bump = attestor_config.bump
+    )]
+    pub attestor_config: Account<'info, AttestorConfig>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"commit",
+            batch.entry.as_ref(),
+            &batch.seq.to_le_bytes()
+        ],
+        bump = batch.bump
+    )]
+    pub batch: Account<'info, BatchCommit>,
+
+    // init, so each attestor counts once per commit
+    #[account(
+        init,
+        payer = attestor,
+        space = 8 + CommitAttestation::INIT_SPACE,
+        seeds = [b"commit_attest", batch.key().as_ref(), attestor.key().as_ref()],
+        bump
+    )]
+    pub commit_attestation: Account<'info, CommitAttestation>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RefreshVerification<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+}
+
+#[derive(Accounts)]
+#[instruction(bit: u8)]
+pub struct DefineTag<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [b"role", state.key().as_ref(), authority.key().as_ref()],
+        bump = role.bump
+    )]
+    pub role: Option<Account<'info, Role>>,
+
+    #[account(
+        mut,
+        seeds = [b"session", state.key().as_ref(), authority.key().as_ref()],
+        bump = session.bump
+    )]
+    pub session: Option<Account<'info, Session>>,
+
+    #[account(
+        mut,
+        seeds = [b"nonce", state.key().as_ref(), authority.key().as_ref()],
+        bump = nonce.bump
+    )]
+    pub nonce: Option<Account<'info, AuthorityNonce>>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + TagDefinition::INIT_SPACE,
+        seeds = [b"tag", state.key().as_ref(), &[bit]],
+        bump
+    )]
+    pub tag_definition: Account<'info, TagDefinition>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump = admin_log.load()?.bump
+    )]
+    pub admin_log: Option<AccountLoader<'info, AdminLog>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CommitBatch<'info> {
+    #[account(mut)]
+    pub creator: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [b"role", state.key().as_ref(), creator.key().as_ref()],
+        bump = role.bump
+    )]
+    pub role: Option<Account<'info, Role>>,
+
+    #[account(
+        mut,
+        seeds = [b"session", state.key().as_ref(), creator.key().as_ref()],
+        bump = session.bump
+    )]
+    pub session: Option<Account<'info, Session>>,
+
+    #[account(
+        mut,
+        seeds = [b"nonce", state.key().as_ref(), creator.key().as_ref()],
+        bump = nonce.bump
+    )]
+    pub nonce: Option<Account<'info, AuthorityNonce>>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+
+    #[account(
+        init,
+        payer = creator,
+        space = 8 + BatchCommit::INIT_SPACE,
+        seeds = [
+            b"commit",
+            entry.key().as_ref(),
+            &entry.commit_count.to_le_bytes()
+        ],
+        bump
+    )]
+    pub batch: Account<'info, BatchCommit>,
+
+    #[account(
+        mut,
+        seeds = [b"recent_commits", state.key().as_ref()],
+        bump = recent_commits.load()?.bump
+    )]
+    pub recent_commits: Option<AccountLoader<'info, RecentCommits>>,
+
+    #[account(mut, address = state.commit_log)]
+    pub commit_log: Option<AccountLoader<'info, CommitLog>>,
+
+    #[account(
+        init_if_needed,
+        payer = creator,
+        space = 8 + EpochSummary::INIT_SPACE,
+        seeds = [
+            b"epoch",
+            state.key().as_ref(),
+            &state.current_epoch(Clock::get()?.unix_timestamp).to_le_bytes()
+        ],
+        bump
+    )]
+    pub epoch_summary: Option<Account<'info, EpochSummary>>,
+
+    #[account(
+        seeds = [b"commit_kind", state.key().as_ref(), &[commit_kind.kind]],
+        bump = commit_kind.bump
+    )]
+    pub commit_kind: Option<Account<'info, CommitKindDefinition>>,
+
+    #[account(
+        seeds = [b"cosigners", state.key().as_ref()],
+        bump = cosigners.bump
+    )]
+    pub cosigners: Option<Account<'info, CommitCosigners>>,
+
+    /// CHECK: the instructions sysvar, read for Ed25519 co-signatures.
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: address checked; invoked when the registry sets a commit verifier.
+    #[account(executable, address = state.commit_verifier)]
+    pub commit_verifier: Option<UncheckedAccount<'info>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CommitBatches<'info> {
+    #[account(mut)]
+    pub creator: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [b"role", state.key().as_ref(), creator.key().as_ref()],
+        bump = role.bump
+    )]
+    pub role: Option<Account<'info, Role>>,
+
+    #[account(
+        mut,
+        seeds = [b"session", state.key().as_ref(), creator.key().as_ref()],
+        bump = session.bump
+    )]
+    pub session: Option<Account<'info, Session>>,
+
+    #[account(
+        mut,
+        seeds = [b"nonce", state.key().as_ref(), creator.key().as_ref()],
+        bump = nonce.bump
+    )]
+    pub nonce: Option<Account<'info, AuthorityNonce>>,
+
+    #[account(
+        mut,
+        seeds = [b"recent_commits", state.key().as_ref()],
+        bump = recent_commits.load()?.bump
+    )]
+    pub recent_commits: Option<AccountLoader<'info, RecentCommits>>,
+
+    #[account(mut, address = state.commit_log)]
+    pub commit_log: Option<AccountLoader<'info, CommitLog>>,
+
+    #[account(
+        init_if_needed,
+        payer = creator,
+        space = 8 + EpochSummary::INIT_SPACE,
+        seeds = [
+            b"epoch",
+            state.key().as_ref(),
+            &state.current_epoch(Clock::get()?.unix_timestamp).to_le_bytes()
+        ],
+        bump
+    )]
+    pub epoch_summary: Option<Account<'info, EpochSummary>>,
+
+    #[account(
+        seeds = [b"commit_kind", state.key().as_ref(), &[commit_kind.kind]],
+        bump = commit_kind.bump
+    )]
+    pub commit_kind: Option<Account<'info, CommitKindDefinition>>,
+
+    #[account(
+        seeds = [b"cosigners", state.key().as_ref()],
+        bump = cosigners.bump
+    )]
+    pub cosigners: Option<Account<'info, CommitCosigners>>,
+
+    /// CHECK: the instructions sysvar, read for Ed25519 co-signatures.
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: address checked; invoked when the registry sets a commit verifier.
+    #[account(executable, address = state.commit_verifier)]
+    pub commit_verifier: Option<UncheckedAccount<'info>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SupersedeCommit<'info> {
+    pub commit: CommitBatch<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"commit",
+            superseded.entry.as_ref(),
+            &superseded.seq.to_le_bytes()
+        ],
+       bump = superseded.bump,
+        constraint = superseded.entry == commit.entry.key() @ MoatError::CommitEntryMismatch
+    )]
+    pub superseded: Account<'info, BatchCommit>,
+}
+
+#[derive(Accounts)]
+pub struct AggregateCommits<'info> {
+    pub commit: CommitBatch<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + CommitAggregate::INIT_SPACE,
+        seeds = [b"aggregate", commit.batch.key().as_ref()],
+        bump
+    )]
+    pub aggregate: Account<'info, CommitAggregate>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CommitRegistryRoot<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [b"role", state.key().as_ref(), authority.key().as_ref()],
+        bump = role.bump
+    )]
+    pub role: Option<Account<'info, Role>>,
+
+    #[account(
+        mut,
+        seeds = [b"session", state.key().as_ref(), authority.key().as_ref()],
+        bump = session.bump
+    )]
+    pub session: Option<Account<'info, Session>>,
+
+    #[account(
+        mut,
+        seeds = [b"nonce", state.key().as_ref(), authority.key().as_ref()],
+        bump = nonce.bump
+    )]
+    pub nonce: Option<Account<'info, AuthorityNonce>>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + RegistryRoot::INIT_SPACE,
+        seeds = [b"registry_root", state.key().as_ref()],
+        bump
+    )]
+    pub registry_root: Account<'info, RegistryRoot>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump = admin_log.load()?.bump
+    )]
+    pub admin_log: Option<AccountLoader<'info, AdminLog>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExportEntryProof<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        seeds = [b"registry_root", state.key().as_ref()],
+        bump = registry_root.bump
+    )]
+    pub registry_root: Account<'info, RegistryRoot>,
+
+    #[account(
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeCommit<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"commit",
+            batch.entry.as_ref(),
+            &batch.seq.to_le_bytes()
+        ],
+        bump = batch.bump
+    )]
+    pub batch: Account<'info, BatchCommit>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump = admin_log.load()?.bump
+    )]
+    pub admin_log: Option<AccountLoader<'info, AdminLog>>,
+}
+
+#[derive(Accounts)]
+pub struct ExcludeLeaves<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"commit",
+            batch.entry.as_ref(),
+            &batch.seq.to_le_bytes()
+        ],
+        bump = batch.bump
+    )]
+    pub batch: Account<'info, BatchCommit>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + ExclusionList::INIT_SPACE,
+        seeds = [b"exclusions", batch.key().as_ref()],
+        bump
+    )]
+    pub exclusions: Account<'info, ExclusionList>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump = admin_log.load()?.bump
+    )]
+    pub admin_log: Option<AccountLoader<'info, AdminLog>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetCommitConsumer<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"commit",
+            batch.entry.as_ref(),
+            &batch.seq.to_le_bytes()
+        ],
+        bump = batch.bump
+    )]
+    pub batch: Account<'info, BatchCommit>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump = admin_log.load()?.bump
+    )]
+    pub admin_log: Option<AccountLoader<'info, AdminLog>>,
+}
+
+#[derive(Accounts)]
+pub struct SetCommitAcl<'info> {
+    #[account(mut)]
+    pub creator: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = creator @ MoatError::Unauthorized,
+        seeds = [
+            b"commit",
+            batch.entry.as_ref(),
+            &batch.seq.to_le_bytes()
+        ],
+        bump = batch.bump
+    )]
+    pub batch: Account<'info, BatchCommit>,
+
+    #[account(
+        init_if_needed,
+        payer = creator,
+        space = 8 + CommitAcl::INIT_SPACE,
+        seeds = [b"acl", batch.key().as_ref()],
+        bump
+    )]
+    pub acl: Account<'info, CommitAcl>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AnnotateCommit<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        seeds = [
+            b"commit",
+            batch.entry.as_ref(),
+            &batch.seq.to_le_bytes()
+        ],
+        bump = batch.bump
+    )]
+    pub batch: Account<'info, BatchCommit>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + CommitAnnotations::INIT_SPACE,
+        seeds = [b"annotations", batch.key().as_ref()],
+        bump
+    )]
+    pub annotations: Account<'info, CommitAnnotations>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeCommit<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"commit",
+            batch.entry.as_ref(),
+            &batch.seq.to_le_bytes()
+        ],
+        bump = batch.bump
+    )]
+    pub batch: Account<'info, BatchCommit>,
+}
+
+#[derive(Accounts)]
+pub struct PruneCommit<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    /// CHECK: loaded as a `BatchCommit` in the handler; an `Account` would be
+    /// written back over the tombstone on exit.
+    #[account(mut, owner = crate::ID)]
+    pub batch: UncheckedAccount<'info>,
+
+    /// Receives the refund; must be the commit's creator.
+    #[account(mut)]
+    pub creator: SystemAccount<'info>,
+
+    /// CHECK: receives a revoked commit's stake.
+    #[account(mut, address = state.admin)]
+    pub admin: UncheckedAccount<'info>,
+
+    /// CHECK: closed in the handler when the commit has one.
+    #[account(mut, seeds = [b"claims", batch.key().as_ref()], bump)]
+    pub claim_bitmap: UncheckedAccount<'info>,
+
+    /// CHECK: closed in the handler when the commit has one.
+    #[account(mut, seeds = [b"acl", batch.key().as_ref()], bump)]
+    pub acl: UncheckedAccount<'info>,
+
+    /// CHECK: closed in the handler when the commit has one.
+    #[account(mut, seeds = [b"exclusions", batch.key().as_ref()], bump)]
+    pub exclusions: UncheckedAccount<'info>,
+
+    /// CHECK: closed in the handler when the commit has one.
+    #[account(mut, seeds = [b"annotations", batch.key().as_ref()], bump)]
+    pub annotations: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SweepVault<'info> {
+    /// Receives the tokens and the vault's rent: the commit's creator, or the
+    /// entry's admin once the commit is a tombstone.
+    #[account(mut)]
+    pub recipient: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+
+    /// CHECK: a `BatchCommit` or its `CommitTombstone`, told apart in the handler.
+    #[account(owner = crate::ID)]
+    pub batch: UncheckedAccount<'info>,
+
+    /// CHECK: signs for the vault; holds no data.
+    #[account(seeds = [b"vault", batch.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    /// CHECK: token account owned by `vault_authority`, checked in the handler.
+    #[account(mut, owner = token_program.key())]
+    pub vault: UncheckedAccount<'info>,
+
+    /// CHECK: token account owned by `recipient`, checked in the handler.
+    #[account(mut, owner = token_program.key())]
+    pub destination: UncheckedAccount<'info>,
+
+    /// CHECK: must match both token accounts; the token program checks it too.
+    #[account(owner = token_program.key())]
+    pub mint: UncheckedAccount<'info>,
+
+    /// CHECK: one of the two token programs.
+    #[account(
+        constraint = token_program.key() == TOKEN_PROGRAM_ID
+            || token_program.key() == TOKEN_2022_PROGRAM_ID
+            @ MoatError::InvalidTokenAccount
+    )]
+    pub token_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitClaimBitmap<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [
+            b"commit",
+            batch.entry.as_ref(),
+            &batch.seq.to_le_bytes()
+        ],
+       bump = batch.bump,
+        constraint = batch.claim_slots().div_ceil(8) <= MAX_CLAIM_BITMAP_BYTES as u64
+            @ MoatError::ClaimBitmapTooLarge
+    )]
+    pub batch: Account<'info, BatchCommit>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + ClaimBitmap::INIT_SPACE + batch.claim_slots().div_ceil(8) as usize,
+        seeds = [b"claims", batch.key().as_ref()],
+        bump
+    )]
+    pub claim_bitmap: Account<'info, ClaimBitmap>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(seq: u64)]
+pub struct VerifyInclusion<'info> {
+    pub entry: Account<'info, RegistryEntry>,
+
+    // The stored bump spares every verification a find_program_address search.
+    #[account(
+        seeds = [
+            b"commit",
+            entry.key().as_ref(),
+            &seq.to_le_bytes()
+        ],
+        bump = batch.bump
+    )]
+    pub batch: Account<'info, BatchCommit>,
+
+    #[account(
+        seeds = [b"exclusions", batch.key().as_ref()],
+        bump = exclusions.bump
+    )]
+    pub exclusions: Option<Account<'info, ExclusionList>>,
+}
+
+#[derive(Accounts)]
+pub struct ConsumeLeaf<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [
+            b"commit",
+            batch.entry.as_ref(),
+            &batch.seq.to_le_bytes()
+        ],
+        bump = batch.bump
+    )]
+    pub batch: Account<'info, BatchCommit>,
+
+    #[account(address = batch.entry)]
+    pub entry: Account<'info, RegistryEntry>,
+
+    #[account(
+        mut,
+        seeds = [b"nonce", entry.registry.as_ref(), authority.key().as_ref()],
+        bump = nonce.bump
+    )]
+    pub nonce: Option<Account<'info, AuthorityNonce>>,
+
+    #[account(
+        mut,
+        seeds = [b"claims", batch.key().as_ref()],
+        bump = claim_bitmap.bump
+    )]
+    pub claim_bitmap: Account<'info, ClaimBitmap>,
+
+    #[account(
+        seeds = [b"exclusions", batch.key().as_ref()],
+        bump = exclusions.bump
+    )]
+    pub exclusions: Option<Account<'info, ExclusionList>>,
+
+    #[account(
+        seeds = [b"acl", batch.key().as_ref()],
+        bump = acl.bump
+    )]
+    pub acl: Option<Account<'info, CommitAcl>>,
+
+    /// CHECK: the instructions sysvar, read to find the calling program.
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct Claim<'info> {
+    pub claimant: Signer<'info>,
+
+    #[account(
+        seeds = [
+            b"commit",
+            batch.entry.as_ref(),
+            &batch.seq.to_le_bytes()
+        ],
+        bump = batch.bump
+    )]
+    pub batch: Account<'info, BatchCommit>,
+
+    #[account(address = batch.entry)]
+    pub entry: Account<'info, RegistryEntry>,
+
+    #[account(
+        mut,
+        seeds = [b"claims", batch.key().as_ref()],
+        bump = claim_bitmap.bump
+    )]
+    pub claim_bitmap: Account<'info, ClaimBitmap>,
+
+    #[account(
+        seeds = [b"exclusions", batch.key().as_ref()],
+        bump = exclusions.bump
+    )]
+    pub exclusions: Option<Account<'info, ExclusionList>>,
+
+    #[account(
+        seeds = [b"acl", batch.key().as_ref()],
+        bump = acl.bump
+    )]
+    pub acl: Option<Account<'info, CommitAcl>>,
+
+    /// CHECK: the instructions sysvar, read to find the calling program.
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: signs for the vault; holds no data.
+    #[account(seeds = [b"vault", batch.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    /// CHECK: token account owned by `vault_authority`, checked in the handler.
+    #[account(mut, owner = token_program.key())]
+    pub vault: UncheckedAccount<'info>,
+
+    /// CHECK: token account owned by `claimant`, checked in the handler.
+    #[account(mut, owner = token_program.key())]
+    pub destination: UncheckedAccount<'info>,
+
+    /// CHECK: must match both token accounts; the token program checks it too.
+    #[account(owner = token_program.key())]
+    pub mint: UncheckedAccount<'info>,
+
+    /// CHECK: one of the two token programs.
+    #[account(
+        constraint = token_program.key() == TOKEN_PROGRAM_ID
+            || token_program.key() == TOKEN_2022_PROGRAM_ID
+            @ MoatError::InvalidTokenAccount
+    )]
+    pub token_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ChallengeCommit<'info> {
+    #[account(mut)]
+    pub challenger: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"commit",
+            batch.entry.as_ref(),
+            &batch.seq.to_le_bytes()
+        ],
+        bump = batch.bump
+    )]
+    pub batch: Account<'info, BatchCommit>,
+
+    #[account(
+        init,
+        payer = challenger,
+        space = 8 + Challenge::INIT_SPACE,
+        seeds = [b"challenge", batch.key().as_ref()],
+        bump
+    )]
+    pub challenge: Account<'info, Challenge>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveChallenge<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"commit",
+            batch.entry.as_ref(),
+            &batch.seq.to_le_bytes()
+        ],
+        bump = batch.bump
+    )]
+    pub batch: Account<'info, BatchCommit>,
+
+    #[account(
+        mut,
+        seeds = [b"challenge", batch.key().as_ref()],
+        bump = challenge.bump,
+        has_one = challenger
+    )]
+    pub challenge: Account<'info, Challenge>,
+
+    /// CHECK: bound to the challenge by `has_one`; paid out when upheld.
+    #[account(mut)]
+    pub challenger: UncheckedAccount<'info>,
+
+    /// CHECK: the committer; receives the bond when the challenge is rejected.
+    #[account(mut, address = batch.creator)]
+    pub creator: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"admin_log", state.key().as_ref()],
+        bump = admin_log.load()?.bump
+    )]
+    pub admin_log: Option<AccountLoader<'info, AdminLog>>,
+}
+
+#[derive(Accounts)]
+pub struct InitTree<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+
+    #[account(
+        mut,
+        seeds = [b"nonce", state.key().as_ref(), authority.key().as_ref()],
+        bump = nonce.bump
+    )]
+    pub nonce: Option<Account<'info, AuthorityNonce>>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + IncrementalTree::INIT_SPACE,
+        seeds = [b"tree", entry.key().as_ref()],
+        bump
+    )]
+    pub tree: Account<'info, IncrementalTree>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AppendLeaf<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+
+    #[account(
+        mut,
+        seeds = [b"nonce", state.key().as_ref(), authority.key().as_ref()],
+        bump = nonce.bump
+    )]
+    pub nonce: Option<Account<'info, AuthorityNonce>>,
+
+    #[account(
+        mut,
+        seeds = [b"tree", entry.key().as_ref()],
+        bump = tree.bump
+    )]
+    pub tree: Account<'info, IncrementalTree>,
+}
+
+#[derive(Accounts)]
+pub struct InitCompressedTree<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+
+    #[account(
+        mut,
+        seeds = [b"nonce", state.key().as_ref(), authority.key().as_ref()],
+        bump = nonce.bump
+    )]
+    pub nonce: Option<Account<'info, AuthorityNonce>>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + CompressedTree::INIT_SPACE,
+        seeds = [b"compressed_tree", entry.key().as_ref()],
+        bump
+    )]
+    pub compressed_tree: Account<'info, CompressedTree>,
+
+    /// CHECK: allocated by the client and initialized by the compression program.
+    #[account(mut, owner = SPL_ACCOUNT_COMPRESSION_ID)]
+    pub merkle_tree: UncheckedAccount<'info>,
+
+    /// CHECK: address checked.
+    #[account(address = SPL_ACCOUNT_COMPRESSION_ID)]
+    pub compression_program: UncheckedAccount<'info>,
+
+    /// CHECK: address checked.
+    #[account(address = SPL_NOOP_ID)]
+    pub noop: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AppendCompressedLeaf<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, RegistryState>,
+
+    #[account(
+        seeds = [
+            b"entry",
+            state.key().as_ref(),
+            &entry.id.to_le_bytes()
+        ],
+        bump = entry.bump
+    )]
+    pub entry: Account<'info, RegistryEntry>,
+
+    #[account(
+        mut,
+        seeds = [b"nonce", state.key().as_ref(), authority.key().as_ref()],
+        bump = nonce.bump
+    )]
+    pub nonce: Option<Account<'info, AuthorityNonce>>,
+
+    #[account(
+        mut,
+        seeds = [b"compressed_tree", entry.key().as_ref()],
+        bump = compressed_tree.bump,
+        has_one = merkle_tree
+    )]
+    pub compressed_tree: Account<'info, CompressedTree>,
+
+    /// CHECK: bound to `compressed_tree` by `has_one`.
+    #[account(mut)]
+    pub merkle_tree: UncheckedAccount<'info>,
+
+    /// CHECK: address checked.
+    #[account(address = SPL_ACCOUNT_COMPRESSION_ID)]
+    pub compression_program: UncheckedAccount<'info>,
+
+    /// CHECK: address checked.
+    #[account(address = SPL_NOOP_ID)]
+    pub noop: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyCompressedLeaf<'info> {
+    #[account(
+        seeds = [b"compressed_tree", compressed_tree.entry.as_ref()],
+        bump = compressed_tree.bump,
+        has_one = merkle_tree
+    )]
+    pub compressed_tree: Account<'info, CompressedTree>,
+
+    /// CHECK: bound to `compressed_tree` by `has_one`.
+    pub merkle_tree: UncheckedAccount<'info>,
+
+    /// CHECK: address checked.
+    #[account(address = SPL_ACCOUNT_COMPRESSION_ID)]
+    pub compression_program: UncheckedAccount<'info>,
+}
+
+#[account]
+pub struct RegistryState {
+    pub admin: Pubkey,
+    pub next_id: u64,
+    pub bump: u8,
+    pub defined_tags: u64,
+    pub require_upgrade_authority: bool,
+    pub auditor: Pubkey,
+    pub min_update_interval: i64,
+    pub risk_authority: Pubkey,
+    pub attestation_threshold: u16,
+    pub pending_admin: Pubkey,
+    pub guardian: Pubkey,
+    pub paused: bool,
+    pub timelock_delay: i64,
+    pub next_action_id: u64,
+    pub open_registration: bool,
+    pub commit_authority: Pubkey,
+    pub recovery_authority: Pubkey,
+    pub recovery_admin: Pubkey,
+    pub recovery_eta: i64,
+    pub admin_log_enabled: bool,
+    pub governance: Pubkey,
+    pub paused_operations: u8,
+    pub min_action_slot_gap: u64,
+    pub rotation_grace_slots: u64,
+    pub previous_admin: Pubkey,
+    pub previous_admin_valid_until: u64,
+    pub previous_commit_authority: Pubkey,
+    pub previous_commit_authority_valid_until: u64,
+    pub next_commit_id: u64,
+    pub dispute_window: i64,
+    pub challenge_bond: u64,
+    pub commit_stake: u64,
+    pub recent_commits_enabled: bool,
+    pub commit_log: Pubkey,
+    pub epoch_length: i64,
+    pub epoch_origin: i64,
+    pub epoch_offset: u64,
+    pub commit_cosigner_threshold: u8,
+    pub min_commit_interval_slots: u64,
+    pub last_commit_slot: u64,
+    pub commit_retention: i64,
+    pub root_attestor: Pubkey,
+    pub root_attestor_eth: [u8; 20],
+    pub commit_verifier: Pubkey,
+    pub registration_allowlist: [u8; 32],
+    pub commit_attestation_quorum: u16,
+}
+impl Space for RegistryState {
+    const INIT_SPACE: usize = 32 + 8 + 1 + 8 + 1 + 32 + 8 + 32 + 2 + 32 + 32 + 1 + 8 + 8 + 1 + 32
+        + 32 + 32 + 8 + 1 + 32 + 1 + 8 + 8 + 32 + 8 + 32 + 8 + 8 + 8 + 8 + 8 + 1 + 32 + 8 + 8 + 8
+        + 1 + 8 + 8 + 8 + 32 + 20 + 32 + 32 + 2;
+}
+
+impl RegistryState {
+    // The outgoing admin stays valid until its grace period ends.
+    pub fn is_admin(&self, key: &Pubkey) -> Result<bool> {
+        Ok(*key == self.admin
+            || (*key == self.previous_admin
+                && Clock::get()?.slot <= self.previous_admin_valid_until))
+    }
+
+    pub fn require_admin(&self, key: &Pubkey) -> Result<()> {
+        require!(self.is_admin(key)?, MoatError::Unauthorized);
+        Ok(())
+    }
+
+    /// Stays constant while epochs are disabled.
+    pub fn current_epoch(&self, now: i64) -> u64 {
+        if self.epoch_length <= 0 {
+            return self.epoch_offset;
+        }
+        let elapsed = now.saturating_sub(self.epoch_origin).max(0);
+        self.epoch_offset.saturating_add((elapsed / self.epoch_length) as u64)
+    }
+
+    pub fn is_commit_authority(&self, key: &Pubkey) -> Result<bool> {
+        Ok(*key == self.commit_authority
+            || (*key == self.previous_commit_authority
+                && Clock::get()?.slot <= self.previous_commit_authority_valid_until))
+    }
+
+    pub fn require_not_paused(&self, operation: u8) -> Result<()> {
+        require!(
+            !self.paused && self.paused_operations & operation == 0,
+            MoatError::RegistryPaused
+        );
+        Ok(())
+    }
+}
+
+#[account]
+pub struct RegistryEntry {
+    pub registry: Pubkey,
+    pub id: u32,
+    pub admin: Pubkey,
+    pub target_program: Pubkey,
+    pub kind: u8,
+    pub bump: u8,
+    pub pending_admin: Pubkey,
+    pub status: u8,
+    pub expires_at: i64,
+    pub frozen: bool,
+    pub version: u32,
+    pub delegate: Option<Pubkey>,
+    pub tags: u64,
+    pub target_kind: u8,
+    pub upgrade_authority_verified: bool,
+    pub weight: u16,
+    pub audited: bool,
+    pub active_audits: u16,
+    pub deprecated: bool,
+    pub successor: Option<Pubkey>,
+    pub parent: Option<Pubkey>,
+    pub last_updated_at: i64,
+    pub risk_score: u8,
+    pub risk_updated_at: i64,
+    /// Live attestations; expired ones and those of removed attestors count
+    /// until `lapse_attestation` drops them, and `verified` follows this.
+    pub attestation_count: u16,
+    pub verified: bool,
+    pub name: [u8; 32],
+    pub commit_count: u64,
+    pub last_commit_root: [u8; 32],
+    /// Registered through `register_named_entry`; closing it must release the name.
+    pub named: bool,
+    pub metadata_uri: String,
+}
+impl Space for RegistryEntry {
+    // metadata_uri is stored empty at init and grown by set_entry_metadata
+    const INIT_SPACE: usize = 32 + 4 + 32 + 32 + 1 + 1 + 32 + 1 + 8 + 1 + 4 + (1 + 32) + 8
+        + 1 + 1 + 2 + 1 + 2 + 1 + (1 + 32) + (1 + 32) + 8 + 1 + 8 + 2 + 1 + 32 + 8 + 32 + 1 + 4;
+}
+impl RegistryEntry {
+    pub fn new(
+        registry: Pubkey,
+        id: u32,
+        admin: Pubkey,
+        registration: &EntryRegistration,
+        expires_at: i64,
+        now: i64,
+        bump: u8,
+    ) -> Self {
+        Self {
+            registry,
+            id,
+            admin,
+            target_program: registration.target_program,
+            kind: registration.kind,
+            bump,
+            pending_admin: Pubkey::default(),
+            status: ENTRY_STATUS_ACTIVE,
+            expires_at,
+            frozen: false,
+            version: 0,
+            delegate: None,
+            tags: 0,
+            target_kind: registration.target_kind,
+            upgrade_authority_verified: false,
+            weight: 0,
+            audited: false,
+            active_audits: 0,
+            deprecated: false,
+            successor: None,
+            parent: None,
+            last_updated_at: now,
+            risk_score: 0,
+            risk_updated_at: 0,
+            attestation_count: 0,
+            verified: false,
+            name: registration.name,
+            commit_count: 0,
+            last_commit_root: [0; 32],
+            named: false,
+            metadata_uri: String::new(),
+        }
+    }
+
+    pub fn is_admin_or_delegate(&self, key: &Pubkey) -> bool {
+        self.admin == *key || self.delegate == Some(*key)
+    }
+
+    pub fn require_update_allowed(&self, min_update_interval: i64, now: i64) -> Result<()> {
+        let next_allowed = self.last_updated_at.saturating_add(min_update_interval);
+        require!(now >= next_allowed, MoatError::UpdateTooSoon);
+        Ok(())
+    }
+
+    // A threshold of zero disables attestation-based verification.
+    pub fn refresh_verified(&mut self, threshold: u16) {
+        self.verified = threshold > 0 && self.attestation_count >= threshold;
+    }
+
+    pub fn next_version(&self) -> Result<u32> {
+        Ok(self.version.checked_add(1).ok_or(MoatError::Overflow)?)
+    }
+
+    // expires_at == 0 means the entry never expires
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at != 0 && now >= self.expires_at
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct EntryRegistration {
+    pub target_program: Pubkey,
+    pub kind: u8,
+    pub target_kind: u8,
+    pub name: [u8; 32],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct CommitParams {
+    pub merkle_root: [u8; 32],
+    pub memo_hash: [u8; 32],
+    pub kind: u8,
+    /// Pipeline the root came from; 0 is unclassified, others must be defined.
+    pub commit_kind: u8,
+    pub domain: [u8; 32],
+    pub prev_root: [u8; 32],
+    /// Unix timestamp after which proofs stop verifying; 0 never expires.
+    pub valid_until: i64,
+    /// Total leaves across all roots.
+    pub leaf_count: u64,
+    /// Depth of the deepest root's tree.
+    pub depth: u8,
+    pub shard_roots: Vec<[u8; 32]>,
+    /// Leaves under each root, `merkle_root` first; empty when unsharded.
+    pub shard_leaf_counts: Vec<u64>,
+    /// One of the `HASH_ALG_*` values; applies to leaves, nodes and the memo.
+    pub hash_alg: u8,
+    /// Leaf and node encoding version; one of `ROOT_VERSION_*`.
+    pub root_version: u16,
+    /// Hash each pair in sorted order, as OpenZeppelin trees do; proofs then
+    /// carry no direction and `index` is not bound by them.
+    pub sorted_pairs: bool,
+    /// One of the `LEAF_SCHEMA_*` values; `claim` only redeems claim leaves.
+    pub leaf_schema: u16,
+    /// Mint `claim` pays out in; set for claim leaves only, which don't name one.
+    pub claim_mint: Pubkey,
+    /// Where the full leaf set can be fetched, e.g. an `ipfs://` CID.
+    pub payload_uri: String,
+    /// Proof handed to the registry's commit verifier; empty when none is set.
+    pub validity_proof: Vec<u8>,
+}
+
+/// Return data of `verify_inclusion`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct InclusionResult {
+    pub valid: bool,
+    pub commit_id: u64,
+    pub root_index: u8,
+    pub index: u64,
+}
+
+/// Return data of `verify_multi`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct MultiInclusionResult {
+    pub valid: bool,
+    pub commit_id: u64,
+    pub root_index: u8,
+    pub leaf_count: u32,
+}
+
+/// A leaf of a sorted-key commit next to the key `verify_exclusion` checks.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct KeyNeighbor {
+    pub key: [u8; 32],
+    pub index: u64,
+    pub proof: Vec<[u8; 32]>,
+}
+
+/// Return data of `verify_exclusion`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ExclusionResult {
+    pub valid: bool,
+    pub commit_id: u64,
+    pub key: [u8; 32],
+}
+
+/// Return data of `export_entry_proof`: the `EntryLeafV1` fields and the path
+/// placing them at `leaf_index` under `root`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct EntryStateProof {
+    pub registry: Pubkey,
+    pub root: [u8; 32],
+    pub version: u64,
+    pub entry_count: u64,
+    pub leaf_index: u64,
+    pub id: u32,
+    pub target_program: Pubkey,
+    pub kind: u8,
+    pub tags: u64,
+    pub expires_at: i64,
+    pub proof: Vec<[u8; 32]>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct EntryCommit {
+    pub batch_id: u64,
+    pub params: CommitParams,
+}
+
+#[account]
+pub struct EntryLookup {
+    pub entry: Pubkey,
+    pub id: u32,
+    pub bump: u8,
+}
+impl Space for EntryLookup {
+    const INIT_SPACE: usize = 32 + 4 + 1;
+}
+
+#[account]
+pub struct NameLookup {
+    pub entry: Pubkey,
+    pub id: u32,
+    pub name: String,
+    pub bump: u8,
+}
+impl Space for NameLookup {
+    const INIT_SPACE: usize = 32 + 4 + (4 + MAX_ENTRY_NAME_LEN) + 1;
+}
+
+#[account]
+pub struct KindDefinition {
+    pub registry: Pubkey,
+    pub kind: u8,
+    pub label: String,
+    pub enabled: bool,
+    pub bump: u8,
+    pub active_entries: u64,
+}
+impl Space for KindDefinition {
+    const INIT_SPACE: usize = 32 + 1 + (4 + MAX_KIND_LABEL_LEN) + 1 + 1 + 8;
+}
+
+#[account]
+pub struct CommitKindDefinition {
+    pub registry: Pubkey,
+    pub kind: u8,
+    pub label: String,
+    pub enabled: bool,
+    pub bump: u8,
+}
+impl Space for CommitKindDefinition {
+    const INIT_SPACE: usize = 32 + 1 + (4 + MAX_KIND_LABEL_LEN) + 1 + 1;
+}
+
+#[account]
+pub struct TagDefinition {
+    pub registry: Pubkey,
+    pub bit: u8,
+    pub label: String,
+    pub bump: u8,
+}
+impl Space for TagDefinition {
+    const INIT_SPACE: usize = 32 + 1 + (4 + MAX_TAG_LABEL_LEN) + 1;
+}
+
+#[account]
+pub struct AuditRecord {
+    pub entry: Pubkey,
+    pub auditor: Pubkey,
+    pub report_hash: [u8; 32],
+    pub created_at: i64,
+    pub active: bool,
+    pub bump: u8,
+}
+impl Space for AuditRecord {
+    const INIT_SPACE: usize = 32 + 32 + 32 + 8 + 1 + 1;
+}
+
+#[account]
+pub struct AttestorConfig {
+    pub registry: Pubkey,
+    pub attestor: Pubkey,
+    pub bump: u8,
+}
+impl Space for AttestorConfig {
+    const INIT_SPACE: usize = 32 + 32 + 1;
+}
+
+#[account]
+pub struct Attestation {
+    pub entry: Pubkey,
+    pub attestor: Pubkey,
+    pub claim_hash: [u8; 32],
+    pub created_at: i64,
+    pub expires_at: i64,
+    pub bump: u8,
+    pub revoked: bool,
+    pub revoked_at: i64,
+    pub revocation_reason: u8,
+}
+impl Space for Attestation {
+    const INIT_SPACE: usize = 32 + 32 + 32 + 8 + 8 + 1 + 1 + 8 + 1;
+}
+
+#[account]
+pub struct CommitAttestation {
+    pub commit: Pubkey,
+    pub attestor: Pubkey,
+    pub created_at: i64,
+    pub bump: u8,
+}
+impl Space for CommitAttestation {
+    const INIT_SPACE: usize = 32 + 32 + 8 + 1;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub enum TimelockAction {
+    ProposeAdmin { new_admin: Pubkey },
+    UpdateKind { kind: u8, label: String, enabled: bool },
+    SetTimelockDelay { delay: i64 },
+    SetDisputeBonds { challenge_bond: u64, commit_stake: u64 },
+}
+impl Space for TimelockAction {
+    // tag + largest variant (UpdateKind)
+    const INIT_SPACE: usize = 1 + 1 + (4 + MAX_KIND_LABEL_LEN) + 1;
+}
+
+#[account]
+pub struct PendingAction {
+    pub registry: Pubkey,
+    pub id: u64,
+    pub queued_by: Pubkey,
+    pub action: TimelockAction,
+    pub queued_at: i64,
+    pub eta: i64,
+    pub bump: u8,
+}
+impl Space for PendingAction {
+    const INIT_SPACE: usize = 32 + 8 + 32 + TimelockAction::INIT_SPACE + 8 + 8 + 1;
+}
+
+#[account]
+pub struct BannedTarget {
+    pub registry: Pubkey,
+    pub target: Pubkey,
+    pub banned_at: i64,
+    pub bump: u8,
+}
+impl Space for BannedTarget {
+    const INIT_SPACE: usize = 32 + 32 + 8 + 1;
+}
+
+#[account(zero_copy)]
+pub struct AdminLog {
+    pub registry: Pubkey,
+    pub total: u64,
+    pub bump: u8,
+    pub _padding: [u8; 7],
+    pub records: [AdminLogRecord; ADMIN_LOG_CAPACITY],
+}
+impl Space for AdminLog {
+    const INIT_SPACE: usize = 32 + 8 + 1 + 7 + ADMIN_LOG_CAPACITY * AdminLogRecord::INIT_SPACE;
+}
+
+impl AdminLog {
+    // Ring buffer: the oldest record is overwritten once full.
+    pub fn append(&mut self, record: AdminLogRecord) {
+        let index = (self.total % ADMIN_LOG_CAPACITY as u64) as usize;
+        self.records[index] = record;
+        self.total = self.total.saturating_add(1);
+    }
+}
+
+#[account(zero_copy)]
+pub struct RecentCommits {
+    pub registry: Pubkey,
+    pub total: u64,
+    pub bump: u8,
+    pub _padding: [u8; 7],
+    pub records: [RecentCommit; RECENT_COMMITS_CAPACITY],
+}
+impl Space for RecentCommits {
+    const INIT_SPACE: usize = 32 + 8 + 1 + 7 + RECENT_COMMITS_CAPACITY * RecentCommit::INIT_SPACE;
+}
+
+impl RecentCommits {
+    pub fn append(&mut self, record: RecentCommit) {
+        let index = (self.total % RECENT_COMMITS_CAPACITY as u64) as usize;
+        self.records[index] = record;
+        self.total = self.total.saturating_add(1);
+    }
+}
+
+#[zero_copy]
+pub struct RecentCommit {
+    pub root: [u8; 32],
+    pub entry: Pubkey,
+    pub id: u64,
+    pub timestamp: i64,
+}
+impl Space for RecentCommit {
+    const INIT_SPACE: usize = 32 + 32 + 8 + 8;
+}
+
+#[account(zero_copy)]
+pub struct CommitLog {
+    pub registry: Pubkey,
+    pub previous: Pubkey,
+    pub len: u64,
+    pub segment: u32,
+    pub _padding: [u8; 4],
+    pub records: [CommitLogRecord; COMMIT_LOG_CAPACITY],
+}
+impl Space for CommitLog {
+    const INIT_SPACE: usize =
+        32 + 32 + 8 + 4 + 4 + COMMIT_LOG_CAPACITY * CommitLogRecord::INIT_SPACE;
+}
+
+impl CommitLog {
+    // Append-only: a full segment rejects further records.
+    pub fn append(&mut self, record: CommitLogRecord) -> Result<()> {
+        let index = self.len as usize;
+        require!(index < COMMIT_LOG_CAPACITY, MoatError::CommitLogFull);
+        self.records[index] = record;
+        self.len += 1;
+        Ok(())
+    }
+}
+
+#[zero_copy]
+pub struct CommitLogRecord {
+    pub id: u64,
+    pub seq: u64,
+    pub created_at: i64,
+    pub created_at_slot: u64,
+    pub entry: Pubkey,
+    pub creator: Pubkey,
+    pub merkle_root: [u8; 32],
+    pub memo_hash: [u8; 32],
+}
+impl Space for CommitLogRecord {
+    const INIT_SPACE: usize = 8 + 8 + 8 + 8 + 32 + 32 + 32 + 32;
+}
+
+#[zero_copy]
+pub struct AdminLogRecord {
+    pub timestamp: i64,
+    pub actor: Pubkey,
+    pub payload_hash: [u8; 32],
+    pub action: u8,
+    pub _padding: [u8; 7],
+}
+impl Space for AdminLogRecord {
+    const INIT_SPACE: usize = 8 + 32 + 32 + 1 + 7;
+}
+
+#[account]
+pub struct Role {
+    pub registry: Pubkey,
+    pub holder: Pubkey,
+    pub capabilities: u8,
+    pub bump: u8,
+    pub last_action_slot: u64,
+}
+impl Space for Role {
+    const INIT_SPACE: usize = 32 + 32 + 1 + 1 + 8;
+}
+
+#[account]
+pub struct Session {
+    pub registry: Pubkey,
+    pub session_key: Pubkey,
+    pub capabilities: u8,
+    pub expires_at_slot: u64,
+    pub bump: u8,
+    pub last_action_slot: u64,
+}
+impl Space for Session {
+    const INIT_SPACE: usize = 32 + 32 + 1 + 8 + 1 + 8;
+}
+
+impl Session {
+    pub fn grants(&self, capability: u8, slot: u64) -> bool {
+        self.capabilities & capability == capability && slot <= self.expires_at_slot
+    }
+}
+
+#[account]
+pub struct AuthorityNonce {
+    pub registry: Pubkey,
+    pub authority: Pubkey,
+    pub nonce: u64,
+    pub bump: u8,
+    /// Set by `consume_nonce`, cleared by the session or delegate action it admits.
+    pub armed: bool,
+}
+impl Space for AuthorityNonce {
+    const INIT_SPACE: usize = 32 + 32 + 8 + 1 + 1;
+}
+
+#[account]
+pub struct MultisigConfig {
+    pub registry: Pubkey,
+    pub owners: Vec<Pubkey>,
+    pub threshold: u8,
+    pub owner_set_seqno: u32,
+    pub next_proposal_id: u64,
+    pub bump: u8,
+    pub signer_bump: u8,
+}
+impl Space for MultisigConfig {
+    const INIT_SPACE: usize = 32 + (4 + 32 * MAX_MULTISIG_OWNERS) + 1 + 4 + 8 + 1 + 1;
+}
+
+impl MultisigConfig {
+    pub fn owner_index(&self, key: &Pubkey) -> Result<usize> {
+        self.owners
+            .iter()
+            .position(|owner| owner == key)
+            .ok_or_else(|| error!(MoatError::Unauthorized))
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct MultisigAccountMeta {
+    pub pubkey: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+impl Space for MultisigAccountMeta {
+    const INIT_SPACE: usize = 32 + 1 + 1;
+}
+
+#[account]
+pub struct MultisigProposal {
+    pub multisig: Pubkey,
+    pub id: u64,
+    pub proposer: Pubkey,
+    pub program_id: Pubkey,
+    pub accounts: Vec<MultisigAccountMeta>,
+    pub data: Vec<u8>,
+    pub approvals: u16,
+    pub owner_set_seqno: u32,
+    pub executed: bool,
+    pub created_at: i64,
+    pub bump: u8,
+}
+impl Space for MultisigProposal {
+    // accounts and data are sized per proposal on top of this
+    const INIT_SPACE: usize = 32 + 8 + 32 + 32 + 4 + 4 + 2 + 4 + 1 + 8 + 1;
+}
+
+#[account]
+pub struct BatchCommit {
+    pub creator: Pubkey,
+    pub batch_id: u64,
+    pub kind: u8,
+    pub commit_kind: u8,
+    pub root_version: u16,
+    pub excluded_leaves: u32,
+    pub consumer: Pubkey,
+    pub sorted_pairs: bool,
+    pub leaf_schema: u16,
+    pub claim_mint: Pubkey,
+    pub attestation_count: u16,
+    pub attestation_quorum: u16,
+    pub bump: u8,
+    pub acl_members: u8,
+    pub merkle_root: [u8; 32],
+    pub memo_hash: [u8; 32],
+    pub created_at: i64,
+    pub entry: Pubkey,
+    pub seq: u64,
+    pub prev_root: [u8; 32],
+    pub id: u64,
+    pub revoked: bool,
+    pub revoke_reason: u8,
+    pub revoked_at: i64,
+    pub status: u8,
+    pub dispute_ends_at: i64,
+    pub finalized_at: i64,
+    pub stake: u64,
+    pub challenged: bool,
+    pub valid_until: i64,
+    pub leaf_count: u64,
+    pub depth: u8,
+    pub shard_roots: Vec<[u8; 32]>,
+    pub shard_leaf_counts: Vec<u64>,
+    pub hash_alg: u8,
+    pub created_at_slot: u64,
+    pub supersedes: Option<Pubkey>,
+    pub superseded_by: Option<Pubkey>,
+    pub payload_uri: String,
+    pub epoch: u64,
+    pub memo: Vec<u8>,
+}
+impl Space for BatchCommit {
+    // memo is stored empty at init and grown by commit_batch_with_memo
+    const INIT_SPACE: usize = 32 + 8 + 1 + 1 + 2 + 4 + 32 + 1 + 2 + 32 + 2 + 2 + 1 + 1 + 32 + 32 + 8
+        + 32 + 8 + 32 + 8 + 1 + 1 + 8 + 1 + 8 + 8 + 8 + 1 + 8 + 8 + 1 + 4 + 32 * MAX_SHARD_ROOTS
+        + 4 + 8 * (1 + MAX_SHARD_ROOTS) + 1 + 8 + (1 + 32)
+        + (1 + 32) + 4 + MAX_PAYLOAD_URI_LEN + 8 + 4;
+}
+impl BatchCommit {
+    /// Index 0 is `merkle_root`; later indexes address `shard_roots`.
+    pub fn root(&self, index: u8) -> Result<[u8; 32]> {
+        match index {
+            0 => Ok(self.merkle_root),
+            i => self
+                .shard_roots
+                .get(usize::from(i) - 1)
+                .copied()
+                .ok_or(error!(MoatError::InvalidRootIndex)),
+        }
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.valid_until != 0 && now >= self.valid_until
+    }
+
+    /// Final once finalized, or immediately when it was made without a
+    /// dispute window.
+    pub fn is_final(&self) -> bool {
+        self.status == COMMIT_STATUS_FINALIZED || self.dispute_ends_at <= self.created_at
+    }
+
+    /// Bit positions a claim bitmap needs. Sharded commits reserve a full
+    /// `2^depth` range per root.
+    pub fn claim_slots(&self) -> u64 {
+        if self.shard_roots.is_empty() {
+            self.leaf_count
+        } else {
+            ((1 + self.shard_roots.len()) as u64) << self.depth
+        }
+    }
+
+    /// Leaves under the root at `root_index`; 0 past the last root.
+    pub fn shard_leaf_count(&self, root_index: u8) -> u64 {
+        if self.shard_roots.is_empty() {
+            if root_index == 0 {
+                self.leaf_count
+            } else {
+                0
+            }
+        } else {
+            self.shard_leaf_counts
+                .get(usize::from(root_index))
+                .copied()
+                .unwrap_or(0)
+        }
+    }
+
+    pub fn claim_position(&self, root_index: u8, index: u64) -> u64 {
+        if self.shard_roots.is_empty() {
+            index
+        } else {
+            (u64::from(root_index) << self.depth) | index
+        }
+    }
+
+    /// Checks every proof against this commit's root should pass first.
+    pub fn require_verifiable(&self, now: i64) -> Result<()> {
+        require!(!self.revoked, MoatError::CommitRevoked);
+        require!(
+            self.superseded_by.is_none(),
+            MoatError::CommitAlreadySuperseded
+        );
+        require!(!self.is_expired(now), MoatError::CommitExpired);
+        require!(
+            self.attestation_count >= self.attestation_quorum,
+            MoatError::CommitQuorumNotMet
+        );
+        self.tree()?;
+        Ok(())
+    }
+
+    pub fn tree(&self) -> Result<moat_merkle::Tree> {
+        merkle_tree(self.root_version, self.sorted_pairs)
+    }
+}
+
+#[account]
+pub struct IncrementalTree {
+    pub entry: Pubkey,
+    /// Most leaves the tree takes, as a power of two.
+    pub depth: u8,
+    pub hash_alg: u8,
+    pub root_version: u16,
+    pub leaf_count: u64,
+    /// Left siblings still waiting for a right-hand partner, one per level.
+    pub frontier: [[u8; 32]; MAX_MERKLE_DEPTH as usize],
+    pub root: [u8; 32],
+    pub bump: u8,
+}
+impl Space for IncrementalTree {
+    const INIT_SPACE: usize = 32 + 1 + 1 + 2 + 8 + 32 * MAX_MERKLE_DEPTH as usize + 32 + 1;
+}
+
+impl IncrementalTree {
+    pub fn append(&mut self, leaf: [u8; 32]) -> Result<()> {
+        require!(self.leaf_count < 1u64 << self.depth, MoatError::TreeFull);
+
+        let hasher = CommitHasher::new(self.hash_alg)?;
+        let tree = merkle_tree(self.root_version, false)?;
+        require!(
+            moat_merkle::append_leaf(&hasher, tree, &mut self.frontier, self.leaf_count, &leaf),
+            MoatError::TreeFull
+        );
+        self.leaf_count += 1;
+        (self.root, _) = moat_merkle::frontier_root(&hasher, tree, &self.frontier, self.leaf_count)
+            .ok_or(MoatError::TreeFull)?;
+        Ok(())
+    }
+}
+
+#[account]
+pub struct CompressedTree {
+    pub entry: Pubkey,
+    pub merkle_tree: Pubkey,
+    pub leaf_count: u64,
+    pub bump: u8,
+}
+impl Space for CompressedTree {
+    const INIT_SPACE: usize = 32 + 32 + 8 + 1;
+}
+
+#[account]
+pub struct CommitCosigners {
+    pub registry: Pubkey,
+    pub threshold: u8,
+    pub signers: Vec<Pubkey>,
+    pub bump: u8,
+}
+impl Space for CommitCosigners {
+    const INIT_SPACE: usize = 32 + 1 + 4 + 32 * MAX_COMMIT_COSIGNERS + 1;
+}
+
+#[account]
+pub struct EpochSummary {
+    pub registry: Pubkey,
+    pub epoch: u64,
+    pub commit_count: u64,
+    pub leaf_count: u64,
+    pub first_commit_id: u64,
+    pub last_commit_id: u64,
+    pub bump: u8,
+}
+impl Space for EpochSummary {
+    const INIT_SPACE: usize = 32 + 8 + 8 + 8 + 8 + 8 + 1;
+}
+
+/// Ids of the commits an aggregate commit's leaves are the roots of, in leaf
+/// order.
+#[account]
+pub struct CommitAggregate {
+    pub commit: Pubkey,
+    pub member_ids: Vec<u64>,
+    pub bump: u8,
+}
+impl Space for CommitAggregate {
+    const INIT_SPACE: usize = 32 + 4 + 8 * MAX_AGGREGATE_MEMBERS + 1;
+}
+
+#[account]
+pub struct RegistryRoot {
+    pub registry: Pubkey,
+    /// Last published root over `EntryLeafV1` leaves, in entry id order.
+    pub root: [u8; 32],
+    pub entry_count: u64,
+    pub depth: u8,
+    pub version: u64,
+    pub published_at: i64,
+    /// Next entry id the build in progress accepts.
+    pub cursor: u64,
+    pub pending_count: u64,
+    pub frontier: [[u8; 32]; MAX_MERKLE_DEPTH as usize],
+    pub bump: u8,
+}
+impl Space for RegistryRoot {
+    const INIT_SPACE: usize = 32 + 32 + 8 + 1 + 8 + 8 + 8 + 8 + 32 * MAX_MERKLE_DEPTH as usize + 1;
+}
+
+/// Claim positions voided by `exclude_leaves`.
+#[account]
+pub struct ExclusionList {
+    pub commit: Pubkey,
+    pub bump: u8,
+    pub positions: Vec<u64>,
+}
+impl Space for ExclusionList {
+    const INIT_SPACE: usize = 32 + 1 + 4 + 8 * MAX_EXCLUDED_LEAVES;
+}
+
+/// Who may consume or claim against a commit; see `set_commit_acl`.
+#[account]
+pub struct CommitAcl {
+    pub commit: Pubkey,
+    pub members: Vec<Pubkey>,
+    pub bump: u8,
+}
+impl Space for CommitAcl {
+    const INIT_SPACE: usize = 32 + 4 + 32 * MAX_COMMIT_ACL_MEMBERS + 1;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct CommitAnnotation {
+    pub code: u16,
+    pub hash: [u8; 32],
+    pub author: Pubkey,
+    pub created_at: i64,
+}
+impl Space for CommitAnnotation {
+    const INIT_SPACE: usize = 2 + 32 + 32 + 8;
+}
+
+/// Notes `annotate_commit` appended to a commit, oldest first.
+#[account]
+pub struct CommitAnnotations {
+    pub commit: Pubkey,
+    pub bump: u8,
+    pub notes: Vec<CommitAnnotation>,
+}
+impl Space for CommitAnnotations {
+    const INIT_SPACE: usize = 32 + 1 + 4 + CommitAnnotation::INIT_SPACE * MAX_COMMIT_ANNOTATIONS;
+}
+
+/// What `prune_commit` leaves behind.
+#[account]
+pub struct CommitTombstone {
+    pub id: u64,
+    pub record_hash: [u8; 32],
+}
+impl Space for CommitTombstone {
+    const INIT_SPACE: usize = 8 + 32;
+}
+
+#[account]
+pub struct ClaimBitmap {
+    pub commit: Pubkey,
+    pub consumed: u64,
+    pub bump: u8,
+    pub bits: Vec<u8>,
+}
+impl Space for ClaimBitmap {
+    // bits is sized to the commit's claim slots at init
+    const INIT_SPACE: usize = 32 + 8 + 1 + 4;
+}
+
+impl ClaimBitmap {
+    pub fn consume(&mut self, position: u64) -> Result<()> {
+        let byte = self
+            .bits
+            .get_mut((position / 8) as usize)
+            .ok_or(MoatError::InvalidLeafIndex)?;
+        let mask = 1u8 << (position % 8);
+        require!(*byte & mask == 0, MoatError::LeafAlreadyConsumed);
+        *byte |= mask;
+        self.consumed = self.consumed.saturating_add(1);
+        Ok(())
+    }
+}
+
+#[account]
+pub struct Challenge {
+    pub commit: Pubkey,
+    pub challenger: Pubkey,
+    pub bond: u64,
+    pub claim_hash: [u8; 32],
+    pub created_at: i64,
+    pub bump: u8,
+}
+impl Space for Challenge {
+    const INIT_SPACE: usize = 32 + 32 + 8 + 32 + 8 + 1;
+}
+
+fn process_commit_batch(
+    accounts: &mut CommitBatch,
+    bumps: &CommitBatchBumps,
+    batch_id: u64,
+    params: CommitParams,
+) -> Result<()> {
+    let creator = accounts.creator.key();
+    let clock = Clock::get()?;
+
+    authorize_commit(
+        &mut accounts.state,
+        &creator,
+        accounts.role.as_deref_mut(),
+        accounts.session.as_deref_mut(),
+        accounts.nonce.as_deref_mut(),
+        clock.slot,
+    )?;
+    check_commit_params(
+        &accounts.state,
+        &params,
+        accounts.commit_kind.as_deref(),
+        clock.unix_timestamp,
+    )?;
+    require_cosignatures(
+        &accounts.state,
+        accounts.cosigners.as_deref(),
+        accounts.instructions.as_ref(),
+        &accounts.entry.key(),
+        accounts.entry.commit_count,
+        &params.merkle_root,
+    )?;
+    require_root_attestation(
+        &accounts.state,
+        accounts.instructions.as_ref(),
+        &accounts.entry.key(),
+        accounts.entry.commit_count,
+        &params.merkle_root,
+    )?;
+    verify_commit_proof(
+        &accounts.state,
+        accounts.commit_verifier.as_ref(),
+        &accounts.entry.key(),
+        accounts.entry.commit_count,
+        &params,
+    )?;
+    write_commit(
+        &mut accounts.state,
+        &mut accounts.entry,
+        &mut accounts.batch,
+        creator,
+        batch_id,
+        params,
+        &clock,
+    )?;
+    accounts.batch.bump = bumps.batch;
+
+    let stake = accounts.state.commit_stake;
+    if stake > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: accounts.creator.to_account_info(),
+                    to: accounts.batch.to_account_info(),
+                },
+            ),
+            stake,
+        )?;
+    }
+
+    record_commit(
+        &accounts.state,
+        &accounts.batch,
+        accounts.recent_commits.as_ref(),
+        accounts.epoch_summary.as_deref_mut(),
+        bumps.epoch_summary.unwrap_or_default(),
+        accounts.commit_log.as_ref(),
+    )
+}
+
+// Pause, cooldown and commit-authority gate; runs once per instruction.
+fn authorize_commit(
+    state: &mut RegistryState,
+    creator: &Pubkey,
+    role: Option<&mut Role>,
+    session: Option<&mut Session>,
+    nonce: Option<&mut AuthorityNonce>,
+    slot: u64,
+) -> Result<()> {
+    state.require_not_paused(PAUSE_COMMIT)?;
+    if state.commit_authority == Pubkey::default() {
+        return Ok(());
+    }
+    require!(
+        slot.saturating_sub(state.last_commit_slot) >= state.min_commit_interval_slots,
+        MoatError::CommitTooSoon
+    );
+    state.last_commit_slot = slot;
+    if !state.is_commit_authority(creator)? {
+        require!(
+            use_grant(state, role, session, nonce, ROLE_COMMIT)?,
+            MoatError::Unauthorized
+        );
+    }
+    Ok(())
+}
+
+fn check_commit_params(
+    state: &Account<RegistryState>,
+    params: &CommitParams,
+    commit_kind: Option<&CommitKindDefinition>,
+    now: i64,
+) -> Result<()> {
+    require!(
+        params.domain == commitment_domain(&state.key()),
+        MoatError::InvalidCommitmentDomain
+    );
+    require!(
+        params.valid_until == 0 || params.valid_until > now,
+        MoatError::InvalidExpiry
+    );
+    require!(
+        params.shard_roots.len() <= MAX_SHARD_ROOTS,
+        MoatError::TooManyShardRoots
+    );
+    validate_tree_shape(
+        params.leaf_count,
+        params.depth,
+        params.shard_roots.len(),
+        &params.shard_leaf_counts,
+    )?;
+    require!(
+        params.hash_alg == HASH_ALG_SHA256 || params.hash_alg == HASH_ALG_KECCAK256,
+        MoatError::UnsupportedHashAlg
+    );
+    require!(params.root_version != 0, MoatError::UnsupportedRootVersion);
+    require!(
+        (params.leaf_schema == LEAF_SCHEMA_CLAIM_V1) == (params.claim_mint != Pubkey::default()),
+        MoatError::InvalidClaimMint
+    );
+    require!(
+        params.payload_uri.len() <= MAX_PAYLOAD_URI_LEN,
+        MoatError::PayloadUriTooLong
+    );
+    if params.commit_kind != 0 {
+        require!(
+            commit_kind.is_some_and(|d| d.kind == params.commit_kind && d.enabled),
+            MoatError::InvalidCommitKind
+        );
+    }
+    Ok(())
+}
+
+// What co-signers and root attestors sign for the commit at `seq` on `entry`.
+fn root_message(state: &Pubkey, entry: &Pubkey, merkle_root: &[u8; 32], seq: u64) -> Vec<u8> {
+    [state.as_ref(), entry.as_ref(), merkle_root, &seq.to_le_bytes()].concat()
+}
+
+fn require_cosignatures(
+    state: &Account<RegistryState>,
+    cosigners: Option<&CommitCosigners>,
+    instructions: Option<&UncheckedAccount>,
+    entry: &Pubkey,
+    seq: u64,
+    merkle_root: &[u8; 32],
+) -> Result<()> {
+    if state.commit_cosigner_threshold == 0 {
+        return Ok(());
+    }
+    let (Some(cosigners), Some(instructions)) = (cosigners, instructions) else {
+        return err!(MoatError::NotEnoughCosignatures);
+    };
+    let message = root_message(&state.key(), entry, merkle_root, seq);
+    require!(
+        count_cosignatures(instructions, &cosigners.signers, &message)?
+            >= usize::from(cosigners.threshold),
+        MoatError::NotEnoughCosignatures
+    );
+    Ok(())
+}
+
+fn require_root_attestation(
+    state: &Account<RegistryState>,
+    instructions: Option<&UncheckedAccount>,
+    entry: &Pubkey,
+    seq: u64,
+    merkle_root: &[u8; 32],
+) -> Result<()> {
+    let ed25519 = state.root_attestor != Pubkey::default();
+    let secp256k1 = state.root_attestor_eth != [0; 20];
+    if !ed25519 && !secp256k1 {
+        return Ok(());
+    }
+    let instructions = instructions.ok_or(MoatError::RootAttestationMissing)?;
+    let message = root_message(&state.key(), entry, merkle_root, seq);
+    require!(
+        !ed25519 || count_cosignatures(instructions, &[state.root_attestor], &message)? == 1,
+        MoatError::RootAttestationMissing
+    );
+    require!(
+        !secp256k1 || has_eth_signature(instructions, &state.root_attestor_eth, &message)?,
+        MoatError::RootAttestationMissing
+    );
+    Ok(())
+}
+
+// A verifier that rejects the proof fails the CPI, and with it the commit.
+fn verify_commit_proof(
+    state: &Account<RegistryState>,
+    verifier: Option<&UncheckedAccount>,
+    entry: &Pubkey,
+    seq: u64,
+    params: &CommitParams,
+) -> Result<()> {
+    if state.commit_verifier == Pubkey::default() {
+        return Ok(());
+    }
+    let verifier = verifier.ok_or(MoatError::CommitVerifierRequired)?;
+
+    let mut data = VERIFIER_VERIFY_BATCH.to_vec();
+    data.extend_from_slice(state.key().as_ref());
+    data.extend_from_slice(entry.as_ref());
+    data.extend_from_slice(&seq.to_le_bytes());
+    data.extend_from_slice(&params.merkle_root);
+    data.extend_from_slice(&params.leaf_count.to_le_bytes());
+    params.validity_proof.serialize(&mut data)?;
+
+    invoke(
+        &Instruction {
+            program_id: verifier.key(),
+            accounts: vec![],
+            data,
+        },
+        &[verifier.to_account_info()],
+    )?;
+    Ok(())
+}
+
+// Fills a freshly created commit and advances the entry and registry counters.
+fn write_commit(
+    state: &mut RegistryState,
+    entry: &mut Account<RegistryEntry>,
+    commit: &mut BatchCommit,
+    creator: Pubkey,
+    batch_id: u64,
+    params: CommitParams,
+    clock: &Clock,
+) -> Result<()> {
+    let now = clock.unix_timestamp;
+
+    require!(entry.status == ENTRY_STATUS_ACTIVE, MoatError::EntryNotActive);
+    require!(!entry.frozen, MoatError::EntryFrozen);
+    // Each root links to the entry's previous one, so indexers can't drop or reorder.
+    require!(
+        params.prev_root == entry.last_commit_root,
+        MoatError::InvalidPrevRoot
+    );
+
+    commit.entry = entry.key();
+    commit.seq = entry.commit_count;
+    entry.commit_count = entry
+        .commit_count
+        .checked_add(1)
+        .ok_or(MoatError::Overflow)?;
+    entry.last_commit_root = params.merkle_root;
+
+    commit.id = state.next_commit_id;
+    state.next_commit_id = state
+        .next_commit_id
+        .checked_add(1)
+        .ok_or(MoatError::Overflow)?;
+
+    commit.creator = creator;
+    commit.batch_id = batch_id;
+    commit.kind = params.kind;
+    commit.commit_kind = params.commit_kind;
+    commit.merkle_root = params.merkle_root;
+    commit.memo_hash = params.memo_hash;
+    commit.prev_root = params.prev_root;
+    commit.valid_until = params.valid_until;
+    commit.leaf_count = params.leaf_count;
+    commit.depth = params.depth;
+    commit.shard_roots = params.shard_roots;
+    commit.shard_leaf_counts = params.shard_leaf_counts;
+    commit.hash_alg = params.hash_alg;
+    commit.root_version = params.root_version;
+    commit.sorted_pairs = params.sorted_pairs;
+    commit.leaf_schema = params.leaf_schema;
+    commit.claim_mint = params.claim_mint;
+    commit.attestation_quorum = state.commit_attestation_quorum;
+    commit.payload_uri = params.payload_uri;
+    commit.created_at = now;
+    commit.created_at_slot = clock.slot;
+    commit.epoch = state.current_epoch(now);
+    commit.status = COMMIT_STATUS_PENDING;
+    commit.dispute_ends_at = commit
+        .created_at
+        .checked_add(state.dispute_window)
+        .ok_or(MoatError::Overflow)?;
+
+    commit.stake = state.commit_stake;
+
+    Ok(())
+}
+
+// Appends a commit to whichever of the recent buffer, epoch summary and commit
+// log the registry has enabled.
+fn record_commit(
+    state: &Account<RegistryState>,
+    commit: &Account<BatchCommit>,
+    recent_commits: Option<&AccountLoader<RecentCommits>>,
+    epoch_summary: Option<&mut EpochSummary>,
+    epoch_summary_bump: u8,
+    commit_log: Option<&AccountLoader<CommitLog>>,
+) -> Result<()> {
+    match recent_commits {
+        Some(recent) => recent.load_mut()?.append(RecentCommit {
+            root: commit.merkle_root,
+            entry: commit.entry,
+            id: commit.id,
+            timestamp: commit.created_at,
+        }),
+        None => require!(!state.recent_commits_enabled, MoatError::RecentCommitsRequired),
+    }
+
+    match epoch_summary {
+        Some(summary) => {
+            if summary.commit_count == 0 {
+                summary.registry = state.key();
+                summary.epoch = commit.epoch;
+                summary.first_commit_id = commit.id;
+                summary.bump = epoch_summary_bump;
+            }
+            summary.commit_count = summary
+                .commit_count
+                .checked_add(1)
+                .ok_or(MoatError::Overflow)?;
+            summary.leaf_count = summary.leaf_count.saturating_add(commit.leaf_count);
+            summary.last_commit_id = commit.id;
+        }
+        None => require!(state.epoch_length == 0, MoatError::EpochSummaryRequired),
+    }
+
+    match commit_log {
+        Some(log) => log.load_mut()?.append(CommitLogRecord {
+            id: commit.id,
+            seq: commit.seq,
+            created_at: commit.created_at,
+            created_at_slot: commit.created_at_slot,
+            entry: commit.entry,
+            creator: commit.creator,
+            merkle_root: commit.merkle_root,
+            memo_hash: commit.memo_hash,
+        })?,
+        None => require!(
+            state.commit_log == Pubkey::default(),
+            MoatError::CommitLogRequired
+        ),
+    }
+
+    Ok(())
+}
+
+fn process_register_entry(
+    accounts: &mut RegisterEntry,
+    bumps: &RegisterEntryBumps,
+    registration: EntryRegistration,
+    expires_at: i64,
+    allowlisted: bool,
+) -> Result<()> {
+    let state = &mut accounts.state;
+    let now = Clock::get()?.unix_timestamp;
+
+    let is_registrar = allowlisted
+        || has_capability(
+            state,
+            &accounts.authority.key(),
+            accounts.role.as_deref_mut(),
+            accounts.session.as_deref_mut(),
+            accounts.nonce.as_deref_mut(),
+            ROLE_REGISTER,
+        )?;
+    require!(is_registrar || state.open_registration, MoatError::Unauthorized);
+    state.require_not_paused(PAUSE_REGISTER)?;
+    require_not_banned(&accounts.ban)?;
+    require_kind_enabled(&accounts.kind_definition)?;
+    increment_active_entries(&accounts.kind_definition)?;
+    require!(expires_at == 0 || expires_at > now, MoatError::InvalidExpiry);
+    require!(registration.target_kind <= TARGET_KIND_PDA, MoatError::InvalidTargetKind);
+    validate_display_name(&registration.name)?;
+    if let Some(target) = &accounts.target {
+        validate_target(registration.target_kind, target)?;
+    }
+
+    let upgrade_authority_verified = match (&accounts.program_data, &accounts.upgrade_authority) {
+        (Some(program_data), Some(upgrade_authority)) => {
+            require!(
+                registration.target_kind == TARGET_KIND_PROGRAM,
+                MoatError::InvalidTargetKind
+            );
+            require!(
+                program_data.upgrade_authority_address == Some(upgrade_authority.key()),
+                MoatError::UpgradeAuthorityMismatch
+            );
+            true
+        }
+        (None, None) => false,
+        _ => return err!(MoatError::UpgradeAuthorityMismatch),
+    };
+    require!(
+        upgrade_authority_verified
+            || !state.require_upgrade_authority
+            || registration.target_kind != TARGET_KIND_PROGRAM,
+        MoatError::UpgradeAuthorityRequired
+    );
+    // Open registration still requires proof of control over the target.
+    require!(
+        is_registrar || upgrade_authority_verified,
+        MoatError::UpgradeAuthorityRequired
+    );
+
+    let entry_id =
+        u32::try_from(state.next_id).map_err(|_| MoatError::NextIdOverflow)?;
+    let entry = &mut accounts.entry;
+    entry.set_inner(RegistryEntry::new(
+        state.key(),
+        entry_id,
+        accounts.authority.key(),
+        &registration,
+        expires_at,
+        now,
+        bumps.entry,
+    ));
+    entry.upgrade_authority_verified = upgrade_authority_verified;
+
+    let lookup = &mut accounts.lookup;
+    if lookup.entry != Pubkey::default() {
+        return err!(MoatError::DuplicateTarget);
+    }
+    lookup.entry = entry.key();
+    lookup.id = entry_id;
+    lookup.bump = bumps.lookup;
+
+    state.next_id = state
+        .next_id
+        .checked_add(1)
+        .ok_or(MoatError::Overflow)?;
+
+    Ok(())
+}
+
+// Names are stored in canonical form: lowercase ascii, digits and inner hyphens.
+fn validate_entry_name(name: &str) -> Result<()> {
+    let bytes = name.as_bytes();
+    require!(
+        !bytes.is_empty() && bytes.len() <= MAX_ENTRY_NAME_LEN,
+        MoatError::InvalidEntryName
+    );
+    require!(
+        bytes
+            .iter()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || *b == b'-'),
+        MoatError::InvalidEntryName
+    );
+    require!(
+        bytes[0] != b'-' && bytes[bytes.len() - 1] != b'-',
+        MoatError::InvalidEntryName
+    );
+    Ok(())
+}
+
+// Display names are zero-padded UTF-8.
+fn validate_display_name(name: &[u8; 32]) -> Result<()> {
+    let len = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+    require!(
+        name[len..].iter().all(|b| *b == 0),
+        MoatError::InvalidEntryName
+    );
+    require!(
+        std::str::from_utf8(&name[..len]).is_ok(),
+        MoatError::InvalidEntryName
+    );
+    Ok(())
+}
+
+// Appends to the admin log when one is passed. The payload is hashed; callers
+// pass the instruction arguments.
+fn record_admin_action(
+    state: &RegistryState,
+    admin_log: Option<&AccountLoader<AdminLog>>,
+    action: u8,
+    actor: Pubkey,
+    payload: &[&[u8]],
+) -> Result<()> {
+    let Some(admin_log) = admin_log else {
+        require!(!state.admin_log_enabled, MoatError::AdminLogRequired);
+        return Ok(());
+    };
+    admin_log.load_mut()?.append(AdminLogRecord {
+        timestamp: Clock::get()?.unix_timestamp,
+        actor,
+        payload_hash: hashv(payload).to_bytes(),
+        action,
+        _padding: [0; 7],
+    });
+    Ok(())
+}
+
+// The registry admin holds every capability; anyone else needs a matching Role PDA.
+fn require_capability(
+    state: &RegistryState,
+    authority: &Pubkey,
+    role: Option<&mut Role>,
+    session: Option<&mut Session>,
+    nonce: Option<&mut AuthorityNonce>,
+    capability: u8,
+) -> Result<()> {
+    require!(
+        has_capability(state, authority, role, session, nonce, capability)?,
+        MoatError::Unauthorized
+    );
+    Ok(())
+}
+
+fn has_capability(
+    state: &RegistryState,
+    authority: &Pubkey,
+    role: Option<&mut Role>,
+    session: Option<&mut Session>,
+    nonce: Option<&mut AuthorityNonce>,
+    capability: u8,
+) -> Result<bool> {
+    if state.is_admin(authority)? {
+        return Ok(true);
+    }
+    use_grant(state, role, session, nonce, capability)
+}
+
+// Checks a role or session grant and charges it against the action slot gap. A
+// session grant also spends the key's consumed nonce.
+fn use_grant(
+    state: &RegistryState,
+    role: Option<&mut Role>,
+    session: Option<&mut Session>,
+    nonce: Option<&mut AuthorityNonce>,
+    capability: u8,
+) -> Result<bool> {
+    let slot = Clock::get()?.slot;
+    let last_action_slot = match (role, session) {
+        (Some(role), _) if role.capabilities & capability == capability => {
+            &mut role.last_action_slot
+        }
+        (_, Some(session)) if session.grants(capability, slot) => {
+            spend_nonce(nonce)?;
+            &mut session.last_action_slot
+        }
+        _ => return Ok(false),
+    };
+    require!(
+        slot.saturating_sub(*last_action_slot) >= state.min_action_slot_gap,
+        MoatError::RateLimited
+    );
+    *last_action_slot = slot;
+    Ok(true)
+}
+
+// Session keys and delegates act only on a nonce consumed for the action, so a
+// replayed payload fails once its `consume_nonce` has run.
+fn spend_nonce(nonce: Option<&mut AuthorityNonce>) -> Result<()> {
+    let nonce = nonce.ok_or(MoatError::NonceNotConsumed)?;
+    require!(nonce.armed, MoatError::NonceNotConsumed);
+    nonce.armed = false;
+    Ok(())
+}
+
+fn require_admin_or_delegate(
+    entry: &RegistryEntry,
+    authority: &Pubkey,
+    nonce: Option<&mut AuthorityNonce>,
+) -> Result<()> {
+    require!(entry.is_admin_or_delegate(authority), MoatError::Unauthorized);
+    if entry.admin != *authority {
+        spend_nonce(nonce)?;
+    }
+    Ok(())
+}
+
+/// Prefix mixed into every leaf and memo preimage committed to `registry`, so roots
+/// built for another program, registry or scheme version never verify here.
+pub fn commitment_domain(registry: &Pubkey) -> [u8; 32] {
+    moat_merkle::commitment_domain(
+        &CommitHasher(HASH_ALG_SHA256),
+        &crate::ID.to_bytes(),
+        &registry.to_bytes(),
+    )
+}
+
+// Odd levels carry their last node up, so a single tree has depth ceil(log2(leaf_count)).
+// Sharded commits can only be bounded: every shard fits within `depth`.
+fn validate_tree_shape(
+    leaf_count: u64,
+    depth: u8,
+    shard_roots: usize,
+    shard_leaf_counts: &[u64],
+) -> Result<()> {
+    require!(
+        leaf_count > 0
+            && depth <= MAX_MERKLE_DEPTH
+            && leaf_count <= ((1 + shard_roots) as u64) << depth,
+        MoatError::InvalidTreeShape
+    );
+    if shard_roots == 0 {
+        require!(
+            shard_leaf_counts.is_empty()
+                && leaf_count.next_power_of_two().trailing_zeros() == u32::from(depth),
+            MoatError::InvalidTreeShape
+        );
+    } else {
+        // Each shard's count bounds its indexes: past it, an odd last leaf
+        // paired with itself would verify a second time.
+        let total = shard_leaf_counts
+            .iter()
+            .try_fold(0u64, |total, &count| total.checked_add(count));
+        require!(
+            shard_leaf_counts.len() == 1 + shard_roots
+                && shard_leaf_counts
+                    .iter()
+                    .all(|&count| count > 0 && count <= 1 << depth)
+                && total == Some(leaf_count),
+            MoatError::InvalidTreeShape
+        );
+    }
+    Ok(())
+}
+
+// Whether an earlier Secp256k1 precompile instruction of this transaction
+// recovered `eth_address` from a signature over `message`. As with Ed25519, the
+// address and message must live in the precompile's own data.
+fn has_eth_signature(
+    instructions: &AccountInfo,
+    eth_address: &[u8; 20],
+    message: &[u8],
+) -> Result<bool> {
+    const OFFSETS_START: usize = 1;
+    const OFFSETS_LEN: usize = 11;
+
+    let read_u16 = |data: &[u8], at: usize| {
+        data.get(at..at + 2)
+            .map(|bytes| usize::from(u16::from_le_bytes([bytes[0], bytes[1]])))
+    };
+
+    let current = load_current_index_checked(instructions)?;
+    for index in 0..current {
+        let instruction = load_instruction_at_checked(usize::from(index), instructions)?;
+        if instruction.program_id != secp256k1_program::ID {
+            continue;
+        }
+        let data = &instruction.data;
+        let count = data.first().copied().unwrap_or(0) as usize;
+        for n in 0..count {
+            let at = OFFSETS_START + n * OFFSETS_LEN;
+            let (Some(addr_at), Some(&addr_ix), Some(msg_at), Some(msg_len), Some(&msg_ix)) = (
+                read_u16(data, at + 3),
+                data.get(at + 5),
+                read_u16(data, at + 6),
+                read_u16(data, at + 8),
+                data.get(at + 10),
+            ) else {
+                break;
+            };
+            if u16::from(addr_ix) != index || u16::from(msg_ix) != index {
+                continue;
+            }
+            if data.get(addr_at..addr_at + 20) == Some(eth_address.as_slice())
+                && data.get(msg_at..msg_at + msg_len) == Some(message)
+            {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+// Counts distinct `signers` with an Ed25519 signature over `message` in an earlier
+// instruction of this transaction. The precompile has already checked each
+// signature; only entries whose key and message live in its own data count.
+fn count_cosignatures(
+    instructions: &AccountInfo,
+    signers: &[Pubkey],
+    message: &[u8],
+) -> Result<usize> {
+    const OFFSETS_START: usize = 2;
+    const OFFSETS_LEN: usize = 14;
+    const SAME_INSTRUCTION: u16 = u16::MAX;
+
+    let read_u16 = |data: &[u8], at: usize| {
+        data.get(at..at + 2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+    };
+
+    let mut seen = 0u16;
+    let current = load_current_index_checked(instructions)?;
+    for index in 0..current {
+        let instruction = load_instruction_at_checked(usize::from(index), instructions)?;
+        if instruction.program_id != ed25519_program::ID {
+            continue;
+        }
+        let data = &instruction.data;
+        let count = data.first().copied().unwrap_or(0) as usize;
+        for n in 0..count {
+            let at = OFFSETS_START + n * OFFSETS_LEN;
+            let (Some(key_offset), Some(key_ix), Some(msg_offset), Some(msg_len), Some(msg_ix)) = (
+                read_u16(data, at + 4),
+                read_u16(data, at + 6),
+                read_u16(data, at + 8),
+                read_u16(data, at + 10),
+                read_u16(data, at + 12),
+            ) else {
+                break;
+            };
+            if key_ix != SAME_INSTRUCTION || msg_ix != SAME_INSTRUCTION {
+                continue;
+            }
+            let key_offset = usize::from(key_offset);
+            let msg_offset = usize::from(msg_offset);
+            let (Some(key), Some(signed)) = (
+                data.get(key_offset..key_offset + 32),
+                data.get(msg_offset..msg_offset + usize::from(msg_len)),
+            ) else {
+                continue;
+            };
+            if signed != message {
+                continue;
+            }
+            if let Some(position) = signers.iter().position(|signer| signer.as_ref() == key) {
+                seen |= 1 << position;
+            }
+        }
+    }
+    Ok(seen.count_ones() as usize)
+}
+
+fn compression_init_data(max_depth: u32, max_buffer_size: u32) -> Vec<u8> {
+    let mut data = COMPRESSION_INIT_EMPTY_MERKLE_TREE.to_vec();
+    data.extend_from_slice(&max_depth.to_le_bytes());
+    data.extend_from_slice(&max_buffer_size.to_le_bytes());
+    data
+}
+
+fn compression_append_data(leaf: &[u8; 32]) -> Vec<u8> {
+    let mut data = COMPRESSION_APPEND.to_vec();
+    data.extend_from_slice(leaf);
+    data
+}
+
+fn compression_verify_leaf_data(root: &[u8; 32], leaf: &[u8; 32], index: u32) -> Vec<u8> {
+    let mut data = COMPRESSION_VERIFY_LEAF.to_vec();
+    data.extend_from_slice(root);
+    data.extend_from_slice(leaf);
+    data.extend_from_slice(&index.to_le_bytes());
+    data
+}
+
+// Reads the sequence number and newest root out of a concurrent tree account:
+// a 56-byte v1 header (max depth at byte 6), then the tree's sequence number,
+// active index and buffer size, then change logs of root, path and index.
+fn compressed_tree_root(data: &[u8]) -> Result<(u64, [u8; 32])> {
+    let read_u64 = |at: usize| -> Option<u64> {
+        Some(u64::from_le_bytes(data.get(at..at + 8)?.try_into().ok()?))
+    };
+    let max_depth = data
+        .get(6..10)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+        .ok_or(MoatError::InvalidCompressedTree)?;
+    let seq = read_u64(56).ok_or(MoatError::InvalidCompressedTree)?;
+    let active = read_u64(64).ok_or(MoatError::InvalidCompressedTree)? as usize;
+    let change_log = 32 + 32 * max_depth + 8;
+    let at = active
+        .checked_mul(change_log)
+        .and_then(|offset| offset.checked_add(80))
+        .ok_or(MoatError::InvalidCompressedTree)?;
+    let root = data
+        .get(at..at + 32)
+        .ok_or(MoatError::InvalidCompressedTree)?;
+    Ok((seq, root.try_into().unwrap()))
+}
+
+// Calls a tree-modifying compression instruction with `compressed_tree` signing
+// as the tree authority.
+fn invoke_compression<'info>(
+    compression_program: &UncheckedAccount<'info>,
+    compressed_tree: &Account<'info, CompressedTree>,
+    merkle_tree: &UncheckedAccount<'info>,
+    noop: &UncheckedAccount<'info>,
+    data: Vec<u8>,
+) -> Result<()> {
+    let instruction = Instruction {
+        program_id: SPL_ACCOUNT_COMPRESSION_ID,
+        accounts: vec![
+            AccountMeta::new(merkle_tree.key(), false),
+            AccountMeta::new_readonly(compressed_tree.key(), true),
+            AccountMeta::new_readonly(noop.key(), false),
+        ],
+        data,
+    };
+    invoke_signed(
+        &instruction,
+        &[
+            merkle_tree.to_account_info(),
+            compressed_tree.to_account_info(),
+            noop.to_account_info(),
+            compression_program.to_account_info(),
+        ],
+        &[&[
+            b"compressed_tree",
+            compressed_tree.entry.as_ref(),
+            &[compressed_tree.bump],
+        ]],
+    )?;
+    Ok(())
+}
+
+// Whether a commit is settled and past the registry's retention window.
+fn require_prunable(commit: &BatchCommit, retention: i64, now: i64) -> Result<()> {
+    require!(
+        retention > 0 && now >= commit.created_at.saturating_add(retention),
+        MoatError::RetentionNotElapsed
+    );
+    require!(!commit.challenged, MoatError::CommitUnderChallenge);
+    require!(
+        commit.revoked || commit.is_final(),
+        MoatError::DisputeWindowOpen
+    );
+    Ok(())
+}
+
+// Hands a program-owned account's lamports to `destination` and returns it
+// to the system program.
+fn close_account<'info>(
+    account: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+) -> Result<()> {
+    let lamports = account.lamports();
+    **account.try_borrow_mut_lamports()? = 0;
+    **destination.try_borrow_mut_lamports()? += lamports;
+    account.assign(&system_program::ID);
+    account.resize(0)?;
+    Ok(())
+}
+
+// Moves `amount` out of a commit vault with `vault_authority` signing.
+fn transfer_from_vault<'info>(
+    token_program: &UncheckedAccount<'info>,
+    vault: &UncheckedAccount<'info>,
+    mint: &UncheckedAccount<'info>,
+    destination: &UncheckedAccount<'info>,
+    vault_authority: &UncheckedAccount<'info>,
+    vault_seeds: &[&[u8]],
+    amount: u64,
+) -> Result<()> {
+    // TransferChecked wants the mint's decimals, which sit at offset 44.
+    let decimals = *mint
+        .try_borrow_data()?
+        .get(44)
+        .ok_or(MoatError::InvalidTokenAccount)?;
+    let mut data = vec![TOKEN_TRANSFER_CHECKED];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.push(decimals);
+
+    let instruction = Instruction {
+        program_id: token_program.key(),
+        accounts: vec![
+            AccountMeta::new(vault.key(), false),
+            AccountMeta::new_readonly(mint.key(), false),
+            AccountMeta::new(destination.key(), false),
+            AccountMeta::new_readonly(vault_authority.key(), true),
+        ],
+        data,
+    };
+    invoke_signed(
+        &instruction,
+        &[
+            vault.to_account_info(),
+            mint.to_account_info(),
+            destination.to_account_info(),
+            vault_authority.to_account_info(),
+            token_program.to_account_info(),
+        ],
+        &[vault_seeds],
+    )?;
+    Ok(())
+}
+
+// Returns the (mint, owner) pair at the head of a token account.
+fn read_token_account(account: &AccountInfo) -> Result<(Pubkey, Pubkey)> {
+    let data = account.try_borrow_data()?;
+    require!(data.len() >= 64, MoatError::InvalidTokenAccount);
+    let mint = Pubkey::try_from(&data[..32]).map_err(|_| MoatError::InvalidTokenAccount)?;
+    let owner = Pubkey::try_from(&data[32..64]).map_err(|_| MoatError::InvalidTokenAccount)?;
+    Ok((mint, owner))
+}
+
+// Returns the leaf's claim position once its proof checks out.
+fn verify_leaf(
+    commit: &BatchCommit,
+    exclusions: Option<&ExclusionList>,
+    root_index: u8,
+    index: u64,
+    leaf: [u8; 32],
+    proof: &[[u8; 32]],
+    now: i64,
+) -> Result<u64> {
+    commit.require_verifiable(now)?;
+
+    // A single tree has an exact depth; shards, and sorted-pair trees built
+    // without padding, are bounded by it.
+    let depth = usize::from(commit.depth);
+    require!(
+        if commit.shard_roots.is_empty() {
+            index < commit.leaf_count
+                && (proof.len() == depth || commit.sorted_pairs && proof.len() <= depth)
+        } else {
+            proof.len() <= depth && index < commit.shard_leaf_count(root_index)
+        },
+        MoatError::InvalidProof
+    );
+    require!(
+        moat_merkle::verify_proof(
+            &CommitHasher::new(commit.hash_alg)?,
+            commit.tree()?,
+            &commit.root(root_index)?,
+            &leaf,
+            index,
+            proof,
+        ),
+        MoatError::InvalidProof
+    );
+
+    let position = commit.claim_position(root_index, index);
+    if commit.excluded_leaves > 0 {
+        let exclusions = exclusions.ok_or(MoatError::ExclusionListRequired)?;
+        require!(!exclusions.positions.contains(&position), MoatError::LeafExcluded);
+    }
+    Ok(position)
+}
+
+fn verify_leaves(
+    commit: &BatchCommit,
+    root_index: u8,
+    leaves: &[[u8; 32]],
+    proof: &[[u8; 32]],
+    flags: &[bool],
+    now: i64,
+) -> Result<()> {
+    commit.require_verifiable(now)?;
+    require!(commit.sorted_pairs, MoatError::SortedPairsRequired);
+    require!(commit.excluded_leaves == 0, MoatError::LeafExcluded);
+    require!(
+        moat_merkle::process_multiproof(
+            &CommitHasher::new(commit.hash_alg)?,
+            commit.tree()?,
+            leaves,
+            proof,
+            flags,
+        ) == Some(commit.root(root_index)?),
+        MoatError::InvalidProof
+    );
+    Ok(())
+}
+
+fn verify_absent(
+    commit: &BatchCommit,
+    exclusions: Option<&ExclusionList>,
+    domain: &[u8; 32],
+    key: &[u8; 32],
+    low: Option<&KeyNeighbor>,
+    high: Option<&KeyNeighbor>,
+) -> Result<()> {
+    require!(
+        commit.leaf_schema == LEAF_SCHEMA_SORTED_KEY_V1,
+        MoatError::LeafSchemaMismatch
+    );
+    // Adjacency is read off leaf indices, which sorted-pair proofs don't bind;
+    // across shards there is no single order to be adjacent in.
+    require!(!commit.sorted_pairs, MoatError::UnboundLeafIndex);
+    require!(commit.shard_roots.is_empty(), MoatError::InvalidNonMembershipProof);
+
+    let adjacent = match (low, high) {
+        (Some(low), Some(high)) => {
+            low.key < *key && *key < high.key && low.index.checked_add(1) == Some(high.index)
+        }
+        (None, Some(high)) => *key < high.key && high.index == 0,
+        (Some(low), None) => low.key < *key && low.index.checked_add(1) == Some(commit.leaf_count),
+        (None, None) => false,
+    };
+    require!(adjacent, MoatError::InvalidNonMembershipProof);
+
+    let hasher = CommitHasher::new(commit.hash_alg)?;
+    let now = Clock::get()?.unix_timestamp;
+    for neighbor in low.into_iter().chain(high) {
+        let leaf = moat_merkle::hash_schema_leaf(
+            &hasher,
+            domain,
+            &moat_merkle::KeyLeafV1 { key: neighbor.key },
+        );
+        verify_leaf(commit, exclusions, 0, neighbor.index, leaf, &neighbor.proof, now)?;
+    }
+    Ok(())
+}
+
+// Passes when the commit has no ACL, or `caller` or the transaction's calling
+// program is on it.
+fn require_acl_member(
+    commit: &BatchCommit,
+    acl: Option<&CommitAcl>,
+    caller: &Pubkey,
+    instructions: Option<&UncheckedAccount>,
+) -> Result<()> {
+    if commit.acl_members == 0 {
+        return Ok(());
+    }
+    let acl = acl.ok_or(MoatError::CommitAclRequired)?;
+
+    let calling_program = match instructions {
+        Some(instructions) => {
+            let current = load_current_index_checked(instructions)?;
+            let program_id =
+                load_instruction_at_checked(usize::from(current), instructions)?.program_id;
+            (program_id != crate::ID).then_some(program_id)
+        }
+        None => None,
+    };
+    require!(
+        acl.members
+            .iter()
+            .any(|member| member == caller || Some(*member) == calling_program),
+        MoatError::NotOnCommitAcl
+    );
+    Ok(())
+}
+
+fn consume_leaf_at(
+    commit: &BatchCommit,
+    bitmap: &mut ClaimBitmap,
+    exclusions: Option<&ExclusionList>,
+    root_index: u8,
+    index: u64,
+    leaf: [u8; 32],
+    proof: &[[u8; 32]],
+) -> Result<()> {
+    require!(commit.is_final(), MoatError::CommitNotFinal);
+    // Sorted-pair proofs don't bind the index, so one leaf could be spent at
+    // every position.
+    require!(!commit.sorted_pairs, MoatError::UnboundLeafIndex);
+    let now = Clock::get()?.unix_timestamp;
+    let position = verify_leaf(commit, exclusions, root_index, index, leaf, proof, now)?;
+    bitmap.consume(position)
+}
+
+// The leaf `commit_registry_root` commits `entry` as.
+fn entry_leaf(hasher: &CommitHasher, domain: &[u8; 32], entry: &RegistryEntry) -> [u8; 32] {
+    moat_merkle::hash_schema_leaf(
+        hasher,
+        domain,
+        &moat_merkle::EntryLeafV1 {
+            id: entry.id,
+            target_program: entry.target_program.to_bytes(),
+            kind: entry.kind,
+            tags: entry.tags,
+            expires_at: entry.expires_at,
+        },
+    )
+}
+
+/// Node layout of a root committed under `root_version`.
+pub fn merkle_tree(root_version: u16, sorted_pairs: bool) -> Result<moat_merkle::Tree> {
+    moat_merkle::Tree::for_root_version(root_version, sorted_pairs)
+        .ok_or(error!(MoatError::UnsupportedRootVersion))
+}
+
+/// Syscall-backed `moat_merkle` hasher for one of the `HASH_ALG_*` values.
+pub struct CommitHasher(u8);
+
+impl CommitHasher {
+    pub fn new(hash_alg: u8) -> Result<Self> {
+        require!(
+            hash_alg == HASH_ALG_SHA256 || hash_alg == HASH_ALG_KECCAK256,
+            MoatError::UnsupportedHashAlg
+        );
+        Ok(Self(hash_alg))
+    }
+}
+
+impl moat_merkle::Hasher for CommitHasher {
+    #[inline]
+    fn hashv(&self, vals: &[&[u8]]) -> [u8; 32] {
+        match self.0 {
+            HASH_ALG_KECCAK256 => solana_keccak_hasher::hashv(vals).to_bytes(),
+            _ => hashv(vals).to_bytes(),
+        }
+    }
+}
+
+/// Hashes with the algorithm a commit was built with.
+pub fn hash_with(hash_alg: u8, vals: &[&[u8]]) -> Result<[u8; 32]> {
+    Ok(moat_merkle::Hasher::hashv(&CommitHasher::new(hash_alg)?, vals))
+}
+
+// Pays only the rent difference, so lamports an account holds beyond rent
+// (such as commit stake) are left untouched.
+fn grow_account<'info>(
+    payer: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    space: usize,
+) -> Result<()> {
+    let rent = Rent::get()?;
+    let extra = rent
+        .minimum_balance(space)
+        .saturating_sub(rent.minimum_balance(account.data_len()));
+    if extra > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.clone(),
+                system_program::Transfer {
+                    from: payer.clone(),
+                    to: account.clone(),
+                },
+            ),
+            extra,
+        )?;
+    }
+    account.resize(space)?;
+    Ok(())
+}
+
+fn require_not_banned(ban: &AccountInfo) -> Result<()> {
+    require!(
+        ban.owner != &crate::ID || ban.data_is_empty(),
+        MoatError::TargetBanned
+    );
+    Ok(())
+}
+
+fn require_kind_enabled(kind_definition: &AccountInfo) -> Result<()> {
+    if kind_definition.owner != &crate::ID || kind_definition.data_is_empty() {
+        return err!(MoatError::KindNotEnabled);
+    }
+    let data = kind_definition.try_borrow_data()?;
+    let definition = KindDefinition::try_deserialize(&mut &data[..])?;
+    require!(definition.enabled, MoatError::KindNotEnabled);
+    Ok(())
+}
+
+fn increment_active_entries(kind_definition: &AccountInfo) -> Result<()> {
+    let mut data = kind_definition.try_borrow_mut_data()?;
+    let mut definition = KindDefinition::try_deserialize(&mut &data[..])?;
+    definition.active_entries = definition
+        .active_entries
+        .checked_add(1)
+        .ok_or(MoatError::Overflow)?;
+    definition.try_serialize(&mut &mut data[..])
+}
+
+fn validate_target(target_kind: u8, target: &AccountInfo) -> Result<()> {
+    match target_kind {
+        TARGET_KIND_PROGRAM => {
+            let loader = target.owner;
+            require!(
+                target.executable
+                    && (*loader == bpf_loader_upgradeable::ID
+                        || *loader == BPF_LOADER_ID
+                        || *loader == BPF_LOADER_DEPRECATED_ID
+                        || *loader == LOADER_V4_ID),
+                MoatError::TargetNotExecutable
+            );
+        }
+        TARGET_KIND_MINT => {
+            require!(
+                *target.owner == TOKEN_PROGRAM_ID || *target.owner == TOKEN_2022_PROGRAM_ID,
+                MoatError::TargetNotMint
+            );
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn validate_multisig_owners(owners: &[Pubkey], threshold: u8) -> Result<()> {
+    require!(
+        !owners.is_empty() && owners.len() <= MAX_MULTISIG_OWNERS,
+        MoatError::InvalidMultisigOwners
+    );
+    for (i, owner) in owners.iter().enumerate() {
+        require!(!owners[..i].contains(owner), MoatError::InvalidMultisigOwners);
+    }
+    require!(
+        threshold > 0 && usize::from(threshold) <= owners.len(),
+        MoatError::InvalidThreshold
+    );
+    Ok(())
+}
+
+fn create_pda_account<'info>(
+    payer: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    space: usize,
+    signer_seeds: &[&[u8]],
+) -> Result<()> {
+    let rent = Rent::get()?.minimum_balance(space);
+    let current = account.lamports();
+
+    if current == 0 {
+        return system_program::create_account(
+            CpiContext::new_with_signer(
+                system_program.clone(),
+                system_program::CreateAccount {
+                    from: payer.clone(),
+                    to: account.clone(),
+                },
+                &[signer_seeds],
+            ),
+            rent,
+            space as u64,
+            &crate::ID,
+        );
+    }
+
+    // Someone pre-funded the address; top up, then allocate and assign.
+    if current < rent {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.clone(),
+                system_program::Transfer {
+                    from: payer.clone(),
+                    to: account.clone(),
+                },
+            ),
+            rent - current,
+        )?;
+    }
+    system_program::allocate(
+        CpiContext::new_with_signer(
+            system_program.clone(),
+            system_program::Allocate {
+                account_to_allocate: account.clone(),
+            },
+            &[signer_seeds],
+        ),
+        space as u64,
+    )?;
+    system_program::assign(
+        CpiContext::new_with_signer(
+            system_program.clone(),
+            system_program::Assign {
+                account_to_assign: account.clone(),
+            },
+            &[signer_seeds],
+        ),
+        &crate::ID,
+    )
+}
+
+#[event]
+pub struct EntryUpdated {
+    pub entry: Pubkey,
+    pub id: u32,
+    pub version: u32,
+    pub target_program: Pubkey,
+    pub kind: u8,
+}
+
+#[event]
+pub struct EntryNameSet {
+    pub entry: Pubkey,
+    pub id: u32,
+    pub version: u32,
+    pub name: [u8; 32],
+}
+
+#[event]
+pub struct EntryMetadataSet {
+    pub entry: Pubkey,
+    pub id: u32,
+    pub version: u32,
+    pub metadata_uri: String,
+}
+
+#[event]
+pub struct EntryClosed {
+    pub entry: Pubkey,
+    pub id: u32,
+    pub destination: Pubkey,
+}
+
+#[event]
+pub struct EntryTransferProposed {
+    pub entry: Pubkey,
+    pub id: u32,
+    pub version: u32,
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey,
+}
+
+#[event]
+pub struct EntryTransferAccepted {
+    pub entry: Pubkey,
+    pub id: u32,
+    pub version: u32,
+    pub previous_admin: Pubkey,
+    pub admin: Pubkey,
+}
+
+#[event]
+pub struct EntryDelegateSet {
+    pub entry: Pubkey,
+    pub id: u32,
+    pub version: u32,
+    pub delegate: Option<Pubkey>,
+}
+
+#[event]
+pub struct EntryTagsSet {
+    pub entry: Pubkey,
+    pub id: u32,
+    pub version: u32,
+    pub tags: u64,
+}
+
+#[event]
+pub struct EntryWeightSet {
+    pub entry: Pubkey,
+    pub id: u32,
+    pub version: u32,
+    pub weight: u16,
+}
+
+#[event]
+pub struct EntryDeprecated {
+    pub entry: Pubkey,
+    pub id: u32,
+    pub version: u32,
+    pub successor: Option<Pubkey>,
+}
+
+#[event]
+pub struct EntryParentSet {
+    pub entry: Pubkey,
+    pub id: u32,
+    pub version: u32,
+    pub parent: Option<Pubkey>,
+}
+
+#[event]
+pub struct EntryStatusChanged {
+    pub entry: Pubkey,
+    pub id: u32,
+    pub version: u32,
+    pub status: u8,
+}
+
+#[event]
+pub struct EntryRenewed {
+    pub entry: Pubkey,
+    pub id: u32,
+    pub version: u32,
+    pub expires_at: i64,
+}
+
+#[event]
+pub struct EntryFreezeChanged {
+    pub entry: Pubkey,
+    pub id: u32,
+    pub version: u32,
+    pub frozen: bool,
+}
+
+#[event]
+pub struct EntryRiskScoreSet {
+    pub entry: Pubkey,
+    pub id: u32,
+    pub version: u32,
+    pub risk_score: u8,
+    pub risk_updated_at: i64,
+}
+
+#[event]
+pub struct AuditRecorded {
+    pub entry: Pubkey,
+    pub id: u32,
+    pub version: u32,
+    pub auditor: Pubkey,
+    pub report_hash: [u8; 32],
+    pub active: bool,
+}
+
+#[event]
+pub struct EntryAttested {
+    pub entry: Pubkey,
+    pub attestor: Pubkey,
+    pub claim_hash: [u8; 32],
+    pub expires_at: i64,
+}
+
+#[event]
+pub struct CommitAttested {
+    pub commit: Pubkey,
+    pub attestor: Pubkey,
+    pub attestation_count: u16,
+    pub quorum: u16,
+}
+
+#[event]
+pub struct AttestationRevoked {
+    pub entry: Pubkey,
+    pub attestor: Pubkey,
+    pub reason: u8,
+    pub revoked_at: i64,
+}
+
+#[event]
+pub struct AttestationLapsed {
+    pub entry: Pubkey,
+    pub attestor: Pubkey,
+    pub expired: bool,
+    pub attestor_removed: bool,
+}
+
+#[event]
+pub struct AdminTransferProposed {
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey,
+}
+
+#[event]
+pub struct AdminTransferAccepted {
+    pub previous_admin: Pubkey,
+    pub admin: Pubkey,
+}
+
+#[event]
+pub struct TargetBanChanged {
+    pub target: Pubkey,
+    pub banned: bool,
+}
+
+#[event]
+pub struct RecoveryInitiated {
+    pub admin: Pubkey,
+    pub new_admin: Pubkey,
+    pub eta: i64,
+}
+
+#[event]
+pub struct ActionQueued {
+    pub id: u64,
+    pub action: TimelockAction,
+    pub eta: i64,
+}
+
+#[event]
+pub struct ActionExecuted {
+    pub id: u64,
+}
+
+#[event]
+pub struct ActionCancelled {
+    pub id: u64,
+}
+
+#[event]
+pub struct RegistryPauseChanged {
+    pub paused: bool,
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct PausedOperationsChanged {
+    pub paused_operations: u8,
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct RoleSet {
+    pub holder: Pubkey,
+    pub capabilities: u8,
+}
+
+#[event]
+pub struct SessionCreated {
+    pub session_key: Pubkey,
+    pub capabilities: u8,
+    pub expires_at_slot: u64,
+}
+
+#[event]
+pub struct SessionRevoked {
+    pub session_key: Pubkey,
+}
+
+#[event]
+pub struct MultisigOwnersSet {
+    pub multisig: Pubkey,
+    pub signer: Pubkey,
+    pub owners: Vec<Pubkey>,
+    pub threshold: u8,
+    pub owner_set_seqno: u32,
+}
+
+#[event]
+pub struct MultisigActionProposed {
+    pub multisig: Pubkey,
+    pub proposal: Pubkey,
+    pub id: u64,
+    pub proposer: Pubkey,
+}
+
+#[event]
+pub struct MultisigActionApproved {
+    pub multisig: Pubkey,
+    pub proposal: Pubkey,
+    pub id: u64,
+    pub owner: Pubkey,
+    pub approvals: u16,
+}
+
+#[event]
+pub struct MultisigActionExecuted {
+    pub multisig: Pubkey,
+    pub proposal: Pubkey,
+    pub id: u64,
+}
+
+#[event]
+pub struct CommitKindDefined {
+    pub kind: u8,
+    pub label: String,
+    pub enabled: bool,
+}
+
+#[event]
+pub struct KindDefined {
+    pub kind: u8,
+    pub label: String,
+    pub enabled: bool,
+}
+
+#[event]
+pub struct TagDefined {
+    pub bit: u8,
+    pub label: String,
+}
+
+#[event]
+pub struct CommitRevoked {
+    pub commit: Pubkey,
+    pub entry: Pubkey,
+    pub seq: u64,
+    pub reason: u8,
+}
+
+#[event]
+pub struct CommitSuperseded {
+    pub commit: Pubkey,
+    pub replacement: Pubkey,
+    pub entry: Pubkey,
+}
+
+#[event]
+pub struct LeafAppended {
+    pub tree: Pubkey,
+    pub entry: Pubkey,
+    pub index: u64,
+    pub leaf: [u8; 32],
+    pub root: [u8; 32],
+}
+
+#[event]
+pub struct CompressedLeafAppended {
+    pub merkle_tree: Pubkey,
+    pub entry: Pubkey,
+    pub index: u64,
+    pub leaf: [u8; 32],
+    pub seq: u64,
+    pub root: [u8; 32],
+}
+
+#[event]
+pub struct TokensClaimed {
+    pub commit: Pubkey,
+    pub claimant: Pubkey,
+    pub mint: Pubkey,
+    pub index: u64,
+    pub amount: u64,
+}
+
+#[event]
+pub struct CommitsAggregated {
+    pub commit: Pubkey,
+    pub member_ids: Vec<u64>,
+}
+
+#[event]
+pub struct RegistryRootCommitted {
+    pub registry: Pubkey,
+    pub root: [u8; 32],
+    pub entry_count: u64,
+    pub depth: u8,
+    pub version: u64,
+}
+
+#[event]
+pub struct EntryProofExported {
+    pub registry: Pubkey,
+    pub entry: Pubkey,
+    pub root: [u8; 32],
+    pub version: u64,
+    pub leaf_index: u64,
+    pub leaf: [u8; 32],
+}
+
+#[event]
+pub struct CommitConsumerChanged {
+    pub commit: Pubkey,
+    pub consumer: Pubkey,
+}
+
+#[event]
+pub struct CommitAclChanged {
+    pub commit: Pubkey,
+    pub members: Vec<Pubkey>,
+}
+
+#[event]
+pub struct CommitAnnotated {
+    pub commit: Pubkey,
+    pub code: u16,
+    pub hash: [u8; 32],
+    pub author: Pubkey,
+}
+
+#[event]
+pub struct LeavesExcluded {
+    pub commit: Pubkey,
+    pub root_index: u8,
+    pub indices: Vec<u64>,
+}
+
+#[event]
+pub struct LeafConsumed {
+    pub commit: Pubkey,
+    pub root_index: u8,
+    pub index: u64,
+    pub leaf: [u8; 32],
+    pub consumer: Pubkey,
+}
+
+#[event]
+pub struct CommitPruned {
+    pub commit: Pubkey,
+    pub id: u64,
+    pub record_hash: [u8; 32],
+    pub refunded: u64,
+    pub forfeited: u64,
+}
+
+#[event]
+pub struct VaultSwept {
+    pub commit: Pubkey,
+    pub vault: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct CommitFinalized {
+    pub commit: Pubkey,
+    pub entry: Pubkey,
+    pub seq: u64,
+}
+
+#[event]
+pub struct CommitChallenged {
+    pub commit: Pubkey,
+    pub challenger: Pubkey,
+    pub bond: u64,
+    pub claim_hash: [u8; 32],
+}
+
+#[event]
+pub struct ChallengeResolved {
+    pub commit: Pubkey,
+    pub challenger: Pubkey,
+    pub upheld: bool,
+}
+
+#[error_code]
+pub enum MoatError {
+    #[msg("Unauthorized")]
+    Unauthorized,
+    #[msg("Next id overflow")]
+    NextIdOverflow,
+    #[msg("Overflow")]
+    Overflow,
+    #[msg("Batch already exists for this creator")]
+    BatchAlreadyExists,
+    #[msg("Invalid target program")]
+    InvalidTargetProgram,
+    #[msg("No pending transfer")]
+    NoPendingTransfer,
+    #[msg("Invalid entry status")]
+    InvalidEntryStatus,
+    #[msg("Entry is not active")]
+    EntryNotActive,
+    #[msg("Entry is already active")]
+    EntryAlreadyActive,
+    #[msg("Label too long")]
+    LabelTooLong,
+    #[msg("Kind is not defined or not enabled")]
+    KindNotEnabled,
+    #[msg("Metadata URI too long")]
+    MetadataUriTooLong,
+    #[msg("Target program is already registered")]
+    DuplicateTarget,
+    #[msg("Invalid expiry")]
+    InvalidExpiry,
+    #[msg("Entry has expired")]
+    EntryExpired,
+    #[msg("Entry is frozen")]
+    EntryFrozen,
+    #[msg("Entry is not frozen")]
+    EntryNotFrozen,
+    #[msg("Batch is empty")]
+    EmptyBatch,
+    #[msg("Invalid remaining accounts")]
+    InvalidRemainingAccounts,
+    #[msg("Invalid delegate")]
+    InvalidDelegate,
+    #[msg("Invalid tag bit")]
+    InvalidTagBit,
+    #[msg("Tag is not defined")]
+    UndefinedTag,
+    #[msg("Invalid target kind")]
+    InvalidTargetKind,
+    #[msg("Target program is not executable")]
+    TargetNotExecutable,
+    #[msg("Target is not a token mint")]
+    TargetNotMint,
+    #[msg("Upgrade authority does not match program data")]
+    UpgradeAuthorityMismatch,
+    #[msg("Upgrade authority co-signature required")]
+    UpgradeAuthorityRequired,
+    #[msg("Audit is not active")]
+    AuditNotActive,
+    #[msg("Invalid successor entry")]
+    InvalidSuccessor,
+    #[msg("Invalid entry name")]
+    InvalidEntryName,
+    #[msg("Entry name is already registered")]
+    DuplicateName,
+    #[msg("Name lookup does not belong to this entry")]
+    InvalidNameLookup,
+    #[msg("Invalid parent entry")]
+    InvalidParent,
+    #[msg("Invalid interval")]
+    InvalidInterval,
+    #[msg("Entry was updated too recently")]
+    UpdateTooSoon,
+    #[msg("Entry has not expired")]
+    EntryNotExpired,
+    #[msg("Attestation has been revoked")]
+    AttestationRevoked,
+    #[msg("Invalid multisig owners")]
+    InvalidMultisigOwners,
+    #[msg("Invalid threshold")]
+    InvalidThreshold,
+    #[msg("Proposal has already been executed")]
+    ProposalAlreadyExecuted,
+    #[msg("Proposal was created under a previous owner set")]
+    StaleProposal,
+    #[msg("Not enough approvals")]
+    NotEnoughApprovals,
+    #[msg("Invalid role capabilities")]
+    InvalidCapabilities,
+    #[msg("Registry is paused")]
+    RegistryPaused,
+    #[msg("Action must go through the timelock")]
+    TimelockRequired,
+    #[msg("Timelock delay has not elapsed")]
+    TimelockNotElapsed,
+    #[msg("Admin log account is required")]
+    AdminLogRequired,
+    #[msg("Invalid governance account")]
+    InvalidGovernance,
+    #[msg("Target is banned")]
+    TargetBanned,
+    #[msg("Nonce does not match")]
+    InvalidNonce,
+    #[msg("Invalid pause flags")]
+    InvalidPauseFlags,
+    #[msg("Too soon since this key's last action")]
+    RateLimited,
+    #[msg("Missing or mismatched accounts for this action")]
+    InvalidActionAccounts,
+    #[msg("Commitment domain does not match this registry")]
+    InvalidCommitmentDomain,
+    #[msg("Previous root does not match the entry's latest commit")]
+    InvalidPrevRoot,
+    #[msg("Commit has been revoked")]
+    CommitRevoked,
+    #[msg("Commit is already finalized")]
+    CommitAlreadyFinalized,
+    #[msg("Dispute window has not passed")]
+    DisputeWindowOpen,
+    #[msg("Dispute window has closed")]
+    DisputeWindowClosed,
+    #[msg("Commit has an open challenge")]
+    CommitUnderChallenge,
+    #[msg("Commit has expired")]
+    CommitExpired,
+    #[msg("Leaf count does not match tree depth")]
+    InvalidTreeShape,
+    #[msg("Too many shard roots")]
+    TooManyShardRoots,
+    #[msg("Root index out of range")]
+    InvalidRootIndex,
+    #[msg("Memo too long")]
+    MemoTooLong,
+    #[msg("Memo does not match memo hash")]
+    InvalidMemoHash,
+    #[msg("Unsupported hash algorithm")]
+    UnsupportedHashAlg,
+    #[msg("Commit has been superseded")]
+    CommitAlreadySuperseded,
+    #[msg("Commit belongs to a different entry")]
+    CommitEntryMismatch,
+    #[msg("Payload URI too long")]
+    PayloadUriTooLong,
+    #[msg("Recent commits account is required")]
+    RecentCommitsRequired,
+    #[msg("Current commit log account is required")]
+    CommitLogRequired,
+    #[msg("Commit log segment is full")]
+    CommitLogFull,
+    #[msg("Epoch summary account is required")]
+    EpochSummaryRequired,
+    #[msg("Invalid commit co-signer set")]
+    InvalidCosigners,
+    #[msg("Not enough co-signatures over the root")]
+    NotEnoughCosignatures,
+    #[msg("Tree is full")]
+    TreeFull,
+    #[msg("Commit is too large for a claim bitmap")]
+    ClaimBitmapTooLarge,
+    #[msg("Leaf index out of range")]
+    InvalidLeafIndex,
+    #[msg("Leaf already consumed")]
+    LeafAlreadyConsumed,
+    #[msg("Invalid merkle proof")]
+    InvalidProof,
+    #[msg("Commit is not final")]
+    CommitNotFinal,
+    #[msg("Invalid token account")]
+    InvalidTokenAccount,
+    #[msg("Too soon since the registry's last commit")]
+    CommitTooSoon,
+    #[msg("Commit retention period has not elapsed")]
+    RetentionNotElapsed,
+    #[msg("Commit kind is not defined or not enabled")]
+    InvalidCommitKind,
+    #[msg("Unsupported root version")]
+    UnsupportedRootVersion,
+    #[msg("Too many excluded leaves")]
+    TooManyExcludedLeaves,
+    #[msg("Commit has exclusions; the exclusion list is required")]
+    ExclusionListRequired,
+    #[msg("Leaf has been excluded")]
+    LeafExcluded,
+    #[msg("Aggregate members must be distinct, final and unsharded")]
+    InvalidAggregateMembers,
+    #[msg("Aggregate root does not match its members")]
+    InvalidAggregateRoot,
+    #[msg("Sorted-pair commits cannot be consumed by index")]
+    UnboundLeafIndex,
+    #[msg("Multiproofs need a sorted-pair commit")]
+    SortedPairsRequired,
+    #[msg("Commit root is not attested")]
+    RootAttestationMissing,
+    #[msg("Registry requires a validity proof; pass the commit verifier")]
+    CommitVerifierRequired,
+    #[msg("Commit leaves do not use the expected schema")]
+    LeafSchemaMismatch,
+    #[msg("Adjacent leaves do not bracket the key")]
+    InvalidNonMembershipProof,
+    #[msg("Entries must be passed as consecutive entry PDAs from the build cursor")]
+    InvalidRegistryRootEntry,
+    #[msg("Wallet is not on the registration allowlist for this target")]
+    NotAllowlisted,
+    #[msg("Commit has not reached its attestation quorum")]
+    CommitQuorumNotMet,
+    #[msg("No registry root has been published yet")]
+    RegistryRootNotPublished,
+    #[msg("Raw leaf payload is too long")]
+    RawLeafTooLong,
+    #[msg("Payload does not decode as a leaf of the commit's schema")]
+    InvalidLeafPayload,
+    #[msg("Too many commit ACL members")]
+    TooManyAclMembers,
+    #[msg("Commit has an ACL; the ACL account is required")]
+    CommitAclRequired,
+    #[msg("Caller is not on the commit's ACL")]
+    NotOnCommitAcl,
+    #[msg("Commit has no room for more annotations")]
+    CommitAnnotationsFull,
+    #[msg("Entry holds a name; its name lookup must be closed with it")]
+    NameLookupRequired,
+    #[msg("Attestation has not expired and its attestor is still registered")]
+    AttestationStillActive,
+    #[msg("Session and delegate actions need a nonce consumed for them")]
+    NonceNotConsumed,
+    #[msg("Compressed tree account data is malformed")]
+    InvalidCompressedTree,
+    #[msg("Claim mint must be set exactly on claim-leaf commits and match the vault")]
+    InvalidClaimMint,
+    #[msg("Commit ACL lists a member twice")]
+    DuplicateAclMember,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::instruction::BorrowedInstruction;
+    use anchor_lang_compression::{Id, InstructionData};
+    use spl_account_compression::instruction as compression_ix;
+    use spl_account_compression::state::{
+        ConcurrentMerkleTreeHeader, CONCURRENT_MERKLE_TREE_HEADER_SIZE_V1,
+    };
+    use spl_account_compression::zero_copy::ZeroCopy;
+    use spl_account_compression::ConcurrentMerkleTree;
+
+    #[test]
+    fn compression_ids_match_spl() {
+        assert_eq!(
+            SPL_ACCOUNT_COMPRESSION_ID.to_bytes(),
+            spl_account_compression::ID.to_bytes()
+        );
+        assert_eq!(
+            SPL_NOOP_ID.to_bytes(),
+            spl_account_compression::Noop::id().to_bytes()
+        );
+    }
+
+    #[test]
+    fn compression_instruction_data_matches_spl() {
+        assert_eq!(
+            compression_init_data(14, 64),
+            compression_ix::InitEmptyMerkleTree {
+                max_depth: 14,
+                max_buffer_size: 64,
+            }
+            .data()
+        );
+        assert_eq!(
+            compression_append_data(&[7; 32]),
+            compression_ix::Append { leaf: [7; 32] }.data()
+        );
+        assert_eq!(
+            compression_verify_leaf_data(&[1; 32], &[2; 32], 5),
+            compression_ix::VerifyLeaf {
+                root: [1; 32],
+                leaf: [2; 32],
+                index: 5,
+            }
+            .data()
+        );
+    }
+
+    #[test]
+    fn compressed_tree_root_reads_spl_layout() {
+        let header_size = CONCURRENT_MERKLE_TREE_HEADER_SIZE_V1;
+        let mut data = vec![0u8; header_size + size_of::<ConcurrentMerkleTree<5, 8>>()];
+        let (header_bytes, tree_bytes) = data.split_at_mut(header_size);
+        let mut header = ConcurrentMerkleTreeHeader::try_from_slice(header_bytes).unwrap();
+        header.initialize(5, 8, &Default::default(), 0);
+        header.serialize(&mut &mut header_bytes[..]).unwrap();
+
+        let tree = ConcurrentMerkleTree::<5, 8>::load_mut_bytes(tree_bytes).unwrap();
+        tree.initialize().unwrap();
+        for leaf in 1..=11u8 {
+            tree.append([leaf; 32]).unwrap();
+        }
+        let expected = (tree.sequence_number, tree.get_root());
+
+        assert_eq!(compressed_tree_root(&data).unwrap(), expected);
+        assert!(compressed_tree_root(&data[..100]).is_err());
+    }
+
+    #[test]
+    fn incremental_tree_matches_computed_roots() {
+        let mut tree = zeroed::<IncrementalTree>();
+        tree.depth = 3;
+        tree.hash_alg = HASH_ALG_SHA256;
+        tree.root_version = ROOT_VERSION_V2;
+
+        let hasher = CommitHasher::new(HASH_ALG_SHA256).unwrap();
+        let layout = merkle_tree(ROOT_VERSION_V2, false).unwrap();
+        let leaves: Vec<[u8; 32]> = (1..=8u8).map(|leaf| [leaf; 32]).collect();
+        for count in 1..=leaves.len() {
+            tree.append(leaves[count - 1]).unwrap();
+            let (root, _) = moat_merkle::compute_root(&hasher, layout, &leaves[..count]).unwrap();
+            assert_eq!(tree.root, root);
+        }
+        assert_eq!(
+            tree.append([9; 32]).unwrap_err(),
+            error!(MoatError::TreeFull)
+        );
+    }
+
+    fn sha256_commit() -> BatchCommit {
+        let mut commit =
+            BatchCommit::deserialize(&mut &[0u8; BatchCommit::INIT_SPACE][..]).unwrap();
+        commit.hash_alg = HASH_ALG_SHA256;
+        commit.root_version = ROOT_VERSION_V2;
+        commit
+    }
+
+    #[test]
+    fn shard_rejects_duplicated_last_leaf() {
+        let hasher = CommitHasher::new(HASH_ALG_SHA256).unwrap();
+        let tree = merkle_tree(ROOT_VERSION_V2, false).unwrap();
+        let shard: Vec<[u8; 32]> = (1..=3u8).map(|leaf| [leaf; 32]).collect();
+        let (shard_root, depth) = moat_merkle::compute_root(&hasher, tree, &shard).unwrap();
+        let proof = moat_merkle::build_proof(&hasher, tree, &shard, 2).unwrap();
+
+        let mut commit = sha256_commit();
+        commit.merkle_root = [9; 32];
+        commit.shard_roots = vec![shard_root];
+        commit.shard_leaf_counts = vec![4, 3];
+        commit.leaf_count = 7;
+        commit.depth = depth;
+
+        let position = verify_leaf(&commit, None, 1, 2, shard[2], &proof, 0).unwrap();
+        assert_eq!(position, commit.claim_position(1, 2));
+        // The odd last leaf is paired with itself, so the same proof also
+        // walks up from index 3.
+        assert!(moat_merkle::verify_proof(&hasher, tree, &shard_root, &shard[2], 3, &proof));
+        assert!(verify_leaf(&commit, None, 1, 3, shard[2], &proof, 0).is_err());
+    }
+
+    #[test]
+    fn commit_interval_only_paces_gated_commits() {
+        let committer = Pubkey::new_unique();
+        let mut state = zeroed::<RegistryState>();
+        state.min_commit_interval_slots = 10;
+
+        // Anyone may commit to an open registry, so nobody holds the window.
+        authorize_commit(&mut state, &committer, None, None, None, 20).unwrap();
+        authorize_commit(&mut state, &Pubkey::new_unique(), None, None, None, 21).unwrap();
+        assert_eq!(state.last_commit_slot, 0);
+
+        state.commit_authority = committer;
+        authorize_commit(&mut state, &committer, None, None, None, 22).unwrap();
+        assert_eq!(
+            authorize_commit(&mut state, &committer, None, None, None, 31).unwrap_err(),
+            error!(MoatError::CommitTooSoon)
+        );
+        authorize_commit(&mut state, &committer, None, None, None, 32).unwrap();
+        assert_eq!(state.last_commit_slot, 32);
+    }
+
+    #[test]
+    fn multiproofs_only_verify_sorted_pair_roots() {
+        let hasher = CommitHasher::new(HASH_ALG_SHA256).unwrap();
+        let tree = merkle_tree(ROOT_VERSION_V2, true).unwrap();
+        let leaves = [[1; 32], [2; 32]];
+        let (root, depth) = moat_merkle::compute_root(&hasher, tree, &leaves).unwrap();
+
+        let mut commit = sha256_commit();
+        commit.sorted_pairs = true;
+        commit.merkle_root = root;
+        commit.leaf_count = 2;
+        commit.depth = depth;
+
+        assert!(verify_leaves(&commit, 0, &leaves, &[], &[true], 0).is_ok());
+        assert!(verify_leaves(&commit, 1, &leaves, &[], &[true], 0).is_err());
+        assert!(verify_leaves(&commit, 0, &[[1; 32], [3; 32]], &[], &[true], 0).is_err());
+        commit.sorted_pairs = false;
+        assert!(verify_leaves(&commit, 0, &leaves, &[], &[true], 0).is_err());
+    }
+
+    fn zeroed<T: AnchorDeserialize + Space>() -> T {
+        T::deserialize(&mut vec![0u8; T::INIT_SPACE].as_slice()).unwrap()
+    }
+
+    fn with_account<T, R>(value: &T, f: impl FnOnce(&mut Account<T>) -> R) -> R
+    where
+        T: AccountSerialize + AccountDeserialize + Owner + Clone,
+    {
+        let key = Pubkey::new_unique();
+        let mut data = Vec::new();
+        value.try_serialize(&mut data).unwrap();
+        let mut lamports = 0;
+        let info = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &crate::ID,
+            false,
+            0,
+        );
+        f(&mut Account::try_from(&info).unwrap())
+    }
+
+    fn commit_params(merkle_root: [u8; 32]) -> CommitParams {
+        CommitParams {
+            merkle_root,
+            memo_hash: [0; 32],
+            kind: 0,
+            commit_kind: 0,
+            domain: [0; 32],
+            prev_root: [0; 32],
+            valid_until: 0,
+            leaf_count: 1,
+            depth: 0,
+            shard_roots: Vec::new(),
+            shard_leaf_counts: Vec::new(),
+            hash_alg: HASH_ALG_SHA256,
+            root_version: ROOT_VERSION_V2,
+            sorted_pairs: false,
+            leaf_schema: 0,
+            claim_mint: Pubkey::default(),
+            payload_uri: String::new(),
+            validity_proof: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn frozen_entries_take_no_commits() {
+        let mut state = zeroed::<RegistryState>();
+        let mut entry = zeroed::<RegistryEntry>();
+        entry.status = ENTRY_STATUS_ACTIVE;
+        entry.frozen = true;
+        let mut commit = sha256_commit();
+        let clock = Clock::default();
+        let mut write = |entry: &RegistryEntry| {
+            with_account(entry, |entry| {
+                write_commit(
+                    &mut state,
+                    entry,
+                    &mut commit,
+                    Pubkey::new_unique(),
+                    0,
+                    commit_params([1; 32]),
+                    &clock,
+                )
+            })
+        };
+
+        assert_eq!(write(&entry), Err(error!(MoatError::EntryFrozen)));
+        entry.frozen = false;
+        assert!(write(&entry).is_ok());
+    }
+
+    #[test]
+    fn shard_leaf_counts_must_cover_every_root() {
+        assert!(validate_tree_shape(7, 2, 1, &[4, 3]).is_ok());
+        assert!(validate_tree_shape(7, 2, 1, &[]).is_err());
+        assert!(validate_tree_shape(7, 2, 1, &[7]).is_err());
+        assert!(validate_tree_shape(7, 2, 1, &[5, 2]).is_err());
+        assert!(validate_tree_shape(7, 2, 1, &[4, 4]).is_err());
+        assert!(validate_tree_shape(4, 2, 1, &[4, 0]).is_err());
+        assert!(validate_tree_shape(3, 2, 0, &[3]).is_err());
+        assert!(validate_tree_shape(3, 2, 0, &[]).is_ok());
+    }
+
+    // Serializes `instructions` with the program's own instruction at `current`.
+    fn instructions_sysvar(instructions: &[(Pubkey, Vec<u8>)], current: u16) -> Vec<u8> {
+        let borrowed: Vec<_> = instructions
+            .iter()
+            .map(|(program_id, data)| BorrowedInstruction {
+                program_id,
+                accounts: Vec::new(),
+                data,
+            })
+            .collect();
+        let mut data = solana_instructions_sysvar::construct_instructions_data(&borrowed);
+        solana_instructions_sysvar::store_current_index_checked(&mut data, current).unwrap();
+        data
+    }
+
+    fn sysvar_info<'a>(lamports: &'a mut u64, data: &'a mut [u8]) -> AccountInfo<'a> {
+        AccountInfo::new(
+            &sysvar::instructions::ID,
+            false,
+            false,
+            lamports,
+            data,
+            &sysvar::ID,
+            false,
+            0,
+        )
+    }
+
+    // One Ed25519 precompile instruction holding `(signer, message, key_ix, msg_ix)`
+    // entries, with zeroed signatures.
+    fn ed25519_data(entries: &[(Pubkey, &[u8], u16, u16)]) -> Vec<u8> {
+        let mut offsets = vec![entries.len() as u8, 0];
+        let mut payload = Vec::new();
+        for &(signer, message, key_ix, msg_ix) in entries {
+            let base = 2 + 14 * entries.len() + payload.len();
+            let (key_at, sig_at, msg_at) = (base, base + 32, base + 96);
+            for value in [
+                sig_at as u16,
+                u16::MAX,
+                key_at as u16,
+                key_ix,
+                msg_at as u16,
+                message.len() as u16,
+                msg_ix,
+            ] {
+                offsets.extend_from_slice(&value.to_le_bytes());
+            }
+            payload.extend_from_slice(signer.as_ref());
+            payload.extend_from_slice(&[0; 64]);
+            payload.extend_from_slice(message);
+        }
+        [offsets, payload].concat()
+    }
+
+    // One Secp256k1 precompile instruction at `index` holding
+    // `(eth_address, message, msg_ix)` entries, with zeroed signatures.
+    fn secp256k1_data(index: u8, entries: &[([u8; 20], &[u8], u8)]) -> Vec<u8> {
+        let mut offsets = vec![entries.len() as u8];
+        let mut payload = Vec::new();
+        for &(eth_address, message, msg_ix) in entries {
+            let base = 1 + 11 * entries.len() + payload.len();
+            let (addr_at, sig_at, msg_at) = (base, base + 20, base + 85);
+            offsets.extend_from_slice(&(sig_at as u16).to_le_bytes());
+            offsets.push(index);
+            offsets.extend_from_slice(&(addr_at as u16).to_le_bytes());
+            offsets.push(index);
+            offsets.extend_from_slice(&(msg_at as u16).to_le_bytes());
+            offsets.extend_from_slice(&(message.len() as u16).to_le_bytes());
+            offsets.push(msg_ix);
+            payload.extend_from_slice(&eth_address);
+            payload.extend_from_slice(&[0; 65]);
+            payload.extend_from_slice(message);
+        }
+        [offsets, payload].concat()
+    }
+
+    #[test]
+    fn cosignatures_count_distinct_signers_over_the_message() {
+        let signers = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let [a, b, c] = signers;
+        let message = b"entry update".as_slice();
+        let same = u16::MAX;
+        let mut data = instructions_sysvar(
+            &[
+                (
+                    ed25519_program::ID,
+                    ed25519_data(&[
+                        (a, message, same, same),
+                        (a, message, same, same),
+                        (b, b"something else", same, same),
+                        (c, message, 0, same),
+                        (Pubkey::new_unique(), message, same, same),
+                    ]),
+                ),
+                (crate::ID, ed25519_data(&[(b, message, same, same)])),
+                (ed25519_program::ID, ed25519_data(&[(c, message, same, same)])),
+                (crate::ID, Vec::new()),
+                // Past the current instruction, so it doesn't count.
+                (ed25519_program::ID, ed25519_data(&[(b, message, same, same)])),
+            ],
+            3,
+        );
+        let mut lamports = 0;
+        let info = sysvar_info(&mut lamports, &mut data);
+        assert_eq!(count_cosignatures(&info, &signers, message).unwrap(), 2);
+        assert_eq!(count_cosignatures(&info, &[b], message).unwrap(), 0);
+        assert_eq!(count_cosignatures(&info, &[a], b"other").unwrap(), 0);
+    }
+
+    #[test]
+    fn eth_signature_must_be_over_the_precompiles_own_data() {
+        let address = [7; 20];
+        let message = b"root".as_slice();
+        let mut data = instructions_sysvar(
+            &[
+                (crate::ID, Vec::new()),
+                (
+                    secp256k1_program::ID,
+                    secp256k1_data(1, &[(address, message, 0), ([8; 20], message, 1)]),
+                ),
+                (crate::ID, Vec::new()),
+                (secp256k1_program::ID, secp256k1_data(3, &[(address, message, 3)])),
+            ],
+            2,
+        );
+        let mut lamports = 0;
+        let info = sysvar_info(&mut lamports, &mut data);
+        assert!(!has_eth_signature(&info, &address, message).unwrap());
+        assert!(has_eth_signature(&info, &[8; 20], message).unwrap());
+        assert!(!has_eth_signature(&info, &[8; 20], b"other").unwrap());
+    }
+
+    #[test]
+    fn root_attestation_needs_every_configured_attestor() {
+        let state_key = Pubkey::new_unique();
+        let entry = Pubkey::new_unique();
+        let root = [3; 32];
+        let seq = 5u64;
+        let message = [state_key.as_ref(), entry.as_ref(), &root, &seq.to_le_bytes()].concat();
+
+        let attest = |state: &RegistryState, instructions: Option<&mut [u8]>| {
+            let mut state_data = Vec::new();
+            state.try_serialize(&mut state_data).unwrap();
+            let mut lamports = 0;
+            let info = AccountInfo::new(
+                &state_key,
+                false,
+                false,
+                &mut lamports,
+                &mut state_data,
+                &crate::ID,
+                false,
+                0,
+            );
+            let state = Account::<RegistryState>::try_from(&info).unwrap();
+            let mut sysvar_lamports = 0;
+            let sysvar = instructions.map(|data| sysvar_info(&mut sysvar_lamports, data));
+            let instructions = sysvar.as_ref().map(UncheckedAccount::try_from);
+            require_root_attestation(&state, instructions.as_ref(), &entry, seq, &root)
+        };
+        let missing = Err(error!(MoatError::RootAttestationMissing));
+
+        let mut state =
+            RegistryState::deserialize(&mut &[0u8; RegistryState::INIT_SPACE][..]).unwrap();
+        assert!(attest(&state, None).is_ok());
+
+        let attestor = Pubkey::new_unique();
+        let eth_attestor = [9; 20];
+        state.root_attestor = attestor;
+        state.root_attestor_eth = eth_attestor;
+        assert_eq!(attest(&state, None), missing);
+
+        let ed25519 = ed25519_data(&[(attestor, &message, u16::MAX, u16::MAX)]);
+        let secp256k1 = secp256k1_data(1, &[(eth_attestor, &message, 1)]);
+        let mut only_ed25519 = instructions_sysvar(
+            &[(ed25519_program::ID, ed25519.clone()), (crate::ID, Vec::new())],
+            1,
+        );
+        assert_eq!(attest(&state, Some(&mut only_ed25519)), missing);
+
+        let mut stale = message.clone();
+        stale[96..].copy_from_slice(&(seq - 1).to_le_bytes());
+        let mut stale_seq = instructions_sysvar(
+            &[
+                (ed25519_program::ID, ed25519.clone()),
+                (secp256k1_program::ID, secp256k1_data(1, &[(eth_attestor, &stale, 1)])),
+                (crate::ID, Vec::new()),
+            ],
+            2,
+        );
+        assert_eq!(attest(&state, Some(&mut stale_seq)), missing);
+
+        let mut both = instructions_sysvar(
+            &[
+                (ed25519_program::ID, ed25519),
+                (secp256k1_program::ID, secp256k1.clone()),
+                (crate::ID, Vec::new()),
+            ],
+            2,
+        );
+        assert!(attest(&state, Some(&mut both)).is_ok());
+
+        state.root_attestor = Pubkey::default();
+        let mut only_secp256k1 = instructions_sysvar(
+            &[
+                (crate::ID, Vec::new()),
+                (secp256k1_program::ID, secp256k1),
+                (crate::ID, Vec::new()),
+            ],
+            2,
+        );
+        assert!(attest(&state, Some(&mut only_secp256k1)).is_ok());
+    }
+
+    #[test]
+    fn cosignatures_bind_the_entry_and_seq() {
+        let signer = Pubkey::new_unique();
+        let mut state = zeroed::<RegistryState>();
+        state.commit_cosigner_threshold = 1;
+        let mut cosigners = zeroed::<CommitCosigners>();
+        cosigners.threshold = 1;
+        cosigners.signers = vec![signer];
+        let entry = Pubkey::new_unique();
+        let root = [4; 32];
+
+        with_account(&state, |state| {
+            let cosign = |signed_entry: &Pubkey, signed_seq: u64, cosigners: Option<&_>| {
+                let message = root_message(&state.key(), signed_entry, &root, signed_seq);
+                let mut data = instructions_sysvar(
+                    &[
+                        (
+                            ed25519_program::ID,
+                            ed25519_data(&[(signer, &message, u16::MAX, u16::MAX)]),
+                        ),
+                        (crate::ID, Vec::new()),
+                    ],
+                    1,
+                );
+                let mut lamports = 0;
+                let info = sysvar_info(&mut lamports, &mut data);
+                let instructions = UncheckedAccount::try_from(&info);
+                require_cosignatures(state, cosigners, Some(&instructions), &entry, 7, &root)
+            };
+            let missing = Err(error!(MoatError::NotEnoughCosignatures));
+
+            assert!(cosign(&entry, 7, Some(&cosigners)).is_ok());
+            assert_eq!(cosign(&entry, 6, Some(&cosigners)), missing);
+            assert_eq!(cosign(&Pubkey::new_unique(), 7, Some(&cosigners)), missing);
+            assert_eq!(cosign(&entry, 7, None), missing);
+        });
+    }
+
+    #[test]
+    fn claim_mint_is_set_exactly_on_claim_commits() {
+        let state = zeroed::<RegistryState>();
+        with_account(&state, |state| {
+            let check = |leaf_schema, claim_mint| {
+                let params = CommitParams {
+                    domain: commitment_domain(&state.key()),
+                    leaf_schema,
+                    claim_mint,
+                    ..commit_params([1; 32])
+                };
+                check_commit_params(state, &params, None, 0)
+            };
+            let invalid = Err(error!(MoatError::InvalidClaimMint));
+            let mint = Pubkey::new_unique();
+
+            assert!(check(LEAF_SCHEMA_CLAIM_V1, mint).is_ok());
+            assert_eq!(check(LEAF_SCHEMA_CLAIM_V1, Pubkey::default()), invalid);
+            assert_eq!(check(LEAF_SCHEMA_CUSTOM, mint), invalid);
+            assert!(check(LEAF_SCHEMA_CUSTOM, Pubkey::default()).is_ok());
+        });
+    }
 }
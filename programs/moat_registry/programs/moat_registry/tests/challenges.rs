@@ -0,0 +1,137 @@
+mod common;
+
+use anchor_lang::error::ErrorCode;
+use anchor_lang::prelude::*;
+use common::{at, moat_error, pda, process, registry_state, zeroed, TestAccount};
+use moat_registry::{instruction, BatchCommit, Challenge, MoatError, COMMIT_STATUS_PENDING};
+
+const STAKE: u64 = 50_000;
+const BALANCE: u64 = 1_000_000_000;
+
+/// A pending commit carrying `STAKE`, under a challenge whose account holds
+/// the bond and its rent.
+struct Dispute {
+    admin: Pubkey,
+    state: TestAccount,
+    batch: TestAccount,
+    challenge: TestAccount,
+    challenger: TestAccount,
+    creator: TestAccount,
+}
+
+impl Dispute {
+    fn new() -> Self {
+        let admin = Pubkey::new_unique();
+        let creator = Pubkey::new_unique();
+        let challenger = Pubkey::new_unique();
+        let entry = Pubkey::new_unique();
+
+        let (batch_key, bump) = pda(&[b"commit", entry.as_ref(), &0u64.to_le_bytes()]);
+        let mut commit = zeroed::<BatchCommit>();
+        commit.creator = creator;
+        commit.entry = entry;
+        commit.status = COMMIT_STATUS_PENDING;
+        commit.dispute_ends_at = 100;
+        commit.stake = STAKE;
+        commit.challenged = true;
+        commit.bump = bump;
+
+        let (challenge_key, bump) = pda(&[b"challenge", batch_key.as_ref()]);
+        let mut challenge = zeroed::<Challenge>();
+        challenge.commit = batch_key;
+        challenge.challenger = challenger;
+        challenge.bond = 10_000;
+        challenge.bump = bump;
+
+        Self {
+            admin,
+            state: registry_state(admin, |_| {}),
+            batch: TestAccount::program(batch_key, &commit, 8 + BatchCommit::INIT_SPACE),
+            challenge: TestAccount::program(challenge_key, &challenge, 8 + Challenge::INIT_SPACE),
+            challenger: TestAccount::raw(challenger, system_program::ID, Vec::new()),
+            creator: TestAccount::raw(creator, system_program::ID, Vec::new()),
+        }
+    }
+
+    fn commit(&self) -> BatchCommit {
+        self.batch.load()
+    }
+
+    fn resolve(
+        &mut self,
+        authority: Pubkey,
+        upheld: bool,
+    ) -> std::result::Result<(), ProgramError> {
+        let mut accounts = [
+            TestAccount::signer(authority),
+            std::mem::replace(&mut self.state, TestAccount::none()),
+            std::mem::replace(&mut self.batch, TestAccount::none()),
+            std::mem::replace(&mut self.challenge, TestAccount::none()),
+            std::mem::replace(&mut self.challenger, TestAccount::none()),
+            std::mem::replace(&mut self.creator, TestAccount::none()),
+            TestAccount::none(),
+        ];
+        let result = process(
+            instruction::ResolveChallenge { upheld, reason: 4 },
+            &mut accounts,
+            &mut [],
+        );
+        let [_, state, batch, challenge, challenger, creator, _] = accounts;
+        (self.state, self.batch, self.challenge) = (state, batch, challenge);
+        (self.challenger, self.creator) = (challenger, creator);
+        result
+    }
+}
+
+#[test]
+fn only_the_admin_resolves_with_the_real_challenger() {
+    let _clock = at(50);
+    let mut dispute = Dispute::new();
+    let creator = dispute.creator.key;
+    assert_eq!(
+        dispute.resolve(creator, false),
+        Err(moat_error(MoatError::Unauthorized))
+    );
+
+    dispute.challenger = TestAccount::raw(Pubkey::new_unique(), system_program::ID, Vec::new());
+    assert_eq!(
+        dispute.resolve(dispute.admin, true),
+        Err(anchor_lang::error::Error::from(ErrorCode::ConstraintHasOne).into())
+    );
+    assert!(dispute.commit().challenged);
+}
+
+#[test]
+fn upholding_pays_the_stake_and_bond_to_the_challenger() {
+    let _clock = at(50);
+    let mut dispute = Dispute::new();
+    let batch_lamports = dispute.batch.lamports;
+    dispute.resolve(dispute.admin, true).unwrap();
+
+    let commit = dispute.commit();
+    assert!(commit.revoked && !commit.challenged);
+    assert_eq!(
+        (commit.revoke_reason, commit.revoked_at, commit.stake),
+        (4, 50, 0)
+    );
+    assert_eq!(dispute.batch.lamports, batch_lamports - STAKE);
+    assert_eq!(dispute.challenger.lamports, BALANCE + STAKE + BALANCE);
+    assert_eq!(dispute.creator.lamports, BALANCE);
+    assert!(dispute.challenge.data.is_empty());
+}
+
+#[test]
+fn rejecting_forfeits_the_bond_to_the_committer() {
+    let _clock = at(50);
+    let mut dispute = Dispute::new();
+    let batch_lamports = dispute.batch.lamports;
+    dispute.resolve(dispute.admin, false).unwrap();
+
+    let commit = dispute.commit();
+    assert!(!commit.revoked && !commit.challenged);
+    assert_eq!(commit.stake, STAKE);
+    assert_eq!(dispute.batch.lamports, batch_lamports);
+    assert_eq!(dispute.creator.lamports, BALANCE + BALANCE);
+    assert_eq!(dispute.challenger.lamports, BALANCE);
+    assert!(dispute.challenge.data.is_empty());
+}
@@ -0,0 +1,434 @@
+mod common;
+
+use anchor_lang::prelude::*;
+use common::{at, moat_error, pda, process, zeroed, TestAccount};
+use moat_registry::{
+    commitment_domain, instruction, merkle_tree, BatchCommit, ClaimBitmap, CommitAcl,
+    CommitHasher, MoatError, RegistryEntry, COMMIT_STATUS_FINALIZED, COMMIT_STATUS_PENDING,
+    HASH_ALG_SHA256, LEAF_SCHEMA_CLAIM_V1, ROOT_VERSION_V2, TOKEN_PROGRAM_ID,
+};
+
+const NOW: i64 = 1_700_000_000;
+
+struct Commit {
+    creator: Pubkey,
+    registry: Pubkey,
+    batch: TestAccount,
+    entry: TestAccount,
+    claim_bitmap: TestAccount,
+    acl: TestAccount,
+}
+
+impl Commit {
+    // Commits `leaves` as one root, or as a root plus one shard per extra set.
+    fn new(sets: &[Vec<[u8; 32]>], leaf_schema: u16) -> Self {
+        let hasher = CommitHasher::new(HASH_ALG_SHA256).unwrap();
+        let tree = merkle_tree(ROOT_VERSION_V2, false).unwrap();
+        let roots: Vec<([u8; 32], u8)> = sets
+            .iter()
+            .map(|leaves| moat_merkle::compute_root(&hasher, tree, leaves).unwrap())
+            .collect();
+
+        let creator = Pubkey::new_unique();
+        let registry = Pubkey::new_unique();
+        let entry_key = Pubkey::new_unique();
+        let mut entry = zeroed::<RegistryEntry>();
+        entry.registry = registry;
+        entry.admin = creator;
+
+        let mut commit = zeroed::<BatchCommit>();
+        commit.creator = creator;
+        commit.entry = entry_key;
+        commit.hash_alg = HASH_ALG_SHA256;
+        commit.root_version = ROOT_VERSION_V2;
+        commit.leaf_schema = leaf_schema;
+        commit.merkle_root = roots[0].0;
+        commit.shard_roots = roots[1..].iter().map(|(root, _)| *root).collect();
+        commit.depth = roots.iter().map(|(_, depth)| *depth).max().unwrap();
+        commit.leaf_count = sets.iter().map(|leaves| leaves.len() as u64).sum();
+        if sets.len() > 1 {
+            commit.shard_leaf_counts = sets.iter().map(|leaves| leaves.len() as u64).collect();
+        }
+        let (batch_key, bump) = pda(&[b"commit", entry_key.as_ref(), &0u64.to_le_bytes()]);
+        commit.bump = bump;
+
+        let (bitmap_key, bump) = pda(&[b"claims", batch_key.as_ref()]);
+        let bytes = commit.claim_slots().div_ceil(8) as usize;
+        let bitmap = ClaimBitmap {
+            commit: batch_key,
+            consumed: 0,
+            bump,
+            bits: vec![0; bytes],
+        };
+
+        Self {
+            creator,
+            registry,
+            batch: TestAccount::program(batch_key, &commit, 8 + BatchCommit::INIT_SPACE),
+            entry: TestAccount::program(entry_key, &entry, 8 + RegistryEntry::INIT_SPACE),
+            claim_bitmap: TestAccount::program(
+                bitmap_key,
+                &bitmap,
+                8 + ClaimBitmap::INIT_SPACE + bytes,
+            ),
+            acl: TestAccount::none(),
+        }
+    }
+
+    fn update(&mut self, change: impl FnOnce(&mut BatchCommit)) {
+        let mut commit = self.batch.load::<BatchCommit>();
+        change(&mut commit);
+        self.batch = TestAccount::program(self.batch.key, &commit, 8 + BatchCommit::INIT_SPACE);
+    }
+
+    fn proof(sets: &[Vec<[u8; 32]>], root_index: usize, index: usize) -> Vec<[u8; 32]> {
+        let hasher = CommitHasher::new(HASH_ALG_SHA256).unwrap();
+        let tree = merkle_tree(ROOT_VERSION_V2, false).unwrap();
+        moat_merkle::build_proof(&hasher, tree, &sets[root_index], index).unwrap()
+    }
+
+    fn consume(
+        &mut self,
+        authority: Pubkey,
+        root_index: u8,
+        index: u64,
+        leaf: [u8; 32],
+        proof: Vec<[u8; 32]>,
+    ) -> std::result::Result<(), ProgramError> {
+        let mut accounts = [
+            TestAccount::signer(authority),
+            std::mem::replace(&mut self.batch, TestAccount::none()),
+            std::mem::replace(&mut self.entry, TestAccount::none()),
+            TestAccount::none(),
+            std::mem::replace(&mut self.claim_bitmap, TestAccount::none()),
+            TestAccount::none(),
+            std::mem::replace(&mut self.acl, TestAccount::none()),
+            TestAccount::none(),
+        ];
+        let result = process(
+            instruction::ConsumeLeaf {
+                root_index,
+                index,
+                leaf,
+                proof,
+            },
+            &mut accounts,
+            &mut [],
+        );
+        let [_, batch, entry, _, claim_bitmap, _, acl, _] = accounts;
+        (self.batch, self.entry, self.claim_bitmap, self.acl) = (batch, entry, claim_bitmap, acl);
+        result
+    }
+
+    fn set_acl(&mut self, members: Vec<Pubkey>) -> std::result::Result<(), ProgramError> {
+        let acl = if self.acl.key == moat_registry::ID {
+            // Already allocated, so `init_if_needed` stops short of the system
+            // program.
+            let (key, _) = pda(&[b"acl", self.batch.key.as_ref()]);
+            TestAccount::program(key, &zeroed::<CommitAcl>(), 8 + CommitAcl::INIT_SPACE)
+        } else {
+            std::mem::replace(&mut self.acl, TestAccount::none())
+        };
+        let mut accounts = [
+            TestAccount::signer(self.creator),
+            std::mem::replace(&mut self.batch, TestAccount::none()),
+            acl,
+            TestAccount::executable(system_program::ID),
+        ];
+        let result = process(instruction::SetCommitAcl { members }, &mut accounts, &mut []);
+        let [_, batch, acl, _] = accounts;
+        (self.batch, self.acl) = (batch, acl);
+        result
+    }
+
+    fn consumed(&self) -> u64 {
+        self.claim_bitmap.load::<ClaimBitmap>().consumed
+    }
+}
+
+fn leaves(count: u8) -> Vec<[u8; 32]> {
+    (1..=count).map(|i| [i; 32]).collect()
+}
+
+#[test]
+fn consume_leaf_marks_a_leaf_once() {
+    let _clock = at(NOW);
+    let sets = [leaves(5)];
+    let mut commit = Commit::new(&sets, 0);
+    let creator = commit.creator;
+
+    commit
+        .consume(creator, 0, 4, sets[0][4], Commit::proof(&sets, 0, 4))
+        .unwrap();
+    assert_eq!(commit.consumed(), 1);
+    assert_eq!(commit.claim_bitmap.load::<ClaimBitmap>().bits[0], 1 << 4);
+
+    assert_eq!(
+        commit.consume(creator, 0, 4, sets[0][4], Commit::proof(&sets, 0, 4)),
+        Err(moat_error(MoatError::LeafAlreadyConsumed))
+    );
+    assert_eq!(commit.consumed(), 1);
+}
+
+#[test]
+fn consume_leaf_rejects_the_duplicated_last_shard_leaf() {
+    let _clock = at(NOW);
+    let sets = [leaves(4), leaves(3)];
+    let mut commit = Commit::new(&sets, 0);
+    let creator = commit.creator;
+    let proof = Commit::proof(&sets, 1, 2);
+
+    commit.consume(creator, 1, 2, sets[1][2], proof.clone()).unwrap();
+    // Index 3 of a three-leaf shard walks the same path as index 2.
+    assert_eq!(
+        commit.consume(creator, 1, 3, sets[1][2], proof),
+        Err(moat_error(MoatError::InvalidProof))
+    );
+    assert_eq!(commit.consumed(), 1);
+}
+
+#[test]
+fn consume_leaf_rejects_bad_proofs_and_strangers() {
+    let _clock = at(NOW);
+    let sets = [leaves(5)];
+    let mut commit = Commit::new(&sets, 0);
+    let creator = commit.creator;
+
+    assert_eq!(
+        commit.consume(creator, 0, 1, sets[0][2], Commit::proof(&sets, 0, 1)),
+        Err(moat_error(MoatError::InvalidProof))
+    );
+    assert_eq!(
+        commit.consume(creator, 0, 5, sets[0][4], Commit::proof(&sets, 0, 4)),
+        Err(moat_error(MoatError::InvalidProof))
+    );
+    assert_eq!(
+        commit.consume(Pubkey::new_unique(), 0, 1, sets[0][1], Commit::proof(&sets, 0, 1)),
+        Err(moat_error(MoatError::Unauthorized))
+    );
+    assert_eq!(commit.consumed(), 0);
+}
+
+#[test]
+fn consume_leaf_waits_for_the_dispute_window() {
+    let _clock = at(NOW);
+    let sets = [leaves(2)];
+    let mut commit = Commit::new(&sets, 0);
+    let creator = commit.creator;
+    commit.update(|batch| {
+        batch.status = COMMIT_STATUS_PENDING;
+        batch.created_at = NOW - 10;
+        batch.dispute_ends_at = NOW + 10;
+    });
+
+    assert_eq!(
+        commit.consume(creator, 0, 0, sets[0][0], Commit::proof(&sets, 0, 0)),
+        Err(moat_error(MoatError::CommitNotFinal))
+    );
+}
+
+struct Claimant {
+    wallet: Pubkey,
+    mint: Pubkey,
+    destination_owner: Pubkey,
+}
+
+fn claim(
+    commit: &mut Commit,
+    claimant: &Claimant,
+    index: u64,
+    amount: u64,
+    proof: Vec<[u8; 32]>,
+) -> std::result::Result<(), ProgramError> {
+    let token_account = |mint: &Pubkey, owner: &Pubkey| {
+        let mut data = vec![0u8; 165];
+        data[..32].copy_from_slice(mint.as_ref());
+        data[32..64].copy_from_slice(owner.as_ref());
+        data[64..72].copy_from_slice(&1_000_000u64.to_le_bytes());
+        data
+    };
+    let (vault_authority, _) = pda(&[b"vault", commit.batch.key.as_ref()]);
+    let mut mint_data = vec![0u8; 82];
+    mint_data[44] = 6;
+
+    let mut accounts = [
+        TestAccount::signer(claimant.wallet),
+        std::mem::replace(&mut commit.batch, TestAccount::none()),
+        std::mem::replace(&mut commit.entry, TestAccount::none()),
+        std::mem::replace(&mut commit.claim_bitmap, TestAccount::none()),
+        TestAccount::none(),
+        TestAccount::none(),
+        TestAccount::none(),
+        TestAccount::empty(vault_authority),
+        TestAccount::raw(
+            Pubkey::new_unique(),
+            TOKEN_PROGRAM_ID,
+            token_account(&claimant.mint, &vault_authority),
+        ),
+        TestAccount::raw(
+            Pubkey::new_unique(),
+            TOKEN_PROGRAM_ID,
+            token_account(&claimant.mint, &claimant.destination_owner),
+        ),
+        TestAccount::raw(claimant.mint, TOKEN_PROGRAM_ID, mint_data),
+        TestAccount::executable(TOKEN_PROGRAM_ID),
+    ];
+    let result = process(
+        instruction::Claim {
+            root_index: 0,
+            index,
+            amount,
+            proof,
+        },
+        &mut accounts,
+        &mut [],
+    );
+    let [_, batch, entry, claim_bitmap, ..] = accounts;
+    (commit.batch, commit.entry, commit.claim_bitmap) = (batch, entry, claim_bitmap);
+    result
+}
+
+// A commit paying `100 * (i + 1)` of a fresh claim mint to `wallets[i]`.
+fn claim_commit(wallets: &[Pubkey], leaf_schema: u16) -> (Commit, Vec<[u8; 32]>, Pubkey) {
+    // The domain depends on the registry, which `Commit::new` picks; build once
+    // to learn it, then again over the real claim leaves.
+    let registry = Commit::new(&[leaves(1)], leaf_schema).registry;
+    let hasher = CommitHasher::new(HASH_ALG_SHA256).unwrap();
+    let domain = commitment_domain(&registry);
+    let claims: Vec<[u8; 32]> = wallets
+        .iter()
+        .enumerate()
+        .map(|(i, wallet)| {
+            moat_merkle::claim_leaf(&hasher, &domain, &wallet.to_bytes(), 100 * (i as u64 + 1))
+        })
+        .collect();
+    let mut commit = Commit::new(std::slice::from_ref(&claims), leaf_schema);
+    let mut entry = commit.entry.load::<RegistryEntry>();
+    entry.registry = registry;
+    commit.entry = TestAccount::program(commit.entry.key, &entry, 8 + RegistryEntry::INIT_SPACE);
+    commit.registry = registry;
+    let mint = Pubkey::new_unique();
+    commit.update(|batch| batch.claim_mint = mint);
+    (commit, claims, mint)
+}
+
+// A successful claim ends in the token CPI, which only runs on-chain; what is
+// checked here is that a leaf spent through the shared bitmap can't pay out.
+#[test]
+fn claim_rejects_a_consumed_leaf() {
+    let _clock = at(NOW);
+    let wallets: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+    let (mut commit, claims, mint) = claim_commit(&wallets, LEAF_SCHEMA_CLAIM_V1);
+    let claimant = Claimant {
+        wallet: wallets[2],
+        mint,
+        destination_owner: wallets[2],
+    };
+    let proof = Commit::proof(std::slice::from_ref(&claims), 0, 2);
+    let creator = commit.creator;
+
+    commit.consume(creator, 0, 2, claims[2], proof.clone()).unwrap();
+    assert_eq!(
+        claim(&mut commit, &claimant, 2, 300, proof),
+        Err(moat_error(MoatError::LeafAlreadyConsumed))
+    );
+    assert_eq!(commit.consumed(), 1);
+}
+
+#[test]
+fn claim_rejects_wrong_amounts_accounts_and_schemas() {
+    let _clock = at(NOW);
+    let wallets: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+    let (mut commit, claims, mint) = claim_commit(&wallets, LEAF_SCHEMA_CLAIM_V1);
+    let proof = Commit::proof(std::slice::from_ref(&claims), 0, 1);
+    let claimant = Claimant {
+        wallet: wallets[1],
+        mint,
+        destination_owner: wallets[1],
+    };
+
+    assert_eq!(
+        claim(&mut commit, &claimant, 1, 201, proof.clone()),
+        Err(moat_error(MoatError::InvalidProof))
+    );
+    let elsewhere = Claimant {
+        destination_owner: Pubkey::new_unique(),
+        ..claimant
+    };
+    assert_eq!(
+        claim(&mut commit, &elsewhere, 1, 200, proof.clone()),
+        Err(moat_error(MoatError::InvalidTokenAccount))
+    );
+    assert_eq!(commit.consumed(), 0);
+
+    let (mut custom, _, mint) = claim_commit(&wallets, 0);
+    let claimant = Claimant {
+        mint,
+        destination_owner: wallets[1],
+        ..elsewhere
+    };
+    assert_eq!(
+        claim(&mut custom, &claimant, 1, 200, proof),
+        Err(moat_error(MoatError::LeafSchemaMismatch))
+    );
+}
+
+#[test]
+fn claim_pays_only_in_the_commits_mint() {
+    let _clock = at(NOW);
+    let wallets: Vec<Pubkey> = (0..2).map(|_| Pubkey::new_unique()).collect();
+    let (mut commit, claims, _) = claim_commit(&wallets, LEAF_SCHEMA_CLAIM_V1);
+    // The vault authority's token account for some other mint.
+    let claimant = Claimant {
+        wallet: wallets[0],
+        mint: Pubkey::new_unique(),
+        destination_owner: wallets[0],
+    };
+
+    assert_eq!(
+        claim(&mut commit, &claimant, 0, 100, Commit::proof(&[claims], 0, 0)),
+        Err(moat_error(MoatError::InvalidClaimMint))
+    );
+    assert_eq!(commit.consumed(), 0);
+}
+
+#[test]
+fn commit_acl_limits_who_consumes() {
+    let _clock = at(NOW);
+    let sets = [leaves(4)];
+    let mut commit = Commit::new(&sets, 0);
+    let creator = commit.creator;
+    let stranger = Pubkey::new_unique();
+
+    commit.set_acl(vec![stranger]).unwrap();
+    assert_eq!(commit.batch.load::<BatchCommit>().acl_members, 1);
+    assert_eq!(
+        commit.consume(creator, 0, 1, sets[0][1], Commit::proof(&sets, 0, 1)),
+        Err(moat_error(MoatError::NotOnCommitAcl))
+    );
+
+    commit.set_acl(vec![stranger, creator]).unwrap();
+    commit
+        .consume(creator, 0, 1, sets[0][1], Commit::proof(&sets, 0, 1))
+        .unwrap();
+}
+
+#[test]
+fn commit_acl_is_set_while_pending_without_duplicates() {
+    let _clock = at(NOW);
+    let mut commit = Commit::new(&[leaves(2)], 0);
+    let member = Pubkey::new_unique();
+
+    assert_eq!(
+        commit.set_acl(vec![member, member]),
+        Err(moat_error(MoatError::DuplicateAclMember))
+    );
+    commit.set_acl(vec![member]).unwrap();
+
+    commit.update(|batch| batch.status = COMMIT_STATUS_FINALIZED);
+    assert_eq!(
+        commit.set_acl(Vec::new()),
+        Err(moat_error(MoatError::CommitAlreadyFinalized))
+    );
+    assert_eq!(commit.acl.load::<CommitAcl>().members, vec![member]);
+}
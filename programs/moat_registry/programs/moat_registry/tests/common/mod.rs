@@ -0,0 +1,231 @@
+//! Runs program instructions natively over hand-built accounts, with clock
+//! and rent from the stubs below. CPIs only work on-chain, so tests stop short
+//! of them.
+
+#![allow(dead_code)]
+
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Mutex, MutexGuard, Once};
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::entrypoint::{MAX_PERMITTED_DATA_INCREASE, SUCCESS};
+use anchor_lang::InstructionData;
+use moat_registry::{MoatError, RegistryState, Role};
+use solana_sysvar::program_stubs::{set_syscall_stubs, SyscallStubs};
+
+pub const SLOT: u64 = 1_000;
+
+static NOW: AtomicI64 = AtomicI64::new(0);
+static SERIAL: Mutex<()> = Mutex::new(());
+static STUBS: Once = Once::new();
+
+struct Stubs;
+
+impl SyscallStubs for Stubs {
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        let clock = Clock {
+            slot: SLOT,
+            unix_timestamp: NOW.load(Ordering::SeqCst),
+            ..Clock::default()
+        };
+        unsafe { *(var_addr as *mut Clock) = clock };
+        SUCCESS
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Rent) = Rent::default() };
+        SUCCESS
+    }
+
+    fn sol_log(&self, _message: &str) {}
+
+    fn sol_log_data(&self, _fields: &[&[u8]]) {}
+}
+
+/// Serializes tests that share the stubbed clock and sets it to `now`.
+pub fn at(now: i64) -> MutexGuard<'static, ()> {
+    STUBS.call_once(|| {
+        set_syscall_stubs(Box::new(Stubs));
+    });
+    let guard = SERIAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    NOW.store(now, Ordering::SeqCst);
+    guard
+}
+
+pub struct TestAccount {
+    pub key: Pubkey,
+    pub owner: Pubkey,
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub is_signer: bool,
+    pub is_writable: bool,
+    pub executable: bool,
+}
+
+impl TestAccount {
+    /// A program-owned account holding `value`, padded to `space` bytes.
+    pub fn program<T: AccountSerialize>(key: Pubkey, value: &T, space: usize) -> Self {
+        let mut data = Vec::with_capacity(space);
+        value.try_serialize(&mut data).unwrap();
+        data.resize(space.max(data.len()), 0);
+        Self {
+            key,
+            owner: moat_registry::ID,
+            lamports: 1_000_000_000,
+            data,
+            is_signer: false,
+            is_writable: true,
+            executable: false,
+        }
+    }
+
+    /// An account owned by `owner` with raw `data`, e.g. a token account.
+    pub fn raw(key: Pubkey, owner: Pubkey, data: Vec<u8>) -> Self {
+        Self {
+            key,
+            owner,
+            lamports: 1_000_000_000,
+            data,
+            is_signer: false,
+            is_writable: true,
+            executable: false,
+        }
+    }
+
+    pub fn signer(key: Pubkey) -> Self {
+        Self {
+            is_signer: true,
+            ..Self::raw(key, system_program::ID, Vec::new())
+        }
+    }
+
+    /// An address nothing was ever created at, or whose account was closed.
+    pub fn empty(key: Pubkey) -> Self {
+        Self {
+            lamports: 0,
+            ..Self::raw(key, system_program::ID, Vec::new())
+        }
+    }
+
+    pub fn executable(key: Pubkey) -> Self {
+        Self {
+            is_writable: false,
+            executable: true,
+            ..Self::raw(key, Pubkey::default(), Vec::new())
+        }
+    }
+
+    /// Anchor reads an optional account passed as the program id as absent.
+    pub fn none() -> Self {
+        Self::executable(moat_registry::ID)
+    }
+
+    pub fn load<T: AccountDeserialize>(&self) -> T {
+        T::try_deserialize(&mut &self.data[..]).unwrap()
+    }
+}
+
+/// A `T` with every field zeroed, to be filled in by the test.
+pub fn zeroed<T: AnchorDeserialize + Space>() -> T {
+    T::deserialize(&mut &vec![0u8; T::INIT_SPACE][..]).unwrap()
+}
+
+pub fn pda(seeds: &[&[u8]]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(seeds, &moat_registry::ID)
+}
+
+/// An account laid out as in the runtime's input buffer, so that closing or
+/// resizing it works: the original data length sits in the 4 bytes before the
+/// key, the current one in the 8 bytes before the data, and the data has room
+/// to grow by `MAX_PERMITTED_DATA_INCREASE`.
+struct Serialized {
+    key: Vec<u8>,
+    data: Vec<u64>,
+}
+
+impl Serialized {
+    fn new(account: &TestAccount) -> Self {
+        let len = account.data.len();
+        let mut key = (len as u32).to_le_bytes().to_vec();
+        key.extend_from_slice(account.key.as_ref());
+        let mut data = vec![0u64; 1 + (len + MAX_PERMITTED_DATA_INCREASE).div_ceil(8)];
+        data[0] = len as u64;
+        let mut serialized = Self { key, data };
+        serialized.parts().1.copy_from_slice(&account.data);
+        serialized
+    }
+
+    /// The key, and the data at its current length.
+    fn parts(&mut self) -> (&Pubkey, &mut [u8]) {
+        let key = unsafe { &*(self.key[4..].as_ptr() as *const Pubkey) };
+        let len = self.data[0] as usize;
+        let data = self.data[1..].as_mut_ptr() as *mut u8;
+        (key, unsafe { std::slice::from_raw_parts_mut(data, len) })
+    }
+}
+
+pub fn process(
+    instruction: impl InstructionData,
+    accounts: &mut [TestAccount],
+    remaining: &mut [TestAccount],
+) -> std::result::Result<(), ProgramError> {
+    let mut serialized: Vec<Serialized> = accounts
+        .iter()
+        .chain(remaining.iter())
+        .map(Serialized::new)
+        .collect();
+    let result = {
+        let infos: Vec<AccountInfo> = accounts
+            .iter_mut()
+            .chain(remaining.iter_mut())
+            .zip(serialized.iter_mut())
+            .map(|(account, serialized)| {
+                let (key, data) = serialized.parts();
+                AccountInfo::new(
+                    key,
+                    account.is_signer,
+                    account.is_writable,
+                    &mut account.lamports,
+                    data,
+                    &account.owner,
+                    account.executable,
+                    0,
+                )
+            })
+            .collect();
+        moat_registry::entry(&moat_registry::ID, &infos, &instruction.data())
+    };
+    for (account, serialized) in accounts
+        .iter_mut()
+        .chain(remaining.iter_mut())
+        .zip(serialized.iter_mut())
+    {
+        account.data = serialized.parts().1.to_vec();
+    }
+    result
+}
+
+pub fn moat_error(error: MoatError) -> ProgramError {
+    anchor_lang::error::Error::from(error).into()
+}
+
+/// The registry state PDA with `admin`, adjusted by `configure`.
+pub fn registry_state(admin: Pubkey, configure: impl FnOnce(&mut RegistryState)) -> TestAccount {
+    let (key, bump) = pda(&[b"state"]);
+    let mut state = zeroed::<RegistryState>();
+    state.admin = admin;
+    state.bump = bump;
+    configure(&mut state);
+    TestAccount::program(key, &state, 8 + RegistryState::INIT_SPACE)
+}
+
+/// A role PDA granting `capabilities` to `holder`.
+pub fn role(state: &Pubkey, holder: &Pubkey, capabilities: u8) -> TestAccount {
+    let (key, bump) = pda(&[b"role", state.as_ref(), holder.as_ref()]);
+    let mut role = zeroed::<Role>();
+    role.registry = *state;
+    role.holder = *holder;
+    role.capabilities = capabilities;
+    role.bump = bump;
+    TestAccount::program(key, &role, 8 + Role::INIT_SPACE)
+}
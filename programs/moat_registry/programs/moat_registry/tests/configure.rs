@@ -0,0 +1,164 @@
+mod common;
+
+use anchor_lang::prelude::*;
+use anchor_lang::InstructionData;
+use common::{at, moat_error, registry_state, role, TestAccount};
+use moat_registry::{instruction, MoatError, RegistryState, ROLE_CONFIGURE};
+
+struct Registry {
+    admin: Pubkey,
+    operator: Pubkey,
+    state: TestAccount,
+    role: TestAccount,
+}
+
+impl Registry {
+    /// A registry whose `operator` holds ROLE_CONFIGURE.
+    fn new() -> Self {
+        let admin = Pubkey::new_unique();
+        let operator = Pubkey::new_unique();
+        let state = registry_state(admin, |_| {});
+        let role = role(&state.key, &operator, ROLE_CONFIGURE);
+        Self {
+            admin,
+            operator,
+            state,
+            role,
+        }
+    }
+
+    fn configure(
+        &mut self,
+        authority: Pubkey,
+        ix: impl InstructionData,
+    ) -> std::result::Result<(), ProgramError> {
+        let role = if authority == self.operator {
+            std::mem::replace(&mut self.role, TestAccount::none())
+        } else {
+            TestAccount::none()
+        };
+        let mut accounts = [
+            TestAccount::signer(authority),
+            std::mem::replace(&mut self.state, TestAccount::none()),
+            role,
+            TestAccount::none(),
+            TestAccount::none(),
+            TestAccount::none(),
+        ];
+        let result = common::process(ix, &mut accounts, &mut []);
+        let [_, state, role, ..] = accounts;
+        self.state = state;
+        if authority == self.operator {
+            self.role = role;
+        }
+        result
+    }
+
+    fn state(&self) -> RegistryState {
+        self.state.load()
+    }
+}
+
+#[test]
+fn only_the_admin_sets_the_commit_authority() {
+    let _clock = at(0);
+    let mut registry = Registry::new();
+    let operator = registry.operator;
+
+    assert_eq!(
+        registry.configure(
+            operator,
+            instruction::SetCommitAuthority {
+                commit_authority: operator,
+            },
+        ),
+        Err(moat_error(MoatError::Unauthorized))
+    );
+    registry
+        .configure(
+            registry.admin,
+            instruction::SetCommitAuthority {
+                commit_authority: operator,
+            },
+        )
+        .unwrap();
+    assert_eq!(registry.state().commit_authority, operator);
+}
+
+#[test]
+fn only_the_admin_sets_the_root_attestor() {
+    let _clock = at(0);
+    let mut registry = Registry::new();
+    let attestor = Pubkey::new_unique();
+    registry
+        .configure(
+            registry.admin,
+            instruction::SetRootAttestor {
+                root_attestor: attestor,
+            },
+        )
+        .unwrap();
+
+    // A configure key can't switch attestation off.
+    assert_eq!(
+        registry.configure(
+            registry.operator,
+            instruction::SetRootAttestor {
+                root_attestor: Pubkey::default(),
+            },
+        ),
+        Err(moat_error(MoatError::Unauthorized))
+    );
+    assert_eq!(registry.state().root_attestor, attestor);
+}
+
+#[test]
+fn only_the_admin_sets_the_eth_root_attestor() {
+    let _clock = at(0);
+    let mut registry = Registry::new();
+    registry
+        .configure(
+            registry.admin,
+            instruction::SetRootAttestorEth {
+                root_attestor_eth: [7; 20],
+            },
+        )
+        .unwrap();
+
+    assert_eq!(
+        registry.configure(
+            registry.operator,
+            instruction::SetRootAttestorEth {
+                root_attestor_eth: [0; 20],
+            },
+        ),
+        Err(moat_error(MoatError::Unauthorized))
+    );
+    assert_eq!(registry.state().root_attestor_eth, [7; 20]);
+}
+
+#[test]
+fn only_the_admin_sets_the_commit_verifier() {
+    let _clock = at(0);
+    let mut registry = Registry::new();
+    let verifier = Pubkey::new_unique();
+    registry
+        .configure(
+            registry.admin,
+            instruction::SetCommitVerifier {
+                commit_verifier: verifier,
+            },
+        )
+        .unwrap();
+
+    assert_eq!(
+        registry.configure(
+            registry.operator,
+            instruction::SetCommitVerifier {
+                commit_verifier: Pubkey::default(),
+            },
+        ),
+        Err(moat_error(MoatError::Unauthorized))
+    );
+    assert_eq!(registry.state().commit_verifier, verifier);
+}
@@ -0,0 +1,182 @@
+mod common;
+
+use anchor_lang::error::ErrorCode;
+use anchor_lang::prelude::*;
+use common::{at, moat_error, pda, process, registry_state, zeroed, TestAccount};
+use moat_registry::{instruction, MoatError, MultisigConfig, MultisigProposal};
+
+/// A 2-of-3 multisig with one proposal its first owner made.
+struct Multisig {
+    owners: [Pubkey; 3],
+    signer: Pubkey,
+    state: TestAccount,
+    multisig: TestAccount,
+    proposal: TestAccount,
+}
+
+impl Multisig {
+    fn new(change: impl FnOnce(&mut MultisigProposal)) -> Self {
+        let owners = [(); 3].map(|_| Pubkey::new_unique());
+        let state = registry_state(Pubkey::new_unique(), |_| {});
+
+        let (multisig_key, bump) = pda(&[b"multisig", state.key.as_ref()]);
+        let (signer, signer_bump) = pda(&[b"multisig_signer", multisig_key.as_ref()]);
+        let mut multisig = zeroed::<MultisigConfig>();
+        multisig.registry = state.key;
+        multisig.owners = owners.to_vec();
+        multisig.threshold = 2;
+        multisig.next_proposal_id = 1;
+        multisig.bump = bump;
+        multisig.signer_bump = signer_bump;
+
+        let (proposal_key, bump) = pda(&[b"proposal", multisig_key.as_ref(), &0u64.to_le_bytes()]);
+        let mut proposal = zeroed::<MultisigProposal>();
+        proposal.multisig = multisig_key;
+        proposal.proposer = owners[0];
+        proposal.program_id = moat_registry::ID;
+        proposal.approvals = 1;
+        proposal.bump = bump;
+        change(&mut proposal);
+
+        Self {
+            owners,
+            signer,
+            state,
+            multisig: TestAccount::program(multisig_key, &multisig, 8 + MultisigConfig::INIT_SPACE),
+            proposal: TestAccount::program(
+                proposal_key,
+                &proposal,
+                8 + MultisigProposal::INIT_SPACE,
+            ),
+        }
+    }
+
+    fn proposal(&self) -> MultisigProposal {
+        self.proposal.load()
+    }
+
+    fn approve(&mut self, owner: Pubkey) -> std::result::Result<(), ProgramError> {
+        let mut accounts = [
+            TestAccount::signer(owner),
+            std::mem::replace(&mut self.state, TestAccount::none()),
+            std::mem::replace(&mut self.multisig, TestAccount::none()),
+            std::mem::replace(&mut self.proposal, TestAccount::none()),
+        ];
+        let result = process(
+            instruction::ApproveMultisigAction {},
+            &mut accounts,
+            &mut [],
+        );
+        let [_, state, multisig, proposal] = accounts;
+        (self.state, self.multisig, self.proposal) = (state, multisig, proposal);
+        result
+    }
+
+    /// Runs `execute_multisig_action`. Invoking the stored instruction is a
+    /// CPI, so only proposals that fail the checks before it can run.
+    fn execute(&mut self) -> std::result::Result<(), ProgramError> {
+        let mut accounts = [
+            std::mem::replace(&mut self.state, TestAccount::none()),
+            std::mem::replace(&mut self.multisig, TestAccount::none()),
+            TestAccount::raw(self.signer, system_program::ID, Vec::new()),
+            std::mem::replace(&mut self.proposal, TestAccount::none()),
+        ];
+        let result = process(
+            instruction::ExecuteMultisigAction {},
+            &mut accounts,
+            &mut [],
+        );
+        let [state, multisig, _, proposal] = accounts;
+        (self.state, self.multisig, self.proposal) = (state, multisig, proposal);
+        result
+    }
+
+    /// Runs `set_multisig_owners` signed by `signer`, which only the signer
+    /// PDA can be on-chain.
+    fn set_owners(
+        &mut self,
+        signer: Pubkey,
+        owners: Vec<Pubkey>,
+        threshold: u8,
+    ) -> std::result::Result<(), ProgramError> {
+        let mut accounts = [
+            TestAccount::signer(signer),
+            std::mem::replace(&mut self.state, TestAccount::none()),
+            std::mem::replace(&mut self.multisig, TestAccount::none()),
+        ];
+        let result = process(
+            instruction::SetMultisigOwners { owners, threshold },
+            &mut accounts,
+            &mut [],
+        );
+        let [_, state, multisig] = accounts;
+        (self.state, self.multisig) = (state, multisig);
+        result
+    }
+}
+
+#[test]
+fn proposals_need_threshold_distinct_owners() {
+    let _clock = at(0);
+    let mut multisig = Multisig::new(|_| {});
+    let [first, second, _] = multisig.owners;
+    let not_enough_approvals = Err(moat_error(MoatError::NotEnoughApprovals));
+    assert_eq!(multisig.execute(), not_enough_approvals);
+
+    assert_eq!(
+        multisig.approve(Pubkey::new_unique()),
+        Err(moat_error(MoatError::Unauthorized))
+    );
+    // Approving twice still counts once.
+    multisig.approve(first).unwrap();
+    assert_eq!(multisig.proposal().approvals, 0b001);
+    assert_eq!(multisig.execute(), not_enough_approvals);
+
+    multisig.approve(second).unwrap();
+    assert_eq!(multisig.proposal().approvals, 0b011);
+    assert!(!multisig.proposal().executed);
+}
+
+#[test]
+fn executed_proposals_take_no_more_approvals() {
+    let _clock = at(0);
+    let mut multisig = Multisig::new(|proposal| {
+        proposal.approvals = 0b011;
+        proposal.executed = true;
+    });
+    let already_executed = Err(moat_error(MoatError::ProposalAlreadyExecuted));
+    assert_eq!(multisig.approve(multisig.owners[2]), already_executed);
+    assert_eq!(multisig.execute(), already_executed);
+}
+
+#[test]
+fn changing_owners_stales_pending_proposals() {
+    let _clock = at(0);
+    let mut multisig = Multisig::new(|proposal| proposal.approvals = 0b011);
+    let [first, second, third] = multisig.owners;
+    let signer = multisig.signer;
+
+    assert_eq!(
+        multisig.set_owners(first, vec![first, second], 1),
+        Err(anchor_lang::error::Error::from(ErrorCode::ConstraintSeeds).into())
+    );
+    assert_eq!(
+        multisig.set_owners(signer, vec![first, first], 1),
+        Err(moat_error(MoatError::InvalidMultisigOwners))
+    );
+    for threshold in [0, 3] {
+        assert_eq!(
+            multisig.set_owners(signer, vec![first, second], threshold),
+            Err(moat_error(MoatError::InvalidThreshold))
+        );
+    }
+
+    multisig.set_owners(signer, vec![first, third], 1).unwrap();
+    let config: MultisigConfig = multisig.multisig.load();
+    assert_eq!(config.owners, vec![first, third]);
+    assert_eq!((config.threshold, config.owner_set_seqno), (1, 1));
+
+    let stale = Err(moat_error(MoatError::StaleProposal));
+    assert_eq!(multisig.approve(third), stale);
+    assert_eq!(multisig.execute(), stale);
+}
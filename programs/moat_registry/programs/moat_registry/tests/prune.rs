@@ -0,0 +1,185 @@
+mod common;
+
+use anchor_lang::prelude::*;
+use common::{at, moat_error, pda, process, registry_state, zeroed, TestAccount};
+use moat_registry::{
+    instruction, BatchCommit, CommitTombstone, MoatError, RegistryEntry, COMMIT_STATUS_FINALIZED,
+    TOKEN_PROGRAM_ID,
+};
+use solana_sha256_hasher::hashv;
+
+const RETENTION: i64 = 1_000;
+const STAKE: u64 = 50_000;
+
+struct Pruned {
+    entry_admin: Pubkey,
+    creator: TestAccount,
+    admin: TestAccount,
+    state: TestAccount,
+    entry: TestAccount,
+    batch: TestAccount,
+}
+
+impl Pruned {
+    /// A finalized commit created at time 0 under a registry that keeps
+    /// commits for `RETENTION` seconds.
+    fn new(change: impl FnOnce(&mut BatchCommit)) -> Self {
+        let admin = Pubkey::new_unique();
+        let entry_admin = Pubkey::new_unique();
+        let creator = Pubkey::new_unique();
+        let state = registry_state(admin, |state| state.commit_retention = RETENTION);
+
+        let (entry_key, bump) = pda(&[b"entry", state.key.as_ref(), &0u32.to_le_bytes()]);
+        let mut entry = zeroed::<RegistryEntry>();
+        entry.registry = state.key;
+        entry.admin = entry_admin;
+        entry.bump = bump;
+
+        let (batch_key, bump) = pda(&[b"commit", entry_key.as_ref(), &0u64.to_le_bytes()]);
+        let mut commit = zeroed::<BatchCommit>();
+        commit.id = 7;
+        commit.creator = creator;
+        commit.entry = entry_key;
+        commit.status = COMMIT_STATUS_FINALIZED;
+        commit.stake = STAKE;
+        commit.bump = bump;
+        change(&mut commit);
+
+        Self {
+            entry_admin,
+            creator: TestAccount::raw(creator, system_program::ID, Vec::new()),
+            admin: TestAccount::raw(admin, system_program::ID, Vec::new()),
+            state,
+            entry: TestAccount::program(entry_key, &entry, 8 + RegistryEntry::INIT_SPACE),
+            batch: TestAccount::program(batch_key, &commit, 8 + BatchCommit::INIT_SPACE),
+        }
+    }
+
+    fn prune(&mut self) -> std::result::Result<(), ProgramError> {
+        let batch = self.batch.key;
+        let sidecar = |seed: &[u8]| TestAccount::empty(pda(&[seed, batch.as_ref()]).0);
+        let mut accounts = [
+            std::mem::replace(&mut self.state, TestAccount::none()),
+            std::mem::replace(&mut self.batch, TestAccount::none()),
+            std::mem::replace(&mut self.creator, TestAccount::none()),
+            std::mem::replace(&mut self.admin, TestAccount::none()),
+            sidecar(b"claims"),
+            sidecar(b"acl"),
+            sidecar(b"exclusions"),
+            sidecar(b"annotations"),
+        ];
+        let result = process(instruction::PruneCommit {}, &mut accounts, &mut []);
+        let [state, batch, creator, admin, ..] = accounts;
+        (self.state, self.batch, self.creator, self.admin) = (state, batch, creator, admin);
+        result
+    }
+
+    /// Sweeps a vault into a token account of `destination_owner`. The
+    /// transfer itself is a CPI, so only the checks before it can run.
+    fn sweep(
+        &mut self,
+        recipient: Pubkey,
+        seq: u64,
+        destination_owner: Pubkey,
+    ) -> std::result::Result<(), ProgramError> {
+        let mint = Pubkey::new_unique();
+        let (vault_authority, _) = pda(&[b"vault", self.batch.key.as_ref()]);
+        let token_account = |owner: Pubkey| {
+            let data = [mint.to_bytes(), owner.to_bytes()].concat();
+            TestAccount::raw(Pubkey::new_unique(), TOKEN_PROGRAM_ID, data)
+        };
+        let mut accounts = [
+            TestAccount::signer(recipient),
+            std::mem::replace(&mut self.state, TestAccount::none()),
+            std::mem::replace(&mut self.entry, TestAccount::none()),
+            std::mem::replace(&mut self.batch, TestAccount::none()),
+            TestAccount::raw(vault_authority, system_program::ID, Vec::new()),
+            token_account(vault_authority),
+            token_account(destination_owner),
+            TestAccount::raw(mint, TOKEN_PROGRAM_ID, Vec::new()),
+            TestAccount::executable(TOKEN_PROGRAM_ID),
+        ];
+        let result = process(instruction::SweepVault { seq }, &mut accounts, &mut []);
+        let [_, state, entry, batch, ..] = accounts;
+        (self.state, self.entry, self.batch) = (state, entry, batch);
+        result
+    }
+}
+
+#[test]
+fn prune_leaves_a_40_byte_tombstone() {
+    let clock = at(RETENTION - 1);
+    let mut pruned = Pruned::new(|_| {});
+    assert_eq!(
+        pruned.prune(),
+        Err(moat_error(MoatError::RetentionNotElapsed))
+    );
+
+    drop(clock);
+    let _clock = at(RETENTION);
+    let record_hash = hashv(&[&pruned.batch.data]).to_bytes();
+    let lamports = pruned.batch.lamports;
+    pruned.prune().unwrap();
+
+    assert_eq!(pruned.batch.data.len(), 8 + 40);
+    let tombstone: CommitTombstone = pruned.batch.load();
+    assert_eq!((tombstone.id, tombstone.record_hash), (7, record_hash));
+    let rent = Rent::default().minimum_balance(8 + 40);
+    assert_eq!(pruned.batch.lamports, rent);
+    assert_eq!(pruned.creator.lamports, 1_000_000_000 + lamports - rent);
+    assert_eq!(pruned.admin.lamports, 1_000_000_000);
+}
+
+#[test]
+fn pruning_a_revoked_commit_forfeits_its_stake() {
+    let _clock = at(RETENTION);
+    let mut pruned = Pruned::new(|commit| commit.revoked = true);
+    let lamports = pruned.batch.lamports;
+    pruned.prune().unwrap();
+
+    let rent = Rent::default().minimum_balance(8 + CommitTombstone::INIT_SPACE);
+    assert_eq!(pruned.admin.lamports, 1_000_000_000 + STAKE);
+    assert_eq!(
+        pruned.creator.lamports,
+        1_000_000_000 + lamports - rent - STAKE
+    );
+}
+
+#[test]
+fn vaults_sweep_to_the_creator_then_the_entry_admin() {
+    let clock = at(RETENTION - 1);
+    let mut pruned = Pruned::new(|_| {});
+    let creator = pruned.creator.key;
+    let entry_admin = pruned.entry_admin;
+    assert_eq!(
+        pruned.sweep(creator, 0, creator),
+        Err(moat_error(MoatError::RetentionNotElapsed))
+    );
+
+    drop(clock);
+    let _clock = at(RETENTION);
+    assert_eq!(
+        pruned.sweep(entry_admin, 0, entry_admin),
+        Err(moat_error(MoatError::Unauthorized))
+    );
+    // The checks pass up to the destination, which must be the creator's.
+    assert_eq!(
+        pruned.sweep(creator, 0, entry_admin),
+        Err(moat_error(MoatError::InvalidTokenAccount))
+    );
+
+    // The tombstone no longer names the creator.
+    pruned.prune().unwrap();
+    assert_eq!(
+        pruned.sweep(creator, 0, creator),
+        Err(moat_error(MoatError::Unauthorized))
+    );
+    assert_eq!(
+        pruned.sweep(entry_admin, 1, entry_admin),
+        Err(moat_error(MoatError::CommitEntryMismatch))
+    );
+    assert_eq!(
+        pruned.sweep(entry_admin, 0, creator),
+        Err(moat_error(MoatError::InvalidTokenAccount))
+    );
+}
@@ -0,0 +1,194 @@
+mod common;
+
+use anchor_lang::prelude::*;
+use common::{at, moat_error, pda, process, zeroed, TestAccount};
+use moat_registry::{
+    commitment_domain, instruction, merkle_tree, CommitHasher, MoatError, RegistryEntry,
+    RegistryRoot, RegistryState, ENTRY_STATUS_ACTIVE, ENTRY_STATUS_REVOKED, HASH_ALG_SHA256,
+    ROOT_VERSION_V2,
+};
+
+const NOW: i64 = 1_700_000_000;
+
+struct Registry {
+    admin: Pubkey,
+    state: TestAccount,
+    registry_root: TestAccount,
+    entries: Vec<RegistryEntry>,
+}
+
+impl Registry {
+    fn new(entries: usize) -> Self {
+        let admin = Pubkey::new_unique();
+        let (state_key, bump) = pda(&[b"state"]);
+        let mut state = zeroed::<RegistryState>();
+        state.admin = admin;
+        state.next_id = entries as u64;
+        state.bump = bump;
+
+        let (root_key, bump) = pda(&[b"registry_root", state_key.as_ref()]);
+        let mut root = zeroed::<RegistryRoot>();
+        root.registry = state_key;
+        root.bump = bump;
+
+        let entries = (0..entries as u32)
+            .map(|id| {
+                let mut entry = zeroed::<RegistryEntry>();
+                entry.registry = state_key;
+                entry.id = id;
+                entry.bump = Self::entry_address(&state_key, id).1;
+                entry.target_program = Pubkey::new_unique();
+                entry.kind = id as u8;
+                entry.tags = 1 << id;
+                entry.status = ENTRY_STATUS_ACTIVE;
+                entry
+            })
+            .collect();
+
+        Self {
+            admin,
+            state: TestAccount::program(state_key, &state, 8 + RegistryState::INIT_SPACE),
+            registry_root: TestAccount::program(root_key, &root, 8 + RegistryRoot::INIT_SPACE),
+            entries,
+        }
+    }
+
+    fn entry_address(state: &Pubkey, id: u32) -> (Pubkey, u8) {
+        pda(&[b"entry", state.as_ref(), &id.to_le_bytes()])
+    }
+
+    fn entry_account(&self, id: u32) -> TestAccount {
+        let key = Self::entry_address(&self.state.key, id).0;
+        TestAccount::program(key, &self.entries[id as usize], 8 + RegistryEntry::INIT_SPACE)
+    }
+
+    fn commit(
+        &mut self,
+        restart: bool,
+        remaining: &mut [TestAccount],
+    ) -> std::result::Result<(), ProgramError> {
+        let mut accounts = [
+            TestAccount::signer(self.admin),
+            TestAccount::signer(Pubkey::new_unique()),
+            std::mem::replace(&mut self.state, TestAccount::none()),
+            TestAccount::none(),
+            TestAccount::none(),
+            TestAccount::none(),
+            std::mem::replace(&mut self.registry_root, TestAccount::none()),
+            TestAccount::none(),
+            TestAccount::executable(system_program::ID),
+        ];
+        let result = process(
+            instruction::CommitRegistryRoot { restart },
+            &mut accounts,
+            remaining,
+        );
+        let [_, _, state, _, _, _, registry_root, ..] = accounts;
+        (self.state, self.registry_root) = (state, registry_root);
+        result
+    }
+
+    fn root(&self) -> RegistryRoot {
+        self.registry_root.load()
+    }
+
+    fn expected_root(&self, ids: &[u32]) -> ([u8; 32], u8) {
+        let hasher = CommitHasher::new(HASH_ALG_SHA256).unwrap();
+        let domain = commitment_domain(&self.state.key);
+        let leaves: Vec<[u8; 32]> = ids
+            .iter()
+            .map(|&id| {
+                let entry = &self.entries[id as usize];
+                moat_merkle::hash_schema_leaf(
+                    &hasher,
+                    &domain,
+                    &moat_merkle::EntryLeafV1 {
+                        id: entry.id,
+                        target_program: entry.target_program.to_bytes(),
+                        kind: entry.kind,
+                        tags: entry.tags,
+                        expires_at: entry.expires_at,
+                    },
+                )
+            })
+            .collect();
+        let tree = merkle_tree(ROOT_VERSION_V2, false).unwrap();
+        moat_merkle::compute_root(&hasher, tree, &leaves).unwrap()
+    }
+}
+
+#[test]
+fn commit_registry_root_folds_live_entries_across_calls() {
+    let _clock = at(NOW);
+    let mut registry = Registry::new(6);
+    registry.entries[1].expires_at = NOW - 1;
+    registry.entries[3].status = ENTRY_STATUS_REVOKED;
+    registry.entries[5].expires_at = NOW + 60;
+
+    let closed = Registry::entry_address(&registry.state.key, 2).0;
+    let mut first = [
+        registry.entry_account(0),
+        registry.entry_account(1),
+        TestAccount::empty(closed),
+    ];
+    registry.commit(false, &mut first).unwrap();
+    let pending = registry.root();
+    assert_eq!((pending.cursor, pending.pending_count, pending.version), (3, 1, 0));
+
+    let mut rest = [
+        registry.entry_account(3),
+        registry.entry_account(4),
+        registry.entry_account(5),
+    ];
+    registry.commit(false, &mut rest).unwrap();
+    let published = registry.root();
+    let (root, depth) = registry.expected_root(&[0, 4, 5]);
+    assert_eq!(published.root, root);
+    assert_eq!(published.depth, depth);
+    assert_eq!(published.entry_count, 3);
+    assert_eq!(published.version, 1);
+    assert_eq!((published.cursor, published.pending_count), (0, 0));
+}
+
+#[test]
+fn commit_registry_root_rejects_skipped_ids() {
+    let _clock = at(NOW);
+    let mut registry = Registry::new(3);
+
+    let mut skipping = [registry.entry_account(0), registry.entry_account(2)];
+    assert_eq!(
+        registry.commit(false, &mut skipping),
+        Err(moat_error(MoatError::InvalidRegistryRootEntry))
+    );
+
+    // A live entry can't be passed off as closed by handing over an empty
+    // account at some other address.
+    let mut hidden = [
+        registry.entry_account(0),
+        TestAccount::empty(Pubkey::new_unique()),
+        registry.entry_account(2),
+    ];
+    assert_eq!(
+        registry.commit(false, &mut hidden),
+        Err(moat_error(MoatError::InvalidRegistryRootEntry))
+    );
+    assert_eq!(registry.root().version, 0);
+}
+
+#[test]
+fn commit_registry_root_restarts_a_build() {
+    let _clock = at(NOW);
+    let mut registry = Registry::new(2);
+
+    registry.commit(false, &mut [registry.entry_account(0)]).unwrap();
+    assert_eq!(registry.root().cursor, 1);
+
+    let mut all = [registry.entry_account(0), registry.entry_account(1)];
+    assert_eq!(
+        registry.commit(false, &mut all),
+        Err(moat_error(MoatError::InvalidRegistryRootEntry))
+    );
+    registry.commit(true, &mut all).unwrap();
+    assert_eq!(registry.root().root, registry.expected_root(&[0, 1]).0);
+    assert_eq!(registry.root().entry_count, 2);
+}
@@ -0,0 +1,143 @@
+mod common;
+
+use anchor_lang::prelude::*;
+use common::{at, moat_error, pda, process, registry_state, role, zeroed, TestAccount};
+use moat_registry::{
+    instruction, MoatError, RegistryState, Role, ROLE_ALL, ROLE_COMMIT, ROLE_CONFIGURE,
+    ROLE_PAUSE,
+};
+
+/// Sets the commit interval, a ROLE_CONFIGURE action, as `authority` holding
+/// `role`.
+fn configure(
+    state: &mut TestAccount,
+    authority: Pubkey,
+    role: TestAccount,
+) -> std::result::Result<(), ProgramError> {
+    let mut accounts = [
+        TestAccount::signer(authority),
+        std::mem::replace(state, TestAccount::none()),
+        role,
+        TestAccount::none(),
+        TestAccount::none(),
+        TestAccount::none(),
+    ];
+    let result = process(
+        instruction::SetMinCommitInterval {
+            min_commit_interval_slots: 5,
+        },
+        &mut accounts,
+        &mut [],
+    );
+    let [_, registry, ..] = accounts;
+    *state = registry;
+    result
+}
+
+fn grant(
+    state: &mut TestAccount,
+    authority: Pubkey,
+    role: &mut TestAccount,
+    holder: Pubkey,
+    capabilities: u8,
+) -> std::result::Result<(), ProgramError> {
+    let mut accounts = [
+        TestAccount::signer(authority),
+        TestAccount::signer(authority),
+        std::mem::replace(state, TestAccount::none()),
+        std::mem::replace(role, TestAccount::none()),
+        TestAccount::none(),
+        TestAccount::executable(system_program::ID),
+    ];
+    let result = process(
+        instruction::GrantRole {
+            holder,
+            capabilities,
+        },
+        &mut accounts,
+        &mut [],
+    );
+    let [_, _, registry, granted, ..] = accounts;
+    (*state, *role) = (registry, granted);
+    result
+}
+
+#[test]
+fn roles_admit_only_their_capabilities() {
+    let _clock = at(0);
+    let admin = Pubkey::new_unique();
+    let operator = Pubkey::new_unique();
+    let mut state = registry_state(admin, |_| {});
+
+    let committer = role(&state.key, &operator, ROLE_COMMIT | ROLE_PAUSE);
+    assert_eq!(
+        configure(&mut state, operator, committer),
+        Err(moat_error(MoatError::Unauthorized))
+    );
+    let configurer = role(&state.key, &operator, ROLE_CONFIGURE);
+    configure(&mut state, operator, configurer).unwrap();
+    assert_eq!(state.load::<RegistryState>().min_commit_interval_slots, 5);
+
+    // The admin needs no role.
+    configure(&mut state, admin, TestAccount::none()).unwrap();
+}
+
+#[test]
+fn only_the_admin_grants_known_capabilities() {
+    let _clock = at(0);
+    let admin = Pubkey::new_unique();
+    let holder = Pubkey::new_unique();
+    let mut state = registry_state(admin, |_| {});
+    // Already allocated, so `init_if_needed` stops short of the system program.
+    let (key, _) = pda(&[b"role", state.key.as_ref(), holder.as_ref()]);
+    let mut granted = TestAccount::program(key, &zeroed::<Role>(), 8 + Role::INIT_SPACE);
+
+    assert_eq!(
+        grant(&mut state, holder, &mut granted, holder, ROLE_COMMIT),
+        Err(moat_error(MoatError::Unauthorized))
+    );
+    for capabilities in [0, ROLE_ALL + 1] {
+        assert_eq!(
+            grant(&mut state, admin, &mut granted, holder, capabilities),
+            Err(moat_error(MoatError::InvalidCapabilities))
+        );
+    }
+
+    grant(&mut state, admin, &mut granted, holder, ROLE_COMMIT).unwrap();
+    grant(&mut state, admin, &mut granted, holder, ROLE_PAUSE).unwrap();
+    let role: Role = granted.load();
+    assert_eq!((role.holder, role.capabilities), (holder, ROLE_PAUSE));
+}
+
+#[test]
+fn revoking_a_role_closes_it() {
+    let _clock = at(0);
+    let admin = Pubkey::new_unique();
+    let holder = Pubkey::new_unique();
+    let state = registry_state(admin, |_| {});
+    let granted = role(&state.key, &holder, ROLE_CONFIGURE);
+    let rent = granted.lamports;
+
+    let revoke = |authority: Pubkey, state: TestAccount, granted: TestAccount| {
+        let mut accounts = [
+            TestAccount::signer(authority),
+            state,
+            granted,
+            TestAccount::none(),
+        ];
+        let result = process(instruction::RevokeRole {}, &mut accounts, &mut []);
+        let [signer, state, granted, _] = accounts;
+        (result, signer, state, granted)
+    };
+    let (result, _, state, granted) = revoke(holder, state, granted);
+    assert_eq!(result, Err(moat_error(MoatError::Unauthorized)));
+
+    let (result, signer, mut state, granted) = revoke(admin, state, granted);
+    result.unwrap();
+    assert_eq!(signer.lamports, 1_000_000_000 + rent);
+    assert!(granted.data.is_empty());
+    assert_eq!(
+        configure(&mut state, holder, TestAccount::none()),
+        Err(moat_error(MoatError::Unauthorized))
+    );
+}
@@ -0,0 +1,173 @@
+mod common;
+
+use anchor_lang::prelude::*;
+use common::{at, moat_error, pda, process, registry_state, zeroed, TestAccount, SLOT};
+use moat_registry::{
+    instruction, AuthorityNonce, MoatError, RegistryState, Session, ROLE_CONFIGURE,
+};
+
+struct Operator {
+    key: Pubkey,
+    admin: Pubkey,
+    state: TestAccount,
+    session: TestAccount,
+    nonce: TestAccount,
+}
+
+impl Operator {
+    /// A session key holding ROLE_CONFIGURE until `expires_at_slot`, with a
+    /// nonce account that was never consumed.
+    fn new(expires_at_slot: u64) -> Self {
+        let key = Pubkey::new_unique();
+        let admin = Pubkey::new_unique();
+        let state = registry_state(admin, |_| {});
+
+        let (session_key, bump) = pda(&[b"session", state.key.as_ref(), key.as_ref()]);
+        let mut session = zeroed::<Session>();
+        session.registry = state.key;
+        session.session_key = key;
+        session.capabilities = ROLE_CONFIGURE;
+        session.expires_at_slot = expires_at_slot;
+        session.bump = bump;
+
+        // Allocated, so `init_if_needed` stops short of the system program.
+        let (nonce_key, bump) = pda(&[b"nonce", state.key.as_ref(), key.as_ref()]);
+        let mut nonce = zeroed::<AuthorityNonce>();
+        nonce.bump = bump;
+
+        Self {
+            key,
+            admin,
+            session: TestAccount::program(session_key, &session, 8 + Session::INIT_SPACE),
+            nonce: TestAccount::program(nonce_key, &nonce, 8 + AuthorityNonce::INIT_SPACE),
+            state,
+        }
+    }
+
+    fn nonce(&self) -> AuthorityNonce {
+        self.nonce.load()
+    }
+
+    /// Sets the commit interval to `slots` through the session, passing the
+    /// nonce account when `with_nonce`.
+    fn configure(
+        &mut self,
+        slots: u64,
+        with_nonce: bool,
+    ) -> std::result::Result<(), ProgramError> {
+        let nonce = if with_nonce {
+            std::mem::replace(&mut self.nonce, TestAccount::none())
+        } else {
+            TestAccount::none()
+        };
+        let mut accounts = [
+            TestAccount::signer(self.key),
+            std::mem::replace(&mut self.state, TestAccount::none()),
+            TestAccount::none(),
+            std::mem::replace(&mut self.session, TestAccount::none()),
+            nonce,
+            TestAccount::none(),
+        ];
+        let result = process(
+            instruction::SetMinCommitInterval {
+                min_commit_interval_slots: slots,
+            },
+            &mut accounts,
+            &mut [],
+        );
+        let [_, state, _, session, nonce, _] = accounts;
+        (self.state, self.session) = (state, session);
+        if with_nonce {
+            self.nonce = nonce;
+        }
+        result
+    }
+
+    fn consume_nonce(&mut self, expected: u64) -> std::result::Result<(), ProgramError> {
+        let mut accounts = [
+            TestAccount::signer(self.key),
+            TestAccount::signer(self.key),
+            std::mem::replace(&mut self.state, TestAccount::none()),
+            std::mem::replace(&mut self.nonce, TestAccount::none()),
+            TestAccount::executable(system_program::ID),
+        ];
+        let result = process(instruction::ConsumeNonce { expected }, &mut accounts, &mut []);
+        let [_, _, state, nonce, _] = accounts;
+        (self.state, self.nonce) = (state, nonce);
+        result
+    }
+}
+
+#[test]
+fn session_actions_spend_a_consumed_nonce() {
+    let _clock = at(0);
+    let mut operator = Operator::new(SLOT + 10);
+    let nonce_not_consumed = Err(moat_error(MoatError::NonceNotConsumed));
+    assert_eq!(operator.configure(5, false), nonce_not_consumed);
+    assert_eq!(operator.configure(5, true), nonce_not_consumed);
+
+    assert_eq!(
+        operator.consume_nonce(1),
+        Err(moat_error(MoatError::InvalidNonce))
+    );
+    operator.consume_nonce(0).unwrap();
+    let nonce = operator.nonce();
+    assert_eq!((nonce.authority, nonce.nonce, nonce.armed), (operator.key, 1, true));
+
+    operator.configure(5, true).unwrap();
+    assert_eq!(operator.state.load::<RegistryState>().min_commit_interval_slots, 5);
+    assert!(!operator.nonce().armed);
+
+    // Replaying the action needs the next nonce, not the spent one.
+    assert_eq!(operator.configure(6, true), nonce_not_consumed);
+    assert_eq!(
+        operator.consume_nonce(0),
+        Err(moat_error(MoatError::InvalidNonce))
+    );
+    operator.consume_nonce(1).unwrap();
+    operator.configure(6, true).unwrap();
+    assert_eq!(operator.state.load::<RegistryState>().min_commit_interval_slots, 6);
+}
+
+#[test]
+fn sessions_expire_after_their_slot() {
+    let _clock = at(0);
+    let mut operator = Operator::new(SLOT);
+    operator.consume_nonce(0).unwrap();
+    operator.configure(5, true).unwrap();
+
+    let mut operator = Operator::new(SLOT - 1);
+    operator.consume_nonce(0).unwrap();
+    assert_eq!(
+        operator.configure(5, true),
+        Err(moat_error(MoatError::Unauthorized))
+    );
+    // The unused nonce stays armed for the next action.
+    assert!(operator.nonce().armed);
+}
+
+#[test]
+fn only_the_admin_revokes_sessions() {
+    let _clock = at(0);
+    let operator = Operator::new(SLOT + 10);
+    let rent = operator.session.lamports;
+
+    let revoke = |authority: Pubkey, state: TestAccount, session: TestAccount| {
+        let mut accounts = [
+            TestAccount::signer(authority),
+            state,
+            session,
+            TestAccount::none(),
+        ];
+        let result = process(instruction::RevokeSession {}, &mut accounts, &mut []);
+        let [signer, state, session, _] = accounts;
+        (result, signer, state, session)
+    };
+    let (result, _, state, session) = revoke(operator.key, operator.state, operator.session);
+    assert_eq!(result, Err(moat_error(MoatError::Unauthorized)));
+
+    let (result, signer, _, session) = revoke(operator.admin, state, session);
+    result.unwrap();
+    assert_eq!(signer.lamports, 1_000_000_000 + rent);
+    assert!(session.data.is_empty());
+}
@@ -0,0 +1,231 @@
+mod common;
+
+use anchor_lang::prelude::*;
+use anchor_lang::InstructionData;
+use common::{at, moat_error, pda, process, registry_state, zeroed, TestAccount};
+use moat_registry::{
+    instruction, KindDefinition, MoatError, PendingAction, RegistryState, TimelockAction,
+};
+
+const DELAY: i64 = 3_600;
+
+/// A registry with a `DELAY` timelock and one `action` its admin queued at
+/// time 0.
+struct Timelock {
+    admin: Pubkey,
+    state: TestAccount,
+    pending: TestAccount,
+    queued_by: TestAccount,
+}
+
+impl Timelock {
+    fn new(action: TimelockAction) -> Self {
+        let admin = Pubkey::new_unique();
+        let state = registry_state(admin, |state| state.timelock_delay = DELAY);
+
+        let (key, bump) = pda(&[b"action", state.key.as_ref(), &0u64.to_le_bytes()]);
+        let mut pending = zeroed::<PendingAction>();
+        pending.registry = state.key;
+        pending.queued_by = admin;
+        pending.action = action;
+        pending.eta = DELAY;
+        pending.bump = bump;
+
+        Self {
+            admin,
+            state,
+            pending: TestAccount::program(key, &pending, 8 + PendingAction::INIT_SPACE),
+            queued_by: TestAccount::raw(admin, system_program::ID, Vec::new()),
+        }
+    }
+
+    /// A kind definition PDA of this registry.
+    fn kind(&self, kind: u8) -> TestAccount {
+        let (key, bump) = pda(&[b"kind", self.state.key.as_ref(), &[kind]]);
+        let mut definition = zeroed::<KindDefinition>();
+        definition.registry = self.state.key;
+        definition.kind = kind;
+        definition.bump = bump;
+        TestAccount::program(key, &definition, 8 + KindDefinition::INIT_SPACE)
+    }
+
+    fn state(&self) -> RegistryState {
+        self.state.load()
+    }
+
+    /// Runs `execute_action`, with `kind` passed for `UpdateKind` actions.
+    fn execute(&mut self, kind: &mut TestAccount) -> std::result::Result<(), ProgramError> {
+        let mut accounts = [
+            std::mem::replace(&mut self.state, TestAccount::none()),
+            std::mem::replace(&mut self.pending, TestAccount::none()),
+            std::mem::replace(&mut self.queued_by, TestAccount::none()),
+            std::mem::replace(kind, TestAccount::none()),
+            TestAccount::none(),
+        ];
+        let result = process(instruction::ExecuteAction {}, &mut accounts, &mut []);
+        let [state, pending, queued_by, definition, _] = accounts;
+        (self.state, self.pending, self.queued_by, *kind) = (state, pending, queued_by, definition);
+        result
+    }
+
+    fn cancel(&mut self, authority: &mut TestAccount) -> std::result::Result<(), ProgramError> {
+        let mut accounts = [
+            std::mem::replace(authority, TestAccount::none()),
+            std::mem::replace(&mut self.state, TestAccount::none()),
+            std::mem::replace(&mut self.pending, TestAccount::none()),
+            TestAccount::none(),
+        ];
+        let result = process(instruction::CancelAction {}, &mut accounts, &mut []);
+        let [signer, state, pending, _] = accounts;
+        (*authority, self.state, self.pending) = (signer, state, pending);
+        result
+    }
+
+    /// Runs a `ConfigureRegistry` instruction as the admin.
+    fn configure(&mut self, ix: impl InstructionData) -> std::result::Result<(), ProgramError> {
+        let mut accounts = [
+            TestAccount::signer(self.admin),
+            std::mem::replace(&mut self.state, TestAccount::none()),
+            TestAccount::none(),
+            TestAccount::none(),
+            TestAccount::none(),
+            TestAccount::none(),
+        ];
+        let result = process(ix, &mut accounts, &mut []);
+        let [_, state, ..] = accounts;
+        self.state = state;
+        result
+    }
+
+    /// Runs `update_kind` on `kind` as the admin.
+    fn update_kind(&mut self, kind: u8) -> std::result::Result<(), ProgramError> {
+        let definition = self.kind(kind);
+        let mut accounts = [
+            TestAccount::signer(self.admin),
+            std::mem::replace(&mut self.state, TestAccount::none()),
+            TestAccount::none(),
+            TestAccount::none(),
+            TestAccount::none(),
+            definition,
+            TestAccount::none(),
+        ];
+        let ix = instruction::UpdateKind {
+            label: "spam".to_string(),
+            enabled: false,
+        };
+        let result = process(ix, &mut accounts, &mut []);
+        let [_, state, ..] = accounts;
+        self.state = state;
+        result
+    }
+}
+
+#[test]
+fn sensitive_changes_need_a_queued_action() {
+    let _clock = at(0);
+    let mut timelock = Timelock::new(TimelockAction::SetTimelockDelay { delay: 0 });
+    let timelock_required = Err(moat_error(MoatError::TimelockRequired));
+
+    assert_eq!(
+        timelock.configure(instruction::ProposeAdmin {
+            new_admin: Pubkey::new_unique(),
+        }),
+        timelock_required
+    );
+    assert_eq!(
+        timelock.configure(instruction::SetTimelockDelay { delay: 0 }),
+        timelock_required
+    );
+    assert_eq!(timelock.update_kind(3), timelock_required);
+    assert_eq!(timelock.state().timelock_delay, DELAY);
+}
+
+#[test]
+fn dispute_bonds_wait_for_the_timelock() {
+    let clock = at(0);
+    let mut timelock = Timelock::new(TimelockAction::SetDisputeBonds {
+        challenge_bond: 5,
+        commit_stake: 7,
+    });
+    assert_eq!(
+        timelock.configure(instruction::SetDisputeBonds {
+            challenge_bond: 5,
+            commit_stake: 7,
+        }),
+        Err(moat_error(MoatError::TimelockRequired))
+    );
+    assert_eq!(
+        timelock.execute(&mut TestAccount::none()),
+        Err(moat_error(MoatError::TimelockNotElapsed))
+    );
+
+    drop(clock);
+    let _clock = at(DELAY);
+    timelock.execute(&mut TestAccount::none()).unwrap();
+    let state = timelock.state();
+    assert_eq!((state.challenge_bond, state.commit_stake), (5, 7));
+    assert!(timelock.pending.lamports == 0 && timelock.pending.data.is_empty());
+}
+
+#[test]
+fn executed_actions_refund_whoever_queued_them() {
+    let _clock = at(DELAY);
+    let new_admin = Pubkey::new_unique();
+    let mut timelock = Timelock::new(TimelockAction::ProposeAdmin { new_admin });
+    let rent = timelock.pending.lamports;
+    timelock.execute(&mut TestAccount::none()).unwrap();
+
+    let state = timelock.state();
+    assert_eq!((state.admin, state.pending_admin), (timelock.admin, new_admin));
+    assert_eq!(timelock.queued_by.lamports, 1_000_000_000 + rent);
+    assert_eq!(timelock.pending.owner, system_program::ID);
+
+    // The delay itself only changes through an action.
+    let mut timelock = Timelock::new(TimelockAction::SetTimelockDelay { delay: 60 });
+    timelock.execute(&mut TestAccount::none()).unwrap();
+    assert_eq!(timelock.state().timelock_delay, 60);
+}
+
+#[test]
+fn update_kind_actions_need_their_kind_definition() {
+    let _clock = at(DELAY);
+    let mut timelock = Timelock::new(TimelockAction::UpdateKind {
+        kind: 3,
+        label: "loans".to_string(),
+        enabled: true,
+    });
+    assert_eq!(
+        timelock.execute(&mut TestAccount::none()),
+        Err(moat_error(MoatError::InvalidActionAccounts))
+    );
+    let mut other = timelock.kind(4);
+    assert_eq!(
+        timelock.execute(&mut other),
+        Err(moat_error(MoatError::InvalidActionAccounts))
+    );
+
+    let mut kind = timelock.kind(3);
+    timelock.execute(&mut kind).unwrap();
+    let definition: KindDefinition = kind.load();
+    assert_eq!(definition.label, "loans");
+    assert!(definition.enabled);
+}
+
+#[test]
+fn only_the_admin_cancels_actions() {
+    let _clock = at(0);
+    let mut timelock = Timelock::new(TimelockAction::SetTimelockDelay { delay: 0 });
+    let mut stranger = TestAccount::signer(Pubkey::new_unique());
+    assert_eq!(
+        timelock.cancel(&mut stranger),
+        Err(moat_error(MoatError::Unauthorized))
+    );
+
+    let rent = timelock.pending.lamports;
+    let mut admin = TestAccount::signer(timelock.admin);
+    timelock.cancel(&mut admin).unwrap();
+    assert_eq!(admin.lamports, 1_000_000_000 + rent);
+    assert!(timelock.pending.data.is_empty());
+    // A cancelled action can't execute.
+    assert!(timelock.execute(&mut TestAccount::none()).is_err());
+}
@@ -7,7 +7,7 @@ describe("moat_registry", () => {
   it("Is initialized!", async () => {
     // Add your test here.
     const program = anchor.workspace.moatRegistry;
-    const tx = await program.methods.initialize().rpc();
+    const tx = await program.methods.initialize(anchor.web3.PublicKey.default).rpc();
     console.log("Your transaction signature", tx);
   });
 });